@@ -0,0 +1,489 @@
+use glam::*;
+use oorandom::Rand32;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use crate::input::InputState;
+use crate::level::ROCKET_VEL;
+use crate::physics::{check_cursor_bound, clamp_cursor, vec_from_angle};
+use crate::simulation::{Point2, GROUND_HEIGHT};
+
+// Rockets fly in one of three perspective layers
+pub const DEPTH_FAR: f32 = 0.7;
+pub const DEPTH_MID: f32 = 1.0;
+pub const DEPTH_NEAR: f32 = 1.3;
+
+const CHAOS_ROCKET_PERIOD: f32 = 0.5;
+
+const HOMING_VEL: f32 = 60.0;
+const HOMING_TURN_RATE: f32 = 1.2; // radians per second
+
+// rockets come in at part speed and pick up more the longer they fly, full
+// speed after 2 seconds and on up to a cap
+const ROCKET_START_SPEED: f32 = 0.8;
+const ROCKET_VELOCITY_RAMP: f32 = 0.1; // of full speed, every second
+const ROCKET_TOP_SPEED: f32 = 1.5;
+
+const WEAVE_FREQUENCY: f32 = 3.0; // radians per second
+const WEAVE_AMPLITUDE: f32 = 40.0; // pixels either side of the straight path
+
+pub const CURSOR_VEL: f32 = 600.0;
+const AXIS_EPSILON: f32 = 0.001;
+pub const CURSOR_WIDTH: f32 = 20.0;
+pub const CURSOR_HEIGHT: f32 = 5.0;
+
+const ROCKET_WIDTH: f32 = 7.5;
+const ROCKET_HEIGHT: f32 = 7.5;
+const TINY_ROCKET_WIDTH: f32 = 3.0;
+const TINY_ROCKET_HEIGHT: f32 = 3.0;
+
+const ROCKET_LIFE: f32 = 1.0;
+pub const GROUND_LIFE: f32 = 5.0;
+pub const BASE_LIFE: f32 = 1.0; // any rocket landing close enough wrecks a base
+
+pub const INTERCEPTOR_BASE_RADIUS: f32 = 20.0;
+pub const INTERCEPTOR_PERIOD: f32 = 5.0;
+// the explosion radius curve peaks at this multiple of the base radius
+pub const INTERCEPTOR_PEAK_SCALE: f32 = 2.5;
+pub const INTERCEPTOR_TRAVEL_SPEED: f32 = 300.0; // from the base to the cursor
+const INTERCEPTOR_ARRIVE_DIST: f32 = 5.0;
+pub const NUKE_RADIUS: f32 = 120.0; // base radius, in place of the interceptor's
+
+const WARP_GATE_RADIUS: f32 = 50.0;
+pub const WARP_GATE_TIME: f32 = 2.0;
+const WARP_GATE_SPIN: f32 = 4.0;
+
+pub const EMP_FUSE: f32 = 0.5; // seconds from firing to the pulse
+
+// Unique for the life of the program, so an actor can be looked up again
+// after others have been removed
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct ActorId(pub u64);
+
+static COUNTER: AtomicU64 = AtomicU64::new(0);
+
+pub fn next_id() -> ActorId {
+    ActorId(COUNTER.fetch_add(1, Ordering::Relaxed))
+}
+
+// the most rockets or interceptors there can be at once
+pub const MAX_ACTORS: usize = 1024;
+
+// Fixed storage for actors, allocated once up front so spawning doesn't
+// touch the heap. Slots are reused, so actors are found again by id.
+pub struct ActorPool {
+    slots: Box<[Option<Actor>]>,
+    in_use: usize, // every slot from here on is free
+    count: usize,
+}
+
+impl Default for ActorPool {
+    fn default() -> ActorPool {
+        ActorPool::new()
+    }
+}
+
+impl ActorPool {
+    pub fn new() -> ActorPool {
+        ActorPool {
+            slots: (0..MAX_ACTORS).map(|_| None).collect(),
+            in_use: 0,
+            count: 0,
+        }
+    }
+
+    // Put an actor in the first free slot. When the pool is full it is
+    // dropped, the screen is more than full already.
+    pub fn alloc(&mut self, actor: Actor) -> Option<&mut Actor> {
+        let index = self.slots.iter().position(|s| s.is_none())?;
+        self.in_use = self.in_use.max(index + 1);
+        self.count += 1;
+        Some(self.slots[index].insert(actor))
+    }
+
+    pub fn free(&mut self, index: usize) {
+        if self.slots[index].take().is_some() {
+            self.count -= 1;
+        }
+        while self.in_use > 0 && self.slots[self.in_use - 1].is_none() {
+            self.in_use -= 1;
+        }
+    }
+
+    // only the slots up to the last one in use are looked at, so a few
+    // actors in a big pool are quick to go through
+    pub fn active_iter(&self) -> impl Iterator<Item = &Actor> {
+        self.slots[..self.in_use].iter().flatten()
+    }
+
+    pub fn active_iter_mut(&mut self) -> impl Iterator<Item = &mut Actor> {
+        self.slots[..self.in_use].iter_mut().flatten()
+    }
+
+    pub fn get(&self, id: ActorId) -> Option<&Actor> {
+        self.active_iter().find(|a| a.id == id)
+    }
+
+    pub fn get_mut(&mut self, id: ActorId) -> Option<&mut Actor> {
+        self.active_iter_mut().find(|a| a.id == id)
+    }
+
+    pub fn contains(&self, id: ActorId) -> bool {
+        self.get(id).is_some()
+    }
+
+    pub fn len(&self) -> usize {
+        self.count
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.count == 0
+    }
+
+    // Free every actor that doesn't pass, in one pass over the slots
+    pub fn retain(&mut self, keep: impl Fn(&Actor) -> bool) {
+        for index in (0..self.in_use).rev() {
+            if self.slots[index].as_ref().is_some_and(|a| !keep(a)) {
+                self.free(index);
+            }
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.retain(|_| false);
+    }
+}
+
+#[derive(Debug)]
+pub struct Actor {
+    pub id: ActorId,
+    pub pos: Point2,
+    pub initial_pos: Point2,
+    pub target: Point2, // for interceptor in flight, where it goes off
+    pub angle: f32,
+    pub life: f32,
+    pub elapsed: f32, // for interceptor, and how long a rocket has flown
+    pub radius: f32,  // for interceptor
+    pub kind: RocketKind,
+    pub angle_change_timer: f32, // for chaos rocket
+    pub wall: bool,              // for wall of death rocket
+    pub formation_leader: bool,  // for rocket, the middle of a V formation
+    pub nuke: bool,              // for interceptor, bigger and slower blast
+    pub depth: f32,              // size and speed scale, bigger is closer
+    pub kills: u32,              // for interceptor
+    pub velocity_ramp: f32,      // for rocket, speed gained per second of flight
+    pub chain_bonus: f32,        // for interceptor, radius gained from merging others
+    pub radius_scale: f32,       // for interceptor, the size it was charged up to
+    pub merged: bool,            // for interceptor, went out by merging into another
+    pub fired: bool,             // for interceptor, false for ground hit explosions
+    pub turret: bool,            // for interceptor, fired by a turret and not a player
+    pub player2: bool,           // for interceptor, fired by the second player
+    // fired interceptors keep their launch base in initial_pos
+    pub target_city_index: Option<usize>, // for rocket, the city its lock-on line points at
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RocketKind {
+    Standard,
+    Chaos,
+    Tiny,
+    Splitter, // breaks in two when an explosion just misses it
+    Homing,   // turns toward the nearest cursor
+    Weaver,   // swings from side to side as it falls
+}
+
+impl RocketKind {
+    // fraction of the normal rocket velocity
+    pub fn speed_scale(&self) -> f32 {
+        match self {
+            RocketKind::Standard => 1.0,
+            RocketKind::Chaos => 0.6,
+            RocketKind::Tiny => 0.4,
+            RocketKind::Splitter => 0.8,
+            RocketKind::Homing => HOMING_VEL / ROCKET_VEL,
+            RocketKind::Weaver => 1.0,
+        }
+    }
+
+    pub fn score(&self) -> i32 {
+        match self {
+            RocketKind::Standard => 150,
+            RocketKind::Chaos => 250,
+            RocketKind::Tiny => 30,
+            RocketKind::Splitter => 200,
+            RocketKind::Homing => 200,
+            RocketKind::Weaver => 200,
+        }
+    }
+
+    // width and height of the rocket body
+    pub fn size(&self) -> (f32, f32) {
+        match self {
+            RocketKind::Tiny => (TINY_ROCKET_WIDTH, TINY_ROCKET_HEIGHT),
+            _ => (ROCKET_WIDTH, ROCKET_HEIGHT),
+        }
+    }
+}
+
+// What set off an explosion, each one is drawn as a different shape so they
+// can be told apart without relying on color
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ExplosionKind {
+    Interceptor,
+    GroundImpact,
+    Emp,
+}
+
+impl Actor {
+    // every actor starts from this, the constructors below fill in the rest
+    fn new(life: f32) -> Actor {
+        Actor {
+            id: next_id(),
+            pos: Point2::ZERO,
+            initial_pos: Point2::ZERO,
+            target: Point2::ZERO,
+            angle: 0.0,
+            life,
+            elapsed: 0.0,
+            radius: 0.0,
+            kind: RocketKind::Standard,
+            angle_change_timer: 0.0,
+            wall: false,
+            formation_leader: false,
+            nuke: false,
+            depth: DEPTH_MID,
+            kills: 0,
+            velocity_ramp: 0.0,
+            chain_bonus: 0.0,
+            radius_scale: 1.0,
+            merged: false,
+            fired: false,
+            turret: false,
+            player2: false,
+            target_city_index: None,
+        }
+    }
+
+    pub fn player_cursor() -> Actor {
+        Actor::new(GROUND_LIFE)
+    }
+
+    pub fn city() -> Actor {
+        Actor::new(GROUND_LIFE)
+    }
+
+    pub fn launch_base() -> Actor {
+        Actor::new(BASE_LIFE)
+    }
+
+    pub fn rocket() -> Actor {
+        Actor {
+            velocity_ramp: ROCKET_VELOCITY_RAMP,
+            ..Actor::new(ROCKET_LIFE)
+        }
+    }
+
+    pub fn warp_gate() -> Actor {
+        Actor {
+            elapsed: WARP_GATE_TIME,
+            radius: WARP_GATE_RADIUS,
+            ..Actor::new(ROCKET_LIFE)
+        }
+    }
+
+    pub fn emp() -> Actor {
+        Actor {
+            elapsed: EMP_FUSE,
+            ..Actor::new(ROCKET_LIFE)
+        }
+    }
+
+    pub fn interceptor() -> Actor {
+        Actor {
+            elapsed: INTERCEPTOR_PERIOD,
+            radius: INTERCEPTOR_BASE_RADIUS,
+            depth: DEPTH_FAR, // reaches every layer unless fired from the cursor
+            ..Actor::new(ROCKET_LIFE)
+        }
+    }
+
+    pub fn nuke() -> Actor {
+        Actor {
+            radius: NUKE_RADIUS,
+            nuke: true,
+            fired: true,
+            ..Actor::interceptor()
+        }
+    }
+
+    // only interceptors the player fired count as shots, the rest are rockets
+    // blowing up on the ground
+    pub fn explosion_kind(&self) -> ExplosionKind {
+        if self.fired {
+            ExplosionKind::Interceptor
+        } else {
+            ExplosionKind::GroundImpact
+        }
+    }
+
+    // The cursor aims into the far layer at the top of the sky, the mid layer
+    // in the middle and the near layer just above the ground
+    pub fn cursor_depth(&self, screen_height: f32) -> f32 {
+        let ground = -screen_height / 2.0 + GROUND_HEIGHT;
+        let height = (self.pos.y - ground) / (screen_height - GROUND_HEIGHT);
+
+        if height > 2.0 / 3.0 {
+            DEPTH_FAR
+        } else if height > 1.0 / 3.0 {
+            DEPTH_MID
+        } else {
+            DEPTH_NEAR
+        }
+    }
+
+    // Move the cursor based on the input supplied, the mouse puts it straight
+    // where it points and the keys move it at vel
+    pub fn cursor_move(&mut self, x: f32, y: f32, input: &InputState, vel: f32, dt: f32) {
+        if let Some(target) = input.mouse {
+            self.pos = clamp_cursor(target, x, y);
+            return;
+        }
+
+        let (pos, in_bounds) = check_cursor_bound(self.pos, x, y);
+        self.pos = pos;
+        if in_bounds {
+            // normalized so holding two arrow keys isn't faster than one, a
+            // stick pushed part way still moves slower though
+            let axis = Vec2::new(input.xaxis, input.yaxis);
+            let length = axis.length();
+            if length > AXIS_EPSILON {
+                self.pos += axis / length * length.min(1.0) * vel * dt;
+            }
+        }
+    }
+
+    // How much of its full speed a rocket is flying at, it speeds up as it
+    // goes
+    pub fn speed_ramp(&self) -> f32 {
+        (ROCKET_START_SPEED + self.velocity_ramp * self.elapsed).min(ROCKET_TOP_SPEED)
+    }
+
+    // Move the rocket based on its angle and velocity, plus any lateral wind.
+    // A weaver's sideways swing goes on top, so it still makes its way down.
+    pub fn rocket_move(&mut self, vel: f32, wind: f32, dt: f32) {
+        let swing = (self.elapsed * WEAVE_FREQUENCY).sin();
+        let vel = vel * self.speed_ramp();
+        self.elapsed += dt;
+
+        self.pos += vec_from_angle(self.angle) * vel * dt;
+        self.pos.x += wind * dt;
+        if self.kind == RocketKind::Weaver {
+            // only the change in the offset since last frame, so the swing
+            // doesn't pile up
+            self.pos.x += ((self.elapsed * WEAVE_FREQUENCY).sin() - swing) * WEAVE_AMPLITUDE;
+        }
+    }
+
+    // Chaos rockets pick a brand new heading every so often
+    pub fn chaos_rocket_steer(&mut self, rng: &mut Rand32, dt: f32) {
+        self.angle_change_timer -= dt;
+        if self.angle_change_timer <= 0.0 {
+            self.angle_change_timer = CHAOS_ROCKET_PERIOD;
+            self.angle = (rng.rand_float() * 2.0 - 1.0) * std::f32::consts::PI;
+        }
+    }
+
+    // Homing rockets turn toward the target, but only so fast, so they can
+    // still be dodged
+    pub fn homing_rocket_steer(&mut self, target: Point2, dt: f32) {
+        use std::f32::consts::{PI, TAU};
+
+        let to_target = target - self.pos;
+        // angles here are measured from straight up, see vec_from_angle
+        let wanted = to_target.x.atan2(to_target.y);
+        let turn = (wanted - self.angle + PI).rem_euclid(TAU) - PI;
+        let max_turn = HOMING_TURN_RATE * dt;
+        self.angle += turn.clamp(-max_turn, max_turn);
+    }
+
+    pub fn start_chaos_timer(&mut self) {
+        self.angle_change_timer = CHAOS_ROCKET_PERIOD;
+    }
+
+    // Keep track of the lifetime of each interceptor, in order to
+    // facilitate the explosion animation and keep track of lifetime
+    pub fn interceptor_elapse(&mut self, base_radius: f32, dt: f32) {
+        self.elapsed -= dt * 3.0; // make it a tad faster
+
+        // https://www.desmos.com/calculator/rwux8jpeud
+        // Model explosion radius with this function I randomly came up with
+        // by messing around in desmos until it had the behavior I wanted
+        self.radius = base_radius
+            * (-(((self.elapsed - 2.5) * (self.elapsed - 2.5)) / 2.5) + INTERCEPTOR_PEAK_SCALE)
+            + self.chain_bonus;
+    }
+
+    // Fly a launched interceptor straight at its target like a rocket flies
+    // at the ground. Returns true once it is close enough to go off.
+    pub fn interceptor_move(&mut self, speed: f32, dt: f32) -> bool {
+        let to_target = self.target - self.pos;
+        if to_target.length() <= INTERCEPTOR_ARRIVE_DIST.max(speed * dt) {
+            self.pos = self.target;
+            return true;
+        }
+        self.pos += to_target.normalize() * speed * dt;
+        false
+    }
+
+    // The explosion a launched interceptor sets off at its target, with a
+    // new id so it bursts into particles like any other
+    pub fn detonation(&self) -> Actor {
+        Actor {
+            pos: self.target,
+            initial_pos: self.initial_pos,
+            depth: self.depth,
+            radius_scale: self.radius_scale,
+            fired: true,
+            player2: self.player2,
+            ..Actor::interceptor()
+        }
+    }
+
+    // Count down the warp gate's lifetime and spin it for the animation
+    pub fn warp_gate_elapse(&mut self, dt: f32) {
+        self.elapsed -= dt;
+        self.angle += WARP_GATE_SPIN * dt;
+    }
+
+    // Meteors and rain drops just fall at a fixed speed, nothing can stop them
+    pub fn weather_move(&mut self, vel: f32, dt: f32) {
+        self.pos += vec_from_angle(self.angle) * vel * dt;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // one second of movement from the center of a screen big enough that
+    // the cursor never reaches an edge
+    fn distance_moved(xaxis: f32, yaxis: f32) -> f32 {
+        let input = InputState {
+            xaxis,
+            yaxis,
+            ..InputState::default()
+        };
+        let mut cursor = Actor::player_cursor();
+        for _ in 0..60 {
+            cursor.cursor_move(4000.0, 4000.0, &input, CURSOR_VEL, 1.0 / 60.0);
+        }
+        cursor.pos.length()
+    }
+
+    #[test]
+    fn diagonal_cursor_moves_at_cursor_speed() {
+        let straight = distance_moved(1.0, 0.0);
+        let diagonal = distance_moved(1.0, 1.0);
+        assert!((straight - CURSOR_VEL).abs() < 0.01);
+        assert!((diagonal - CURSOR_VEL).abs() < 0.01);
+    }
+}
@@ -0,0 +1,156 @@
+use ggez::audio::{self, SoundSource};
+use ggez::graphics::{self, FontData};
+use ggez::Context;
+use std::collections::HashMap;
+use std::fmt;
+
+// ggez registers its built in font under this name, adding ours with the
+// same name makes every Text use it
+const DEFAULT_FONT: &str = "LiberationMono-Regular";
+
+// The sound effects ship in the assets folder, the font and music are
+// optional extras in the resources folder. Paths are relative to either.
+pub const ASSETS_DIR: &str = "assets";
+const FONT_PATH: &str = "/font.ttf";
+const MUSIC_PATH: &str = "/music.ogg";
+const SOUND_PATHS: [(SoundId, &str); 3] = [
+    (SoundId::Fire, "/sounds/fire.ogg"),
+    (SoundId::Intercept, "/sounds/intercept.ogg"),
+    (SoundId::Impact, "/sounds/impact.ogg"),
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum SoundId {
+    Fire,
+    Intercept,
+    Impact,
+}
+
+// A resource that failed to load. The game can't run without critical ones,
+// the rest are skipped.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AssetError {
+    pub path: String,
+    pub reason: String,
+    pub critical: bool,
+}
+
+impl fmt::Display for AssetError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "could not load {}: {}", self.path, self.reason)
+    }
+}
+
+impl std::error::Error for AssetError {}
+
+// Every file the game loads. None of them are needed to play, a missing one
+// is reported and the game carries on without it.
+pub struct Assets {
+    pub font: Option<FontData>,
+    pub sounds: HashMap<SoundId, audio::Source>,
+    pub music: Option<audio::Source>,
+}
+
+// Try a single resource, recording the failure instead of giving up
+fn try_load<T>(
+    errors: &mut Vec<AssetError>,
+    path: &str,
+    critical: bool,
+    result: ggez::GameResult<T>,
+) -> Option<T> {
+    match result {
+        Ok(resource) => Some(resource),
+        Err(e) => {
+            errors.push(AssetError {
+                path: path.to_string(),
+                reason: e.to_string(),
+                critical,
+            });
+            None
+        }
+    }
+}
+
+impl Assets {
+    // Load everything, then report all the failures at once
+    pub fn load(ctx: &mut Context, volume: f32) -> Result<Assets, AssetError> {
+        let mut errors = Vec::new();
+
+        let font = try_load(
+            &mut errors,
+            FONT_PATH,
+            false,
+            FontData::from_path(ctx, FONT_PATH),
+        );
+        let mut music = try_load(
+            &mut errors,
+            MUSIC_PATH,
+            false,
+            audio::Source::new(ctx, MUSIC_PATH),
+        );
+        if let Some(music) = &mut music {
+            music.set_volume(volume);
+        }
+
+        let mut sounds = HashMap::new();
+        for (id, path) in SOUND_PATHS {
+            if let Some(mut sound) =
+                try_load(&mut errors, path, false, audio::Source::new(ctx, path))
+            {
+                sound.set_volume(volume);
+                sounds.insert(id, sound);
+            }
+        }
+
+        for e in &errors {
+            println!("Missing resource, {}", e);
+        }
+        if let Some(e) = errors.into_iter().find(|e| e.critical) {
+            return Err(e);
+        }
+
+        Ok(Assets {
+            font,
+            sounds,
+            music,
+        })
+    }
+
+    pub fn set_volume(&mut self, volume: f32) {
+        for sound in self.sounds.values_mut().chain(&mut self.music) {
+            sound.set_volume(volume);
+        }
+    }
+
+    // Turn just the music down or back up, the sounds keep the volume
+    pub fn set_music_volume(&mut self, volume: f32) {
+        if let Some(music) = &mut self.music {
+            music.set_volume(volume);
+        }
+    }
+
+    // Swap the default font for ours, if it loaded
+    pub fn install_font(&mut self, gfx: &mut graphics::GraphicsContext) {
+        if let Some(font) = self.font.take() {
+            gfx.add_font(DEFAULT_FONT, font);
+        }
+    }
+
+    pub fn play_music(&mut self, ctx: &Context) {
+        if let Some(music) = &mut self.music {
+            music.set_repeat(true);
+            if let Err(e) = music.play(ctx) {
+                println!("Could not play music: {}", e);
+            }
+        }
+    }
+
+    // Missing sounds are silent
+    pub fn play_sound(&mut self, ctx: &Context, id: SoundId) {
+        if let Some(sound) = self.sounds.get_mut(&id) {
+            if let Err(e) = sound.play_detached(ctx) {
+                println!("Could not play sound: {}", e);
+            }
+        }
+    }
+}
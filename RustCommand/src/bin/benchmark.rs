@@ -0,0 +1,7 @@
+// Time 100 levels on the default seed and window size with no window, like
+// the game's --benchmark option. The last line of output is the JSON summary.
+use rust_command::headless::run_benchmark;
+
+fn main() {
+    run_benchmark();
+}
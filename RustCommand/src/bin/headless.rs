@@ -0,0 +1,28 @@
+// Play one game with no window and nobody at the controls and print the
+// final score, like the game's --headless option. It takes the same
+// --chaos, --timed and --seed options, and reads config.toml.
+use rust_command::config::Config;
+use rust_command::headless::{headless_game, run_headless};
+use rust_command::simulation::{GameMode, SINGLE_PLAYER_SEED};
+
+fn main() {
+    let args: Vec<String> = std::env::args().collect();
+    let config = Config::from_args(&args);
+
+    let mode = if args.iter().any(|arg| arg == "--chaos") {
+        GameMode::Chaos
+    } else if args.iter().any(|arg| arg == "--timed") {
+        GameMode::TimedChallenge
+    } else {
+        GameMode::Normal
+    };
+    let seed = args
+        .iter()
+        .position(|arg| arg == "--seed")
+        .and_then(|i| args.get(i + 1))
+        .map_or(SINGLE_PLAYER_SEED, |seed| {
+            seed.parse().expect("--seed takes a whole number")
+        });
+
+    run_headless(&mut headless_game(&config, mode, seed));
+}
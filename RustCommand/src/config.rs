@@ -0,0 +1,523 @@
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+
+use crate::input::{key_from_name, key_name, KeyBindings};
+use crate::scoring::MISS_PENALTY;
+use crate::ui::HudLayout;
+
+const DEFAULT_WINDOW_WIDTH: f32 = 1280.0;
+const DEFAULT_WINDOW_HEIGHT: f32 = 760.0;
+const DEFAULT_MASTER_VOLUME: f32 = 0.8;
+
+// the game can be drawn this many times smaller and blown back up for big
+// chunky pixels
+const PIXEL_SCALES: [u32; 3] = [1, 2, 4];
+
+const CONFIG_FILE: &str = "config.toml";
+
+// anything outside this range is a typo or won't fit the HUD and play area
+const MIN_WINDOW_WIDTH: f32 = 640.0;
+const MIN_WINDOW_HEIGHT: f32 = 480.0;
+const MAX_WINDOW_SIZE: f32 = 8192.0;
+
+// Player options that don't change the rules of the game. The command line
+// flags are skipped, the rest is what the settings file holds.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    // disable animations and flashing for players with vestibular
+    // disorders or photosensitivity
+    #[serde(skip)]
+    pub reduced_motion: bool,
+    #[serde(skip)]
+    pub night_vision: bool,
+    #[serde(skip)]
+    pub debug: bool, // start with the debug overlay up
+    #[serde(skip)]
+    pub fixed_difficulty: bool, // keep the wave sizes the same however the player does
+    #[serde(skip)]
+    pub fixed_seed: bool, // --seed was given, so playing again keeps the same rockets
+    #[serde(skip)]
+    pub tutorial: bool, // show the tutorial even if it has been done
+    pub tutorial_done: bool, // saved once the tutorial has been finished
+    // the window is only made once, so these need a restart
+    #[serde(rename = "width")]
+    pub window_width: f32,
+    #[serde(rename = "height")]
+    pub window_height: f32,
+    pub fullscreen: bool,
+    #[serde(flatten)]
+    pub runtime: RuntimeConfig,
+}
+
+// The settings file options that F5 can reload while the game is running
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RuntimeConfig {
+    // blue and orange instead of red and green, with numbers and shapes
+    // backing up the colors
+    pub colorblind_mode: bool,
+    pub master_volume: f32, // every sound and the music play at this volume
+    pub pixel_scale: u32,   // 1 draws at the full window resolution
+    pub waste_penalty: i32, // points lost for a shot that hits nothing, 0 turns it off
+    // the [keys] and [hud] tables, which have to come after the plain values
+    #[serde(rename = "keys", with = "key_table")]
+    pub bindings: KeyBindings,
+    pub hud: HudLayout,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            reduced_motion: false,
+            night_vision: false,
+            debug: false,
+            fixed_difficulty: false,
+            fixed_seed: false,
+            tutorial: false,
+            tutorial_done: false,
+            window_width: DEFAULT_WINDOW_WIDTH,
+            window_height: DEFAULT_WINDOW_HEIGHT,
+            fullscreen: false,
+            runtime: RuntimeConfig::default(),
+        }
+    }
+}
+
+impl Default for RuntimeConfig {
+    fn default() -> Self {
+        RuntimeConfig {
+            colorblind_mode: false,
+            master_volume: DEFAULT_MASTER_VOLUME,
+            pixel_scale: 1,
+            waste_penalty: MISS_PENALTY,
+            bindings: KeyBindings::default(),
+            hud: HudLayout::default(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ConfigError {
+    // the file isn't TOML or a value is the wrong type
+    Parse(String),
+    InvalidField {
+        field: String,
+        value: f32,
+        reason: String,
+    },
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ConfigError::Parse(reason) => write!(f, "could not read {}: {}", CONFIG_FILE, reason),
+            ConfigError::InvalidField {
+                field,
+                value,
+                reason,
+            } => write!(f, "{} = {} is invalid: {}", field, value, reason),
+        }
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+fn invalid(field: &str, value: f32, reason: &str) -> ConfigError {
+    ConfigError::InvalidField {
+        field: field.to_string(),
+        value,
+        reason: reason.to_string(),
+    }
+}
+
+// Window dimensions have to be finite, positive and within the size range
+fn check_dimension(field: &str, value: f32, min: f32) -> Result<(), ConfigError> {
+    if !value.is_finite() || value <= 0.0 {
+        return Err(invalid(field, value, "must be a positive number"));
+    }
+    if value < min {
+        return Err(invalid(field, value, &format!("must be at least {}", min)));
+    }
+    if value > MAX_WINDOW_SIZE {
+        return Err(invalid(
+            field,
+            value,
+            &format!("must be at most {}", MAX_WINDOW_SIZE),
+        ));
+    }
+    Ok(())
+}
+
+// A key binding in the [keys] table is `action = "KeyName"`. A key that
+// isn't recognized leaves the default binding in place.
+fn parse_binding(action: &str, value: &str, bindings: &mut KeyBindings) {
+    let Some(binding) = bindings.named_mut(action) else {
+        println!("Ignoring unknown key binding {} in {}", action, CONFIG_FILE);
+        return;
+    };
+    match key_from_name(value) {
+        Some(key) => *binding = key,
+        None => println!(
+            "Unknown key {} for {} in {}, keeping {}",
+            value,
+            action,
+            CONFIG_FILE,
+            key_name(*binding)
+        ),
+    }
+}
+
+// The [keys] table goes by the names in named() and key_name, so it reads
+// the same as the controls in the README
+mod key_table {
+    use serde::{Deserialize, Deserializer, Serializer};
+    use std::collections::BTreeMap;
+
+    use super::parse_binding;
+    use crate::input::{key_name, KeyBindings};
+
+    pub fn serialize<S: Serializer>(bindings: &KeyBindings, s: S) -> Result<S::Ok, S::Error> {
+        s.collect_map(
+            bindings
+                .named()
+                .into_iter()
+                .map(|(action, key)| (action, key_name(key))),
+        )
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(d: D) -> Result<KeyBindings, D::Error> {
+        let mut bindings = KeyBindings::default();
+        for (action, key) in BTreeMap::<String, String>::deserialize(d)? {
+            parse_binding(&action, &key, &mut bindings);
+        }
+        Ok(bindings)
+    }
+}
+
+// The settings file, anything it leaves out keeps its default
+fn parse_settings(text: &str) -> Result<Config, ConfigError> {
+    toml::from_str(text).map_err(|e| ConfigError::Parse(e.to_string()))
+}
+
+// The settings file lives next to the executable, so every copy of the game
+// keeps its own
+fn config_path() -> PathBuf {
+    match std::env::current_exe() {
+        Ok(exe) => exe.with_file_name(CONFIG_FILE),
+        Err(_) => PathBuf::from(CONFIG_FILE),
+    }
+}
+
+fn unreadable(e: ConfigError) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, e)
+}
+
+impl Config {
+    fn to_toml(&self) -> String {
+        toml::to_string(self).expect("the settings are all plain values")
+    }
+
+    // Write a new HUD layout into the settings file. The rest comes from the
+    // file as it is, so options given on the command line don't get saved,
+    // and a file that can't be read is left alone rather than overwritten.
+    pub fn save_hud(hud: HudLayout) -> io::Result<()> {
+        let mut settings = Config::from_file().map_err(unreadable)?;
+        settings.runtime.hud = hud;
+        fs::write(config_path(), settings.to_toml())
+    }
+
+    // Note in the settings file that the tutorial has been finished
+    pub fn save_tutorial_done() -> io::Result<()> {
+        let mut settings = Config::from_file().map_err(unreadable)?;
+        settings.tutorial_done = true;
+        fs::write(config_path(), settings.to_toml())
+    }
+
+    // Read the settings file again for the options that can change without
+    // a restart. A file that doesn't pass validation changes nothing.
+    pub fn reload_runtime() -> Result<RuntimeConfig, ConfigError> {
+        let settings = Config::from_file()?;
+        settings.runtime.validate()?;
+        Ok(settings.runtime)
+    }
+
+    // Read the settings file, writing out the defaults if there isn't one
+    // yet so there is something to edit
+    fn from_file() -> Result<Config, ConfigError> {
+        let path = config_path();
+        match fs::read_to_string(&path) {
+            Ok(text) => parse_settings(&text),
+            Err(_) => {
+                let config = Config::default();
+                if let Err(e) = fs::write(&path, config.to_toml()) {
+                    println!("Could not write {}: {}", path.display(), e);
+                }
+                Ok(config)
+            }
+        }
+    }
+
+    // Read the options from the settings file and then the command line,
+    // which wins, falling back to the defaults for anything that doesn't
+    // pass validation
+    pub fn from_args(args: &[String]) -> Config {
+        let settings = Config::from_file().unwrap_or_else(|e| {
+            println!("Invalid config, using the defaults: {}", e);
+            Config::default()
+        });
+        let flag = |name: &str| args.iter().any(|arg| arg == name);
+        let number = |name: &str, default: f32| {
+            args.iter()
+                .position(|arg| arg == name)
+                .and_then(|i| args.get(i + 1))
+                .map_or(default, |value| value.parse().unwrap_or(f32::NAN))
+        };
+
+        let config = Config {
+            reduced_motion: flag("--reduced-motion"),
+            night_vision: flag("--night-vision"),
+            debug: flag("--debug"),
+            fixed_difficulty: flag("--fixed-difficulty"),
+            fixed_seed: flag("--seed"),
+            tutorial: flag("--tutorial"),
+            tutorial_done: settings.tutorial_done,
+            window_width: number("--width", settings.window_width),
+            window_height: number("--height", settings.window_height),
+            fullscreen: settings.fullscreen,
+            runtime: settings.runtime,
+        };
+
+        match config.validate() {
+            Ok(()) => config,
+            Err(e) => {
+                println!("Invalid config, using the defaults: {}", e);
+                // the flags can't be wrong, so keep those
+                Config {
+                    reduced_motion: config.reduced_motion,
+                    night_vision: config.night_vision,
+                    debug: config.debug,
+                    fixed_difficulty: config.fixed_difficulty,
+                    fixed_seed: config.fixed_seed,
+                    tutorial: config.tutorial,
+                    tutorial_done: config.tutorial_done,
+                    fullscreen: config.fullscreen,
+                    runtime: RuntimeConfig {
+                        colorblind_mode: config.runtime.colorblind_mode,
+                        bindings: config.runtime.bindings,
+                        ..RuntimeConfig::default()
+                    },
+                    ..Config::default()
+                }
+            }
+        }
+    }
+
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        check_dimension("window_width", self.window_width, MIN_WINDOW_WIDTH)?;
+        check_dimension("window_height", self.window_height, MIN_WINDOW_HEIGHT)?;
+        self.runtime.validate()
+    }
+}
+
+impl RuntimeConfig {
+    fn validate(&self) -> Result<(), ConfigError> {
+        if !(0.0..=1.0).contains(&self.master_volume) {
+            return Err(invalid(
+                "master_volume",
+                self.master_volume,
+                "must be between 0 and 1",
+            ));
+        }
+        if !PIXEL_SCALES.contains(&self.pixel_scale) {
+            return Err(invalid(
+                "pixel_scale",
+                self.pixel_scale as f32,
+                "must be 1, 2 or 4",
+            ));
+        }
+        if self.waste_penalty < 0 {
+            return Err(invalid(
+                "waste_penalty",
+                self.waste_penalty as f32,
+                "must be a whole number of points, 0 or more",
+            ));
+        }
+
+        if !self.hud.healthbar_pos.is_finite() {
+            return Err(invalid(
+                "hud.healthbar",
+                f32::NAN,
+                "must be two numbers, like [0, -46]",
+            ));
+        }
+        let fractions = [
+            ("hud.score", self.hud.score_pos),
+            ("hud.level", self.hud.level_pos),
+            ("hud.ammo", self.hud.ammo_pos),
+        ];
+        for (field, pos) in fractions {
+            for value in [pos.x, pos.y] {
+                if !(0.0..=1.0).contains(&value) {
+                    return Err(invalid(field, value, "must be between 0 and 1"));
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // The settings file text on top of the defaults, checked
+    fn validate(text: &str) -> Result<(), ConfigError> {
+        parse_settings(text)?.validate()
+    }
+
+    // The field a settings file is rejected for, if it is
+    fn rejected(text: &str) -> Option<String> {
+        match validate(text) {
+            Ok(()) => None,
+            Err(ConfigError::InvalidField { field, .. }) => Some(field),
+            Err(e) => panic!("{} should parse: {}", text, e),
+        }
+    }
+
+    fn unparsable(text: &str) -> bool {
+        matches!(parse_settings(text), Err(ConfigError::Parse(_)))
+    }
+
+    #[test]
+    fn defaults_are_valid() {
+        assert_eq!(validate(""), Ok(()));
+        assert_eq!(validate(&Config::default().to_toml()), Ok(()));
+    }
+
+    #[test]
+    fn settings_round_trip() {
+        let mut config = Config {
+            window_width: 1600.0,
+            fullscreen: true,
+            tutorial_done: true,
+            ..Config::default()
+        };
+        config.runtime.master_volume = 0.25;
+        config.runtime.pixel_scale = 4;
+        config.runtime.waste_penalty = 0;
+        config.runtime.bindings.fire = key_from_name("Return").unwrap();
+        config.runtime.hud.score_pos = glam::Vec2::new(0.25, 0.75);
+
+        let read = parse_settings(&config.to_toml()).unwrap();
+        assert_eq!(read.window_width, 1600.0);
+        assert!(read.fullscreen);
+        assert!(read.tutorial_done);
+        assert_eq!(read.runtime.master_volume, 0.25);
+        assert_eq!(read.runtime.pixel_scale, 4);
+        assert_eq!(read.runtime.waste_penalty, 0);
+        assert_eq!(key_name(read.runtime.bindings.fire), "Return");
+        assert_eq!(read.runtime.hud, config.runtime.hud);
+        // command line flags aren't settings
+        assert!(!config.to_toml().contains("reduced_motion"));
+    }
+
+    #[test]
+    fn wrong_types_are_errors() {
+        assert!(parse_settings("fullscreen = true").unwrap().fullscreen);
+        assert!(unparsable("fullscreen = yes"));
+        assert!(unparsable("fullscreen = \"true\""));
+        assert!(unparsable("width = wide"));
+        assert!(unparsable("master_volume = loud"));
+        assert!(unparsable("pixel_scale = 2.5"));
+        assert!(unparsable("waste_penalty = lots"));
+        assert!(unparsable("width 1280"));
+        assert!(unparsable("[keys]\nfire = Return"));
+    }
+
+    #[test]
+    fn window_size_limits() {
+        assert_eq!(rejected("width = 640\nheight = 480"), None);
+        assert_eq!(rejected("width = 8192\nheight = 8192"), None);
+        assert_eq!(
+            validate("width = 639"),
+            Err(invalid("window_width", 639.0, "must be at least 640"))
+        );
+        assert_eq!(
+            validate("height = 479"),
+            Err(invalid("window_height", 479.0, "must be at least 480"))
+        );
+        assert_eq!(
+            validate("width = 8193"),
+            Err(invalid("window_width", 8193.0, "must be at most 8192"))
+        );
+        assert_eq!(
+            validate("height = -760"),
+            Err(invalid(
+                "window_height",
+                -760.0,
+                "must be a positive number"
+            ))
+        );
+        assert_eq!(rejected("width = 0"), Some("window_width".to_string()));
+        assert_eq!(rejected("height = inf"), Some("window_height".to_string()));
+    }
+
+    #[test]
+    fn master_volume_limits() {
+        assert_eq!(rejected("master_volume = 0"), None);
+        assert_eq!(rejected("master_volume = 1"), None);
+        assert_eq!(
+            validate("master_volume = 1.01"),
+            Err(invalid("master_volume", 1.01, "must be between 0 and 1"))
+        );
+        assert_eq!(
+            validate("master_volume = -0.1"),
+            Err(invalid("master_volume", -0.1, "must be between 0 and 1"))
+        );
+    }
+
+    #[test]
+    fn pixel_scale_must_be_1_2_or_4() {
+        for scale in ["1", "2", "4"] {
+            assert_eq!(rejected(&format!("pixel_scale = {}", scale)), None);
+        }
+        assert_eq!(
+            validate("pixel_scale = 3"),
+            Err(invalid("pixel_scale", 3.0, "must be 1, 2 or 4"))
+        );
+    }
+
+    #[test]
+    fn waste_penalty_must_not_be_negative() {
+        assert_eq!(rejected("waste_penalty = 0"), None);
+        let reason = "must be a whole number of points, 0 or more";
+        assert_eq!(
+            validate("waste_penalty = -5"),
+            Err(invalid("waste_penalty", -5.0, reason))
+        );
+    }
+
+    #[test]
+    fn hud_positions() {
+        assert_eq!(
+            rejected("[hud]\nscore = [0, 1]\nhealthbar = [-20, 50]"),
+            None
+        );
+        assert_eq!(
+            validate("[hud]\nscore = [0.5, 1.5]"),
+            Err(invalid("hud.score", 1.5, "must be between 0 and 1"))
+        );
+        assert!(unparsable("[hud]\nammo = [0.5]"));
+        assert!(unparsable("[hud]\nhealthbar = [0, -46, 3]"));
+    }
+
+    // unknown settings and keys are skipped rather than rejected
+    #[test]
+    fn unknown_settings_are_ignored() {
+        assert_eq!(rejected("speed = 11\n[keys]\nfire = \"NoSuchKey\""), None);
+    }
+}
@@ -0,0 +1,95 @@
+use serde::{Deserialize, Serialize};
+use std::time::Instant;
+
+use crate::config::Config;
+use crate::simulation::{GameMode, GameSimulation, Simulation, DESIRED_FPS, SINGLE_PLAYER_SEED};
+
+// A game with nobody playing, set up from the config the same way a game
+// in the window would be
+pub fn headless_game(config: &Config, mode: GameMode, seed: u64) -> GameSimulation {
+    let mut sim = GameSimulation::new(config.window_width, config.window_height, mode, seed, false);
+    sim.adaptive = !config.fixed_difficulty;
+    sim.scoring.miss_penalty = config.runtime.waste_penalty;
+    sim
+}
+
+// Step a game at the normal frame rate until it ends, with no window and
+// nobody playing
+pub fn run_headless(sim: &mut impl Simulation) {
+    let seconds = 1.0 / (DESIRED_FPS as f32);
+    while !sim.is_over() {
+        sim.step(seconds);
+    }
+    println!("Final score: {} on level {}", sim.score(), sim.level());
+}
+
+const BENCHMARK_LEVELS: u32 = 100;
+
+// What a benchmark run prints, as one line of JSON
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BenchmarkReport {
+    pub levels: u32,
+    pub ticks: u64,
+    pub total_seconds: f64,
+    pub average_tick_us: f64,
+    pub peak_entities: usize,
+}
+
+// Time BENCHMARK_LEVELS levels and print the report. Nothing else goes to
+// stdout, so the output can be read straight into a JSON parser.
+pub fn run_benchmark() {
+    println!("{}", benchmark_output(BENCHMARK_LEVELS));
+}
+
+fn benchmark_output(levels: u32) -> String {
+    serde_json::to_string(&benchmark(levels)).expect("the report is plain numbers")
+}
+
+// Step through the given number of levels as fast as possible. Nobody
+// plays, so the cities fall early on and the rockets keep coming for the
+// rest. The seed and window size are fixed so every run simulates the same
+// game.
+pub fn benchmark(levels: u32) -> BenchmarkReport {
+    let config = Config::default();
+    let mut sim = GameSimulation::new(
+        config.window_width,
+        config.window_height,
+        GameMode::Normal,
+        SINGLE_PLAYER_SEED,
+        false,
+    );
+
+    let seconds = 1.0 / (DESIRED_FPS as f32);
+    let mut ticks: u64 = 0;
+    let mut peak_entities = 0;
+    let start = Instant::now();
+    while sim.level() <= levels {
+        sim.step(seconds);
+        ticks += 1;
+        peak_entities = peak_entities.max(sim.entity_count());
+    }
+    let total = start.elapsed().as_secs_f64();
+
+    BenchmarkReport {
+        levels,
+        ticks,
+        total_seconds: total,
+        average_tick_us: total * 1_000_000.0 / ticks as f64,
+        peak_entities,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn benchmark_output_is_one_json_object() {
+        let output = benchmark_output(2);
+        assert_eq!(output.lines().count(), 1);
+        let report: BenchmarkReport = serde_json::from_str(&output).unwrap();
+        assert_eq!(report.levels, 2);
+        assert!(report.ticks > 0);
+        assert!(report.peak_entities > 0);
+    }
+}
@@ -0,0 +1,391 @@
+use ggez::input::keyboard::KeyCode;
+use serde::{Deserialize, Serialize};
+
+use crate::simulation::Point2;
+
+const GAMEPAD_DEADZONE: f32 = 0.15;
+
+// Stick readings this close to center are noise from a stick at rest
+pub fn apply_deadzone(value: f32) -> f32 {
+    if value.abs() < GAMEPAD_DEADZONE {
+        0.0
+    } else {
+        value.clamp(-1.0, 1.0)
+    }
+}
+
+// Held state of the controls, read by the update loop every frame
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct InputState {
+    pub xaxis: f32,
+    pub yaxis: f32,
+    pub fire: bool,
+    pub trigger: f32, // gamepad fire rate trigger, 0.0 released to 1.0 fully pulled
+    pub mouse: Option<Point2>, // where the mouse put the cursor, in world coordinates
+}
+
+impl Default for InputState {
+    fn default() -> Self {
+        InputState {
+            xaxis: 0.0,
+            yaxis: 0.0,
+            fire: false,
+            trigger: 0.0,
+            mouse: None,
+        }
+    }
+}
+
+impl InputState {
+    // Movement and fire are held down, everything else is a one-shot action
+    // that the caller handles itself
+    pub fn press(&mut self, action: GameAction) {
+        // the arrow keys take over from wherever the mouse left the cursor
+        if matches!(
+            action,
+            GameAction::MoveUp
+                | GameAction::MoveDown
+                | GameAction::MoveLeft
+                | GameAction::MoveRight
+        ) {
+            self.mouse = None;
+        }
+
+        match action {
+            GameAction::MoveUp => self.yaxis = 1.0,
+            GameAction::MoveDown => self.yaxis = -1.0,
+            GameAction::MoveLeft => self.xaxis = -1.0,
+            GameAction::MoveRight => self.xaxis = 1.0,
+            GameAction::Fire => self.fire = true,
+            _ => (),
+        }
+    }
+
+    pub fn release(&mut self, action: GameAction) {
+        match action {
+            GameAction::MoveUp | GameAction::MoveDown => self.yaxis = 0.0,
+            GameAction::MoveLeft | GameAction::MoveRight => self.xaxis = 0.0,
+            GameAction::Fire => self.fire = false,
+            _ => (),
+        }
+    }
+}
+
+// Everything the player can ask the game to do from the keyboard
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GameAction {
+    MoveUp,
+    MoveDown,
+    MoveLeft,
+    MoveRight,
+    Fire,
+    CycleInterceptor,
+    ToggleCinematic,
+    ToggleNightVision,
+    ToggleFullscreen,
+    ToggleDebug,
+    ToggleHudEditor,
+    ReloadConfig,
+    StartCoop,
+    ShowStats,
+    ShowHighScores,
+    ContinueCheckpoint,
+    DeleteCheckpoint,
+    BuyUpgrade(usize), // which of the offers in the shop
+    RepairBase,
+    PlaceTurret,
+    FireNuke,
+    Pause,
+    Restart,
+    Quit, // pauses first, quits if already paused
+}
+
+// Which key triggers each action, the cursor can be moved with either of two
+#[derive(Debug, Clone)]
+pub struct KeyBindings {
+    pub move_up: KeyCode,
+    pub move_down: KeyCode,
+    pub move_left: KeyCode,
+    pub move_right: KeyCode,
+    pub alt_move_up: KeyCode,
+    pub alt_move_down: KeyCode,
+    pub alt_move_left: KeyCode,
+    pub alt_move_right: KeyCode,
+    pub fire: KeyCode,
+    pub player2_fire: KeyCode,
+    pub nuke: KeyCode,
+    pub start_coop: KeyCode,
+    pub show_stats: KeyCode,
+    pub show_high_scores: KeyCode,
+    pub continue_checkpoint: KeyCode,
+    pub delete_checkpoint: KeyCode,
+    pub upgrade_1: KeyCode,
+    pub upgrade_2: KeyCode,
+    pub upgrade_3: KeyCode,
+    pub repair_base: KeyCode,
+    pub place_turret: KeyCode,
+    pub cycle_interceptor: KeyCode,
+    pub toggle_cinematic: KeyCode,
+    pub toggle_night_vision: KeyCode,
+    pub toggle_fullscreen: KeyCode,
+    pub toggle_debug: KeyCode,
+    pub toggle_hud_editor: KeyCode,
+    pub reload_config: KeyCode,
+    pub pause: KeyCode,
+    pub restart: KeyCode,
+    pub quit: KeyCode,
+}
+
+impl Default for KeyBindings {
+    fn default() -> Self {
+        KeyBindings {
+            move_up: KeyCode::Up,
+            move_down: KeyCode::Down,
+            move_left: KeyCode::Left,
+            move_right: KeyCode::Right,
+            alt_move_up: KeyCode::W,
+            alt_move_down: KeyCode::S,
+            alt_move_left: KeyCode::A,
+            alt_move_right: KeyCode::D,
+            fire: KeyCode::Space,
+            player2_fire: KeyCode::Tab,
+            nuke: KeyCode::N,
+            start_coop: KeyCode::C,
+            show_stats: KeyCode::I,
+            show_high_scores: KeyCode::H,
+            continue_checkpoint: KeyCode::Return,
+            delete_checkpoint: KeyCode::Delete,
+            upgrade_1: KeyCode::Key1,
+            upgrade_2: KeyCode::Key2,
+            upgrade_3: KeyCode::Key3,
+            repair_base: KeyCode::Key4,
+            place_turret: KeyCode::T,
+            cycle_interceptor: KeyCode::Q,
+            toggle_cinematic: KeyCode::F6,
+            toggle_night_vision: KeyCode::V,
+            toggle_fullscreen: KeyCode::F11,
+            toggle_debug: KeyCode::F3,
+            toggle_hud_editor: KeyCode::F2,
+            reload_config: KeyCode::F5,
+            pause: KeyCode::P,
+            restart: KeyCode::R,
+            quit: KeyCode::Escape,
+        }
+    }
+}
+
+impl KeyBindings {
+    // Every binding with the name it has in the settings file
+    pub fn named(&self) -> [(&'static str, KeyCode); 31] {
+        [
+            ("move_up", self.move_up),
+            ("move_down", self.move_down),
+            ("move_left", self.move_left),
+            ("move_right", self.move_right),
+            ("alt_move_up", self.alt_move_up),
+            ("alt_move_down", self.alt_move_down),
+            ("alt_move_left", self.alt_move_left),
+            ("alt_move_right", self.alt_move_right),
+            ("fire", self.fire),
+            ("player2_fire", self.player2_fire),
+            ("nuke", self.nuke),
+            ("start_coop", self.start_coop),
+            ("show_stats", self.show_stats),
+            ("show_high_scores", self.show_high_scores),
+            ("continue_checkpoint", self.continue_checkpoint),
+            ("delete_checkpoint", self.delete_checkpoint),
+            ("upgrade_1", self.upgrade_1),
+            ("upgrade_2", self.upgrade_2),
+            ("upgrade_3", self.upgrade_3),
+            ("repair_base", self.repair_base),
+            ("place_turret", self.place_turret),
+            ("cycle_interceptor", self.cycle_interceptor),
+            ("toggle_cinematic", self.toggle_cinematic),
+            ("toggle_night_vision", self.toggle_night_vision),
+            ("toggle_fullscreen", self.toggle_fullscreen),
+            ("toggle_debug", self.toggle_debug),
+            ("toggle_hud_editor", self.toggle_hud_editor),
+            ("reload_config", self.reload_config),
+            ("pause", self.pause),
+            ("restart", self.restart),
+            ("quit", self.quit),
+        ]
+    }
+
+    // The binding with the given settings file name
+    pub fn named_mut(&mut self, name: &str) -> Option<&mut KeyCode> {
+        match name {
+            "move_up" => Some(&mut self.move_up),
+            "move_down" => Some(&mut self.move_down),
+            "move_left" => Some(&mut self.move_left),
+            "move_right" => Some(&mut self.move_right),
+            "alt_move_up" => Some(&mut self.alt_move_up),
+            "alt_move_down" => Some(&mut self.alt_move_down),
+            "alt_move_left" => Some(&mut self.alt_move_left),
+            "alt_move_right" => Some(&mut self.alt_move_right),
+            "fire" => Some(&mut self.fire),
+            "player2_fire" => Some(&mut self.player2_fire),
+            "nuke" => Some(&mut self.nuke),
+            "start_coop" => Some(&mut self.start_coop),
+            "show_stats" => Some(&mut self.show_stats),
+            "show_high_scores" => Some(&mut self.show_high_scores),
+            "continue_checkpoint" => Some(&mut self.continue_checkpoint),
+            "delete_checkpoint" => Some(&mut self.delete_checkpoint),
+            "upgrade_1" => Some(&mut self.upgrade_1),
+            "upgrade_2" => Some(&mut self.upgrade_2),
+            "upgrade_3" => Some(&mut self.upgrade_3),
+            "repair_base" => Some(&mut self.repair_base),
+            "place_turret" => Some(&mut self.place_turret),
+            "cycle_interceptor" => Some(&mut self.cycle_interceptor),
+            "toggle_cinematic" => Some(&mut self.toggle_cinematic),
+            "toggle_night_vision" => Some(&mut self.toggle_night_vision),
+            "toggle_fullscreen" => Some(&mut self.toggle_fullscreen),
+            "toggle_debug" => Some(&mut self.toggle_debug),
+            "toggle_hud_editor" => Some(&mut self.toggle_hud_editor),
+            "reload_config" => Some(&mut self.reload_config),
+            "pause" => Some(&mut self.pause),
+            "restart" => Some(&mut self.restart),
+            "quit" => Some(&mut self.quit),
+            _ => None,
+        }
+    }
+}
+
+// The keys that can be named in the settings file, by their ggez names
+const KEY_NAMES: [(&str, KeyCode); 63] = [
+    ("A", KeyCode::A),
+    ("B", KeyCode::B),
+    ("C", KeyCode::C),
+    ("D", KeyCode::D),
+    ("E", KeyCode::E),
+    ("F", KeyCode::F),
+    ("G", KeyCode::G),
+    ("H", KeyCode::H),
+    ("I", KeyCode::I),
+    ("J", KeyCode::J),
+    ("K", KeyCode::K),
+    ("L", KeyCode::L),
+    ("M", KeyCode::M),
+    ("N", KeyCode::N),
+    ("O", KeyCode::O),
+    ("P", KeyCode::P),
+    ("Q", KeyCode::Q),
+    ("R", KeyCode::R),
+    ("S", KeyCode::S),
+    ("T", KeyCode::T),
+    ("U", KeyCode::U),
+    ("V", KeyCode::V),
+    ("W", KeyCode::W),
+    ("X", KeyCode::X),
+    ("Y", KeyCode::Y),
+    ("Z", KeyCode::Z),
+    ("Key0", KeyCode::Key0),
+    ("Key1", KeyCode::Key1),
+    ("Key2", KeyCode::Key2),
+    ("Key3", KeyCode::Key3),
+    ("Key4", KeyCode::Key4),
+    ("Key5", KeyCode::Key5),
+    ("Key6", KeyCode::Key6),
+    ("Key7", KeyCode::Key7),
+    ("Key8", KeyCode::Key8),
+    ("Key9", KeyCode::Key9),
+    ("F1", KeyCode::F1),
+    ("F2", KeyCode::F2),
+    ("F3", KeyCode::F3),
+    ("F4", KeyCode::F4),
+    ("F5", KeyCode::F5),
+    ("F6", KeyCode::F6),
+    ("F7", KeyCode::F7),
+    ("F8", KeyCode::F8),
+    ("F9", KeyCode::F9),
+    ("F10", KeyCode::F10),
+    ("F11", KeyCode::F11),
+    ("F12", KeyCode::F12),
+    ("Up", KeyCode::Up),
+    ("Down", KeyCode::Down),
+    ("Left", KeyCode::Left),
+    ("Right", KeyCode::Right),
+    ("Space", KeyCode::Space),
+    ("Return", KeyCode::Return),
+    ("Escape", KeyCode::Escape),
+    ("Tab", KeyCode::Tab),
+    ("Back", KeyCode::Back),
+    ("Delete", KeyCode::Delete),
+    ("LShift", KeyCode::LShift),
+    ("RShift", KeyCode::RShift),
+    ("LControl", KeyCode::LControl),
+    ("RControl", KeyCode::RControl),
+    ("Pause", KeyCode::Pause),
+];
+
+pub fn key_from_name(name: &str) -> Option<KeyCode> {
+    KEY_NAMES
+        .iter()
+        .find(|(key_name, _)| *key_name == name)
+        .map(|(_, key)| *key)
+}
+
+pub fn key_name(key: KeyCode) -> &'static str {
+    KEY_NAMES
+        .iter()
+        .find(|(_, k)| *k == key)
+        .map_or("?", |(name, _)| name)
+}
+
+// In local co-op the alternate move keys and the second fire key belong to
+// player two, this is what they do for that player
+pub fn player2_action(keycode: KeyCode, bindings: &KeyBindings) -> Option<GameAction> {
+    let actions = [
+        (bindings.alt_move_up, GameAction::MoveUp),
+        (bindings.alt_move_down, GameAction::MoveDown),
+        (bindings.alt_move_left, GameAction::MoveLeft),
+        (bindings.alt_move_right, GameAction::MoveRight),
+        (bindings.player2_fire, GameAction::Fire),
+    ];
+
+    actions
+        .iter()
+        .find(|(key, _)| *key == keycode)
+        .map(|(_, action)| *action)
+}
+
+// Look up the action bound to a key, used for both presses and releases
+pub fn process_keydown(keycode: KeyCode, bindings: &KeyBindings) -> Option<GameAction> {
+    let actions = [
+        (bindings.move_up, GameAction::MoveUp),
+        (bindings.move_down, GameAction::MoveDown),
+        (bindings.move_left, GameAction::MoveLeft),
+        (bindings.move_right, GameAction::MoveRight),
+        (bindings.alt_move_up, GameAction::MoveUp),
+        (bindings.alt_move_down, GameAction::MoveDown),
+        (bindings.alt_move_left, GameAction::MoveLeft),
+        (bindings.alt_move_right, GameAction::MoveRight),
+        (bindings.fire, GameAction::Fire),
+        (bindings.nuke, GameAction::FireNuke),
+        (bindings.cycle_interceptor, GameAction::CycleInterceptor),
+        (bindings.toggle_cinematic, GameAction::ToggleCinematic),
+        (bindings.toggle_night_vision, GameAction::ToggleNightVision),
+        (bindings.toggle_fullscreen, GameAction::ToggleFullscreen),
+        (bindings.toggle_debug, GameAction::ToggleDebug),
+        (bindings.toggle_hud_editor, GameAction::ToggleHudEditor),
+        (bindings.reload_config, GameAction::ReloadConfig),
+        (bindings.start_coop, GameAction::StartCoop),
+        (bindings.show_stats, GameAction::ShowStats),
+        (bindings.show_high_scores, GameAction::ShowHighScores),
+        (bindings.continue_checkpoint, GameAction::ContinueCheckpoint),
+        (bindings.delete_checkpoint, GameAction::DeleteCheckpoint),
+        (bindings.upgrade_1, GameAction::BuyUpgrade(0)),
+        (bindings.upgrade_2, GameAction::BuyUpgrade(1)),
+        (bindings.upgrade_3, GameAction::BuyUpgrade(2)),
+        (bindings.repair_base, GameAction::RepairBase),
+        (bindings.place_turret, GameAction::PlaceTurret),
+        (bindings.pause, GameAction::Pause),
+        (bindings.restart, GameAction::Restart),
+        (bindings.quit, GameAction::Quit),
+    ];
+
+    actions
+        .iter()
+        .find(|(key, _)| *key == keycode)
+        .map(|(_, action)| *action)
+}
@@ -0,0 +1,196 @@
+use serde::{Deserialize, Serialize};
+use std::ops::Range;
+
+use crate::actor::{INTERCEPTOR_BASE_RADIUS, INTERCEPTOR_TRAVEL_SPEED};
+use crate::simulation::{WaveModifier, SHOT_TIMEOUT};
+use crate::wave_manager::ROCKET_DELAY;
+
+pub const LEVEL_TIME: f32 = 15.0;
+pub const ROCKET_VEL: f32 = 80.0;
+
+// Picked on the main menu before a game starts
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum DifficultyPreset {
+    Easy,
+    Normal,
+    Hard,
+}
+
+const PRESETS: [DifficultyPreset; 3] = [
+    DifficultyPreset::Easy,
+    DifficultyPreset::Normal,
+    DifficultyPreset::Hard,
+];
+
+impl DifficultyPreset {
+    pub fn name(&self) -> &'static str {
+        match self {
+            DifficultyPreset::Easy => "Easy",
+            DifficultyPreset::Normal => "Normal",
+            DifficultyPreset::Hard => "Hard",
+        }
+    }
+
+    fn index(&self) -> usize {
+        PRESETS.iter().position(|p| p == self).unwrap_or(1)
+    }
+
+    // left and right on the main menu, stopping at either end
+    pub fn easier(&self) -> DifficultyPreset {
+        PRESETS[self.index().saturating_sub(1)]
+    }
+
+    pub fn harder(&self) -> DifficultyPreset {
+        PRESETS[(self.index() + 1).min(PRESETS.len() - 1)]
+    }
+
+    pub fn settings(&self) -> DifficultySettings {
+        let normal = DifficultySettings {
+            rocket_vel: ROCKET_VEL,
+            rocket_delay: ROCKET_DELAY,
+            shot_timeout: SHOT_TIMEOUT,
+            level_time: LEVEL_TIME,
+            head_start: 0,
+            interceptor_speed: INTERCEPTOR_TRAVEL_SPEED,
+        };
+        match self {
+            DifficultyPreset::Easy => DifficultySettings {
+                rocket_vel: ROCKET_VEL * 0.5,
+                rocket_delay: ROCKET_DELAY * 2.0,
+                level_time: 20.0,
+                interceptor_speed: INTERCEPTOR_TRAVEL_SPEED * 1.1,
+                ..normal
+            },
+            DifficultyPreset::Normal => normal,
+            DifficultyPreset::Hard => DifficultySettings {
+                rocket_vel: ROCKET_VEL * 1.5,
+                rocket_delay: ROCKET_DELAY * 0.75,
+                level_time: 12.0,
+                head_start: 2,
+                interceptor_speed: INTERCEPTOR_TRAVEL_SPEED * 0.9,
+                ..normal
+            },
+        }
+    }
+}
+
+// The base values for a whole game, Difficulty scales them per level
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DifficultySettings {
+    pub rocket_vel: f32,
+    pub rocket_delay: f32, // between waves
+    pub shot_timeout: f32,
+    pub level_time: f32,
+    pub head_start: u32,        // new rocket types turn up this many levels early
+    pub interceptor_speed: f32, // from the base to the cursor, harder is slower
+}
+
+impl DifficultySettings {
+    // A timed challenge sends waves twice as often and moves up a level on
+    // a fixed clock, whatever the preset
+    pub fn timed_challenge(self) -> DifficultySettings {
+        DifficultySettings {
+            rocket_delay: self.rocket_delay / 2.0,
+            level_time: LEVEL_TIME,
+            ..self
+        }
+    }
+}
+
+// Scaling applied on top of the base values for the current level
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Difficulty {
+    pub rocket_speed: f32,
+    pub explosion_size: f32,
+}
+
+impl Difficulty {
+    fn for_modifier(modifier: WaveModifier) -> Difficulty {
+        let mut difficulty = Difficulty::default();
+        match modifier {
+            WaveModifier::FastRockets => difficulty.rocket_speed = 1.5,
+            WaveModifier::TinyExplosions => difficulty.explosion_size = 0.5,
+            _ => {}
+        }
+        difficulty
+    }
+}
+
+impl Default for Difficulty {
+    fn default() -> Self {
+        Difficulty {
+            rocket_speed: 1.0,
+            explosion_size: 1.0,
+        }
+    }
+}
+
+// Reported when the level timer runs out, the next level is already in
+// LevelState by then
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LevelUp {
+    pub cleared: u32,
+}
+
+// Which level is being played, how long is left of it and how hard it is
+#[derive(Debug)]
+pub struct LevelState {
+    pub level: u32,
+    pub timer: f32,
+    pub difficulty: Difficulty,
+    pub modifier: Option<WaveModifier>,
+    pub settings: DifficultySettings,
+}
+
+impl Default for LevelState {
+    fn default() -> LevelState {
+        LevelState::new()
+    }
+}
+
+impl LevelState {
+    pub fn new() -> LevelState {
+        LevelState {
+            level: 1,
+            timer: LEVEL_TIME,
+            difficulty: Difficulty::default(),
+            modifier: None,
+            settings: DifficultyPreset::Normal.settings(),
+        }
+    }
+
+    pub fn tick(&mut self, dt: f32) -> Option<LevelUp> {
+        self.timer -= dt;
+        if self.timer > 0.0 {
+            return None;
+        }
+        Some(self.finish())
+    }
+
+    // End the level early, whatever is left on the timer
+    pub fn finish(&mut self) -> LevelUp {
+        self.timer = self.settings.level_time;
+        self.level += 1;
+        LevelUp {
+            cleared: self.level - 1,
+        }
+    }
+
+    // modifiers don't stack, each one replaces the last
+    pub fn set_modifier(&mut self, modifier: WaveModifier) {
+        self.modifier = Some(modifier);
+        self.difficulty = Difficulty::for_modifier(modifier);
+    }
+
+    pub fn current_rocket_vel(&self) -> f32 {
+        self.settings.rocket_vel * self.difficulty.rocket_speed
+    }
+
+    pub fn current_interceptor_radius(&self) -> f32 {
+        INTERCEPTOR_BASE_RADIUS * self.difficulty.explosion_size
+    }
+
+    pub fn rockets_per_wave_range(&self) -> Range<u32> {
+        (1 + self.level)..(3 + self.level)
+    }
+}
@@ -0,0 +1,16 @@
+// Everything but the window, shared by the game and the tools in src/bin
+pub mod actor;
+pub mod config;
+pub mod headless;
+pub mod input;
+pub mod level;
+pub mod particles;
+pub mod physics;
+pub mod replay;
+pub mod save;
+pub mod scoring;
+pub mod simulation;
+pub mod tutorial;
+pub mod ui;
+pub mod upgrades;
+pub mod wave_manager;
@@ -1,490 +1,1282 @@
+use cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
 use ggez::conf;
-use ggez::event::{self, EventHandler};
-use ggez::glam::*;
-use ggez::graphics::{self, Color};
-use ggez::input::keyboard::KeyCode;
-use ggez::input::keyboard::KeyInput;
+use ggez::event::{self, Axis, Button, EventHandler};
+use ggez::glam::Vec2;
+use ggez::graphics;
+use ggez::input::gamepad::GamepadId;
+use ggez::input::keyboard::{KeyInput, KeyMods};
+use ggez::input::mouse::MouseButton;
 use ggez::timer;
-use ggez::{Context, ContextBuilder, GameResult};
+use ggez::winit::event::TouchPhase;
+use ggez::{Context, ContextBuilder, GameError, GameResult};
 use oorandom::Rand32;
+use std::net::{SocketAddr, UdpSocket};
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::Arc;
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
+
+mod assets;
+mod rendering;
+
+use rust_command::headless::{headless_game, run_benchmark, run_headless};
+use rust_command::{
+    actor, config, input, level, particles, physics, replay, save, simulation, tutorial, ui,
+    upgrades,
+};
+
+use actor::{ActorId, INTERCEPTOR_PEAK_SCALE};
+use assets::{Assets, SoundId};
+use config::Config;
+use input::{apply_deadzone, player2_action, process_keydown, GameAction, InputState, KeyBindings};
+use level::DifficultyPreset;
+use particles::{spawn_burst, update_particles, Particle};
+use physics::{screen_to_world_coords, world_to_screen_coords};
+use rendering::{
+    generate_skyline, letterbox_viewport, Palette, Renderer, DEFAULT_PALETTE, NIGHT_VISION_PALETTE,
+    SCANLINE_SHADER,
+};
+use replay::{load_replay, save_replay, Frame, Replay, ReplayStart};
+use save::{
+    autosave, delete_autosave, delete_checkpoint, first_launch, insert_high_score, load_checkpoint,
+    load_high_scores, load_stats, prompt_resume, save_checkpoint, save_high_scores, save_stats,
+    Checkpoint, HighScoreEntry, HighScoreTables, SaveState, Stats, AUTOSAVE_PERIOD,
+    CHECKPOINT_LEVELS,
+};
+use simulation::{
+    Command, GameMode, GameSimulation, Outcome, Point2, DESIRED_FPS, SINGLE_PLAYER_SEED,
+};
+use tutorial::TutorialStep;
+use ui::{HudElement, UiManager};
+
+const CHAOS_LEADERBOARD_FILE: &str = "chaos_leaderboard.txt";
+const CHAOS_LEADERBOARD_SIZE: usize = 10;
+
+// Add a finished chaos score to the leaderboard file, most negative first
+fn submit_chaos_score(score: i32) -> std::io::Result<Vec<i32>> {
+    let mut scores: Vec<i32> = match std::fs::read_to_string(CHAOS_LEADERBOARD_FILE) {
+        Ok(contents) => contents
+            .lines()
+            .filter_map(|line| line.trim().parse().ok())
+            .collect(),
+        Err(_) => Vec::new(), // no leaderboard yet
+    };
+
+    scores.push(score);
+    scores.sort();
+    scores.truncate(CHAOS_LEADERBOARD_SIZE);
+
+    let contents: Vec<String> = scores.iter().map(|s| s.to_string()).collect();
+    std::fs::write(CHAOS_LEADERBOARD_FILE, contents.join("\n"))?;
+    Ok(scores)
+}
 
-type Point2 = Vec2;
+const RADAR_MIN_RATE: f32 = 1.0; // beeps per second when rockets are far away
+const RADAR_MAX_RATE: f32 = 10.0; // beeps per second when rockets are at the ground
+const RADAR_BEEP_LENGTH: f32 = 0.04;
+const RADAR_TONE: f32 = 880.0;
+const RADAR_VOLUME: f32 = 0.1;
+
+// ggez only changes a sound's pitch the next time it is played, and the music
+// loops without being played again, so the danger the cities are in comes
+// through as a hi-hat that ticks faster the less of them is left
+const HIHAT_HEALTH: f32 = 0.5; // comes in once the cities are down to this much
+const HIHAT_MIN_RATE: f32 = 4.0; // ticks per second as it comes in
+const HIHAT_MAX_RATE: f32 = 12.0; // ticks per second with the cities nearly gone
+const HIHAT_LENGTH: f32 = 0.03;
+const HIHAT_VOLUME: f32 = 0.05;
+
+const MUSIC_DUCK: f32 = 0.3; // of the music volume while the EMP flash lasts
+
+// Proximity beeper, like a geiger counter for the lowest rocket. The distance
+// is shared with the audio thread as the bits of an f32, normalized so that 0.0
+// is the ground and 1.0 is the top of the screen. Anything above 1.0 is silent.
+// The city health is shared the same way, for the hi-hat.
+struct Radar {
+    distance: Arc<AtomicU32>,
+    health: Arc<AtomicU32>,
+}
 
-// convert an angle into a vector, from ggez example
+impl Radar {
+    fn start() -> Radar {
+        let distance = Arc::new(AtomicU32::new(f32::MAX.to_bits()));
+        let health = Arc::new(AtomicU32::new(1.0f32.to_bits()));
+        let thread_distance = distance.clone();
+        let thread_health = health.clone();
 
-fn vec_from_angle(angle: f32) -> Vec2 {
-    let vx = angle.sin();
-    let vy = angle.cos();
-    Vec2::new(vx, vy)
-}
+        std::thread::spawn(move || {
+            if let Err(e) = run_radar(thread_distance, thread_health) {
+                println!("Radar audio disabled: {}", e);
+            }
+        });
 
-// get screen coordinates from world coordinates, from ggez example
+        Radar { distance, health }
+    }
 
-fn world_to_screen_coords(screen_width: f32, screen_height: f32, point: Point2) -> Point2 {
-    let x = point.x + screen_width / 2.0;
-    let y = screen_height - (point.y + screen_height / 2.0);
-    Point2::new(x, y)
-}
+    fn set_distance(&self, distance: f32) {
+        self.distance.store(distance.to_bits(), Ordering::Relaxed);
+    }
 
-#[derive(Debug)]
-struct InputState {
-    xaxis: f32,
-    yaxis: f32,
-    fire: bool,
+    fn set_health(&self, health: f32) {
+        self.health.store(health.to_bits(), Ordering::Relaxed);
+    }
 }
 
-impl Default for InputState {
-    fn default() -> Self {
-        InputState {
-            xaxis: 0.0,
-            yaxis: 0.0,
-            fire: false,
-        }
+// Open the default output device and beep until the game exits
+fn run_radar(
+    distance: Arc<AtomicU32>,
+    health: Arc<AtomicU32>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let device = cpal::default_host()
+        .default_output_device()
+        .ok_or("no output device")?;
+    let config = device.default_output_config()?;
+    if config.sample_format() != cpal::SampleFormat::F32 {
+        return Err("output device doesn't take f32 samples".into());
     }
-}
 
-#[derive(Debug)]
-struct Actor {
-    pos: Point2,
-    initial_pos: Point2,
-    angle: f32,
-    life: f32,
-    elapsed: f32, // for interceptor
-    radius: f32,  // for interceptor
-}
+    let sample_rate = config.sample_rate().0 as f32;
+    let channels = config.channels() as usize;
+    let mut since_beep = 0.0; // seconds since the last beep started
+    let mut since_hihat = 0.0;
+    let mut noise = Rand32::new(0);
+
+    let stream = device.build_output_stream(
+        &config.into(),
+        move |data: &mut [f32], _: &cpal::OutputCallbackInfo| {
+            let dist = f32::from_bits(distance.load(Ordering::Relaxed));
+            let health = f32::from_bits(health.load(Ordering::Relaxed));
+
+            for frame in data.chunks_mut(channels) {
+                since_beep += 1.0 / sample_rate;
+
+                let mut sample = 0.0;
+                if dist <= 1.0 {
+                    let rate = RADAR_MAX_RATE - (RADAR_MAX_RATE - RADAR_MIN_RATE) * dist.max(0.0);
+                    if since_beep >= 1.0 / rate {
+                        since_beep = 0.0;
+                    }
+                    if since_beep < RADAR_BEEP_LENGTH {
+                        // square wave
+                        sample = if (since_beep * RADAR_TONE).fract() < 0.5 {
+                            RADAR_VOLUME
+                        } else {
+                            -RADAR_VOLUME
+                        };
+                    }
+                }
 
-const GROUND_HEIGHT: f32 = 150.0;
+                since_hihat += 1.0 / sample_rate;
+                if health < HIHAT_HEALTH {
+                    let danger = 1.0 - health.max(0.0) / HIHAT_HEALTH;
+                    let rate = HIHAT_MIN_RATE + (HIHAT_MAX_RATE - HIHAT_MIN_RATE) * danger;
+                    if since_hihat >= 1.0 / rate {
+                        since_hihat = 0.0;
+                    }
+                    if since_hihat < HIHAT_LENGTH {
+                        // a burst of noise that dies away
+                        let decay = 1.0 - since_hihat / HIHAT_LENGTH;
+                        sample += (noise.rand_float() * 2.0 - 1.0) * HIHAT_VOLUME * decay;
+                    }
+                }
 
-const CURSOR_VEL: f32 = 600.0;
-const CURSOR_WIDTH: f32 = 20.0;
-const CURSOR_HEIGHT: f32 = 5.0;
+                for channel in frame.iter_mut() {
+                    *channel = sample;
+                }
+            }
+        },
+        |e| println!("Radar audio error: {}", e),
+        None,
+    )?;
+    stream.play()?;
+
+    // the stream stops when dropped, so keep this thread around
+    loop {
+        std::thread::park();
+    }
+}
 
-const ROCKET_WIDTH: f32 = 7.5;
-const ROCKET_HEIGHT: f32 = 7.5;
+const NET_HELLO: &[u8] = b"RUSTCOMMAND_HELLO";
+const NET_PACKET_SIZE: usize = 18;
 
-const ROCKET_LIFE: f32 = 1.0;
-const GROUND_LIFE: f32 = 5.0;
+// Peer to peer link to the other player. The rockets are simulated on both
+// machines from the same seed, so only the cursors travel over the wire.
+struct NetSession {
+    socket: UdpSocket,
+    peer: SocketAddr,
+    player_id: u8,
+}
 
-const INTERCEPTOR_BASE_RADIUS: f32 = 20.0;
-const INTERCEPTOR_PERIOD: f32 = 5.0;
+// Input and cursor position of one player for one frame
+struct NetPacket {
+    player_id: u8,
+    input: InputState,
+    pos: Point2,
+}
 
-fn create_player_cursor() -> Actor {
-    Actor {
-        pos: Point2::ZERO,
-        initial_pos: Point2::ZERO,
-        angle: 0.0,
-        life: GROUND_LIFE,
-        elapsed: 0.0,
-        radius: 0.0,
+impl NetPacket {
+    fn to_bytes(&self) -> [u8; NET_PACKET_SIZE] {
+        let mut bytes = [0; NET_PACKET_SIZE];
+        bytes[0] = self.player_id;
+        bytes[1..5].copy_from_slice(&self.input.xaxis.to_le_bytes());
+        bytes[5..9].copy_from_slice(&self.input.yaxis.to_le_bytes());
+        bytes[9] = self.input.fire as u8;
+        bytes[10..14].copy_from_slice(&self.pos.x.to_le_bytes());
+        bytes[14..18].copy_from_slice(&self.pos.y.to_le_bytes());
+        bytes
     }
-}
 
-fn create_rocket() -> Actor {
-    Actor {
-        pos: Point2::ZERO,
-        initial_pos: Point2::ZERO,
-        angle: 0.0,
-        life: ROCKET_LIFE,
-        elapsed: 0.0,
-        radius: 0.0,
+    fn from_bytes(bytes: &[u8]) -> Option<NetPacket> {
+        if bytes.len() != NET_PACKET_SIZE {
+            return None;
+        }
+        let f32_at = |i: usize| f32::from_le_bytes(bytes[i..i + 4].try_into().unwrap());
+
+        Some(NetPacket {
+            player_id: bytes[0],
+            input: InputState {
+                xaxis: f32_at(1),
+                yaxis: f32_at(5),
+                fire: bytes[9] != 0,
+                trigger: 0.0,
+                mouse: None,
+            },
+            pos: Point2::new(f32_at(10), f32_at(14)),
+        })
     }
 }
 
-fn create_interceptor() -> Actor {
-    Actor {
-        pos: Point2::ZERO,
-        initial_pos: Point2::ZERO,
-        angle: 0.0,
-        life: ROCKET_LIFE,
-        elapsed: INTERCEPTOR_PERIOD,
-        radius: INTERCEPTOR_BASE_RADIUS,
+impl NetSession {
+    // Wait for someone to join on the given port, then send them the seed
+    fn host(port: u16) -> std::io::Result<(NetSession, u64)> {
+        let socket = UdpSocket::bind(("0.0.0.0", port))?;
+        println!("Waiting for player 2 on port {}...", port);
+
+        let mut buf = [0; 64];
+        let peer = loop {
+            let (len, from) = socket.recv_from(&mut buf)?;
+            if &buf[..len] == NET_HELLO {
+                break from;
+            }
+        };
+
+        let mut seed = [0; 8];
+        getrandom::getrandom(&mut seed).map_err(|e| std::io::Error::other(e.to_string()))?;
+        socket.send_to(&seed, peer)?;
+        println!("Player 2 joined from {}", peer);
+
+        socket.set_nonblocking(true)?;
+        let session = NetSession {
+            socket,
+            peer,
+            player_id: 1,
+        };
+        Ok((session, u64::from_le_bytes(seed)))
     }
-}
 
-const ROCKET_VEL: f32 = 80.0;
-const ROCKET_DELAY: f32 = 4.0;
-const SHOT_TIMEOUT: f32 = 0.5;
+    // Say hello to the host and wait for the seed it picked
+    fn join(addr: &str) -> std::io::Result<(NetSession, u64)> {
+        let socket = UdpSocket::bind("0.0.0.0:0")?;
+        let peer: SocketAddr = addr
+            .parse()
+            .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidInput, e))?;
+
+        socket.set_read_timeout(Some(std::time::Duration::from_secs(1)))?;
+        let mut seed = [0; 8];
+        loop {
+            println!("Joining {}...", peer);
+            socket.send_to(NET_HELLO, peer)?;
+            match socket.recv_from(&mut seed) {
+                Ok((8, from)) if from == peer => break,
+                Ok(_) => continue,
+                Err(e)
+                    if e.kind() == std::io::ErrorKind::WouldBlock
+                        || e.kind() == std::io::ErrorKind::TimedOut =>
+                {
+                    continue
+                }
+                Err(e) => return Err(e),
+            }
+        }
 
-// Prevents the cursor from going out of bounds
-fn check_cursor_bound(actor: &mut Actor, x: f32, y: f32) -> bool {
-    let screen_x = x / 2.0;
-    let screen_y = y / 2.0;
+        socket.set_nonblocking(true)?;
+        let session = NetSession {
+            socket,
+            peer,
+            player_id: 2,
+        };
+        Ok((session, u64::from_le_bytes(seed)))
+    }
 
-    if actor.pos.x + CURSOR_WIDTH > screen_x {
-        // can't let the cursor get stuck, so adjust for each case
-        actor.pos -= Vec2::new(1.0, 0.0);
-        return false;
-    } else if actor.pos.x < -screen_x {
-        actor.pos += Vec2::new(1.0, 0.0);
-        return false;
+    fn send(&self, input: &InputState, pos: Point2) {
+        let packet = NetPacket {
+            player_id: self.player_id,
+            input: InputState {
+                xaxis: input.xaxis,
+                yaxis: input.yaxis,
+                fire: input.fire,
+                // the trigger only sets the local player's fire rate
+                trigger: 0.0,
+                // the cursor position is sent on its own
+                mouse: None,
+            },
+            pos,
+        };
+        // a dropped frame of input isn't worth stopping the game for
+        let _ = self.socket.send_to(&packet.to_bytes(), self.peer);
     }
 
-    if actor.pos.y > screen_y {
-        actor.pos -= Vec2::new(0.0, 1.0);
-        return false;
-    } else if actor.pos.y - CURSOR_HEIGHT < -screen_y + GROUND_HEIGHT {
-        actor.pos += Vec2::new(0.0, 1.0);
-        return false;
+    // Drain everything that arrived since last frame, keeping the newest
+    fn receive(&self) -> Option<NetPacket> {
+        let mut latest = None;
+        let mut buf = [0; 64];
+        while let Ok((len, from)) = self.socket.recv_from(&mut buf) {
+            if from != self.peer {
+                continue;
+            }
+            if let Some(packet) = NetPacket::from_bytes(&buf[..len]) {
+                if packet.player_id != self.player_id {
+                    latest = Some(packet);
+                }
+            }
+        }
+        latest
     }
-    true
 }
 
-// Move the cursor based on the input supplied
-fn cursor_move(actor: &mut Actor, x: f32, y: f32, input: &InputState, dt: f32) {
-    if check_cursor_bound(actor, x, y) {
-        actor.pos += Vec2::new(input.xaxis * CURSOR_VEL * dt, input.yaxis * CURSOR_VEL * dt);
+const BURST_TRIGGER: f32 = 0.9; // how far the right trigger has to be pulled
+
+// A new seed for every game played again after the first, so R doesn't
+// send the same rockets twice
+fn fresh_seed() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(SINGLE_PLAYER_SEED, |time| time.as_nanos() as u64)
+}
+
+const USAGE: &str = "Usage: rust_command [options]
+
+Options:
+  --chaos             play chaos mode
+  --timed             play a 60 second timed challenge
+  --seed <number>     seed the rockets of every local game, otherwise the
+                      first is seeded 1337 and each replay with R is new
+  --width <pixels>    window width, overrides config.toml
+  --height <pixels>   window height, overrides config.toml
+  --reduced-motion    turn off animations and flashing
+  --night-vision      green night vision palette with scanlines
+  --debug             start with the debug overlay up
+  --fixed-difficulty  keep the wave sizes the same however well you play
+  --tutorial          show the tutorial even if it has been done before
+  --host <port>       host a networked game
+  --join <address>    join a networked game at host:port
+  --replay            watch the last game played
+  --headless          play one game with no window or player and print
+                      the final score
+  --benchmark         time 100 levels with no window and print the
+                      results as JSON
+  --help              show this message";
+
+// Desktop fullscreen keeps the desktop resolution, so the drawable size is
+// just the monitor size
+fn fullscreen_type(fullscreen: bool) -> conf::FullscreenType {
+    if fullscreen {
+        conf::FullscreenType::Desktop
+    } else {
+        conf::FullscreenType::Windowed
     }
 }
 
-// Move the rocket based on its angle and velocity
-fn rocket_move(actor: &mut Actor, dt: f32) {
-    actor.pos += vec_from_angle(actor.angle) * ROCKET_VEL * dt;
+// The colors to draw with and the scanline shader laid over them, if any
+fn night_vision_look(
+    ctx: &mut Context,
+    night_vision: bool,
+) -> GameResult<(Palette, Option<graphics::Shader>)> {
+    if !night_vision {
+        return Ok((DEFAULT_PALETTE, None));
+    }
+    let shader = graphics::ShaderBuilder::new()
+        .fragment_code(SCANLINE_SHADER)
+        .build(ctx)?;
+    Ok((NIGHT_VISION_PALETTE, Some(shader)))
 }
 
-// Keep track of the lifetime of each interceptor, in order to
-// facilitate the explosion animation and keep track of lifetime
-fn interceptor_elapse(actor: &mut Actor, dt: f32) {
-    actor.elapsed -= dt * 3.0; // make it a tad faster
+const FRAME_SAMPLES: usize = 60; // frame times kept for the debug overlay
 
-    // https://www.desmos.com/calculator/rwux8jpeud
-    // Model explosion radius with this function I randomly came up with
-    // by messing around in desmos until it had the behavior I wanted
-    actor.radius =
-        INTERCEPTOR_BASE_RADIUS * (-(((actor.elapsed - 2.5) * (actor.elapsed - 2.5)) / 2.5) + 2.5);
+const STAR_COUNT: usize = 200;
+
+const SHAKE_TIME: f32 = 0.3;
+const SHAKE_INTENSITY: f32 = 8.0; // pixels at the start of the shake
+
+const FLASH_MESSAGE_TIME: f32 = 1.5; // seconds to fade out
+
+const COUNTDOWN_TIME: f32 = 5.0; // seconds left in a level when the countdown comes up
+
+const WARNING_LEAD: f32 = 1.0; // seconds of warning before a held back rocket comes in
+const WARNING_FADE: f32 = 0.5; // seconds to fade out once it has
+
+// Which screen is up, the simulation only runs while Playing
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum GamePhase {
+    MainMenu,
+    Statistics,
+    HighScores,
+    Playing,
+    GameOver {
+        final_score: i32,
+        final_level: u32,
+        new_record: bool,
+    },
 }
 
 struct MainState {
-    player: Actor,
-    screen_width: f32,
-    screen_height: f32,
+    phase: GamePhase,
+    sim: GameSimulation,
+    ui: UiManager,
+    window_width: f32,
+    window_height: f32,
+    // the size of the image the game is drawn into before being blown up
+    // by the pixel scale, the window's size without one
+    internal_width: f32,
+    internal_height: f32,
+    cinematic: bool,
+    viewport: graphics::Rect,
     input: InputState,
-    rockets: Vec<Actor>,
-    interceptors: Vec<Actor>,
-    shot_timeout: f32,
-    rocket_delay: f32,
-    rng: Rand32,
-    level_timer: f32,
-    level: u32,
-    score: i32,
+    input2: Option<InputState>, // the second player's keys in local co-op
+    bindings: KeyBindings,
+    config: Config,
+    radar: Radar,
+    net: Option<NetSession>,
+    palette: Palette,
+    scanlines: Option<graphics::Shader>,
+    // the small image the game is drawn into when the pixel scale is over 1
+    pixel_image: Option<graphics::Image>,
+    assets: Assets,
+    gamepad: Option<GamepadId>, // the first one used, the rest are ignored
+    paused: bool,
+    hud_editor: bool,             // the game stops while the HUD is moved around
+    hud_drag: Option<HudElement>, // held by the mouse in the HUD editor
+    burst_trigger: bool,          // right trigger past the burst threshold
+    touches: u32,                 // fingers on the screen, ggez doesn't say which is which
+    autosave_timer: f32,
+    shake_timer: f32,
+    shake_intensity: f32,
+    flash_message: Option<(String, f32)>, // text and how long it has left
+    tutorial: Option<TutorialStep>,       // until the first launch tutorial is finished
+    checkpoint: Option<Checkpoint>,       // offered on the main menu, kept every few levels
+    confirm_wipe: bool,                   // Delete was pressed once on the main menu
+    particles: Vec<Particle>,
+    cursor_idle_timer: f32, // how long the cursor has stayed put
+    // 1.0 on each second of the end of level countdown, falling to 0.0 by
+    // the next one, the countdown digit is scaled up by it
+    countdown_pulse: f32,
+    burst_from: ActorId, // interceptors from this id on haven't burst yet
+    // x and time left of the arrows over rockets about to come in, counting
+    // down to 0 over the lead and then the fade
+    warnings: Vec<(f32, f32)>,
+    warned_from: ActorId, // held back rockets from this id on have no warning yet
+    // for screen shake and particles, separate from the simulation's so
+    // effects can't knock a networked game out of sync
+    effects_rng: Rand32,
+    // fraction of the way across and down the screen, and brightness
+    stars: Vec<(Point2, f32)>,
+    city_buildings: Vec<graphics::Rect>, // see generate_skyline
+    replay: Replay,                      // this game's inputs, saved when it ends
+    start: ReplayStart,                  // what the next game to start picks up from
+    playback: Option<std::vec::IntoIter<Frame>>, // set by --replay
+    debug_overlay: bool,
+    // recent frame times in milliseconds, only filled in while the debug
+    // overlay is up, buf_head is the oldest
+    ring_buffer: [f32; FRAME_SAMPLES],
+    buf_head: usize,
+    local_seed: u64, // what local games and restarts are seeded with
+    difficulty: DifficultyPreset,
+    stats: Stats,
+    high_scores: HighScoreTables,
+    session_start: Instant, // when the current game started
 }
 
-const LEVEL_TIME: f32 = 15.0;
-
 impl MainState {
-    fn new(ctx: &mut Context) -> GameResult<MainState> {
+    fn new(
+        ctx: &mut Context,
+        mode: GameMode,
+        config: Config,
+        local_seed: u64,
+        net: Option<(NetSession, u64)>,
+        resume: Option<SaveState>,
+        playback: Option<Replay>,
+    ) -> GameResult<MainState> {
         println!("rust_command Instructions:");
-        println!("Use arrow keys to move cursor");
-        println!("Use space to fire an interceptor");
-
-        let rng = Rand32::new(1337);
+        println!("Use arrow keys or WASD to move cursor");
+        println!("Use space to fire an interceptor, hold it longer for a bigger one");
+        println!("Use shift and space to drop an EMP that freezes the rockets");
+        println!("Use N to drop a nuke when one is ready");
+        println!("Use Q to cycle standard, warp gate and traveling interceptors");
+        println!("Press space again while a traveling interceptor flies to curve it");
+        println!("Use F6 to toggle cinematic mode");
+        println!("Use F11 to toggle fullscreen");
+        println!("Use F3 to toggle the debug overlay");
+        println!("Use F5 to reload config.toml");
+        println!("Use P or escape to pause, escape again quits");
+        println!("Use R to play again after a game ends");
+        if mode == GameMode::Chaos {
+            println!("Chaos mode: lose points for every rocket destroyed,");
+            println!("gain points for every ground hit. Lowest score wins!");
+        }
+        if mode == GameMode::TimedChallenge {
+            println!("Timed challenge: destroy as many rockets as you can in 60 seconds,");
+            println!("the cities can't fall and the rockets get harder every 15 seconds");
+        }
 
-        let player = create_player_cursor();
+        // both machines need the same rockets, so use the shared seed
+        let (net, seed) = match net {
+            Some((session, seed)) => (Some(session), seed),
+            None => (None, local_seed),
+        };
+        let seed = playback.as_ref().map_or(seed, |replay| replay.seed);
 
         let (width, height) = ctx.gfx.drawable_size();
 
-        let s = MainState {
-            player,
-            screen_width: width,
-            screen_height: height,
+        let (palette, scanlines) = night_vision_look(ctx, config.night_vision)?;
+
+        let mut assets = Assets::load(ctx, config.runtime.master_volume)
+            .map_err(|e| GameError::ResourceLoadError(e.to_string()))?;
+        assets.install_font(&mut ctx.gfx);
+        assets.play_music(ctx);
+
+        // networked games always play Normal, both sides skip the menu
+        let difficulty = playback
+            .as_ref()
+            .map_or(DifficultyPreset::Normal, |replay| replay.difficulty);
+        let start = resume.map_or(ReplayStart::NewGame, ReplayStart::Resume);
+        let mut sim = match &playback {
+            Some(replay) => replay.game(),
+            None => {
+                let mut sim = GameSimulation::new(width, height, mode, seed, net.is_some());
+                sim.set_difficulty(difficulty);
+                // picking an upgrade isn't sent over the network
+                sim.shop_enabled = mode == GameMode::Normal && net.is_none();
+                // the other side of a networked game might not have the flag
+                sim.adaptive = !config.fixed_difficulty && net.is_none();
+                if net.is_none() {
+                    sim.scoring.miss_penalty = config.runtime.waste_penalty;
+                }
+                if let Some(save) = resume {
+                    save.restore(&mut sim);
+                }
+                sim
+            }
+        };
+        // the same games that autosave
+        let checkpoint = if mode == GameMode::Normal && net.is_none() && playback.is_none() {
+            load_checkpoint()
+        } else {
+            None
+        };
+        let high_scores = load_high_scores();
+        if let Some(best) = high_scores.table(mode, config.night_vision).first() {
+            sim.scoring.high_score = best.score;
+        }
+
+        // a networked game starts as soon as both sides are connected, and
+        // a replay goes straight into the recorded game
+        let phase = if net.is_some() || playback.is_some() {
+            GamePhase::Playing
+        } else {
+            GamePhase::MainMenu
+        };
+
+        let mut effects_rng = Rand32::new(seed);
+        let stars = (0..STAR_COUNT)
+            .map(|_| {
+                let pos = Point2::new(effects_rng.rand_float(), effects_rng.rand_float());
+                (pos, effects_rng.rand_float())
+            })
+            .collect();
+        let city_buildings = generate_skyline(&mut effects_rng);
+
+        let debug_overlay = config.debug;
+        // only a local game can stop for it
+        let tutorial = (config.tutorial || (first_launch() && !config.tutorial_done))
+            && net.is_none()
+            && playback.is_none();
+        let replay = Replay::new(seed, &sim, difficulty, start);
+        let mut s = MainState {
+            phase,
+            sim,
+            ui: UiManager::new(
+                config.reduced_motion,
+                config.runtime.colorblind_mode,
+                net.is_none(),
+                &config.runtime.hud,
+            ),
+            window_width: width,
+            window_height: height,
+            internal_width: width,
+            internal_height: height,
+            cinematic: false,
+            viewport: graphics::Rect::new(0.0, 0.0, width, height),
             input: InputState::default(),
-            rockets: Vec::new(),
-            interceptors: Vec::new(),
-            shot_timeout: 0.0,
-            rocket_delay: ROCKET_DELAY,
-            rng,
-            level_timer: LEVEL_TIME,
-            level: 1,
-            score: 0,
+            input2: None,
+            bindings: config.runtime.bindings.clone(),
+            config,
+            radar: Radar::start(),
+            net,
+            palette,
+            scanlines,
+            pixel_image: None,
+            assets,
+            gamepad: None,
+            paused: false,
+            hud_editor: false,
+            hud_drag: None,
+            burst_trigger: false,
+            touches: 0,
+            autosave_timer: AUTOSAVE_PERIOD,
+            shake_timer: 0.0,
+            shake_intensity: 0.0,
+            flash_message: None,
+            tutorial: tutorial.then_some(TutorialStep::MoveLeft),
+            checkpoint,
+            confirm_wipe: false,
+            particles: Vec::new(),
+            cursor_idle_timer: 0.0,
+            countdown_pulse: 0.0,
+            burst_from: ActorId(0),
+            warnings: Vec::new(),
+            warned_from: ActorId(0),
+            effects_rng,
+            stars,
+            city_buildings,
+            replay,
+            start,
+            playback: playback.map(|replay| replay.log.into_iter()),
+            debug_overlay,
+            ring_buffer: [0.0; FRAME_SAMPLES],
+            buf_head: 0,
+            local_seed,
+            difficulty,
+            stats: load_stats(),
+            high_scores,
+            session_start: Instant::now(),
         };
+        s.update_internal_size();
 
         Ok(s)
     }
 
-    // Handle the case where a missile hits the side of the screen or the ground
-    fn handle_border_collisions(&mut self) -> GameResult {
-        let screen_x = self.screen_width / 2.0;
-        let screen_y = self.screen_height / 2.0;
+    // Switch between the full window and a letterboxed 16:9 play field
+    fn toggle_cinematic(&mut self) {
+        self.cinematic = !self.cinematic;
+        self.update_viewport();
+    }
 
-        for rocket in &mut self.rockets {
-            if rocket.pos.y < -screen_y + GROUND_HEIGHT {
-                // hit ground
-                rocket.life = 0.0; // kill missile
-                self.player.life -= 1.0; // damage player
+    // Switch the look on the main menu, along with the high score to beat,
+    // night vision games have their own table
+    fn toggle_night_vision(&mut self, ctx: &mut Context) -> GameResult {
+        self.config.night_vision = !self.config.night_vision;
+        (self.palette, self.scanlines) = night_vision_look(ctx, self.config.night_vision)?;
+        let table = self
+            .high_scores
+            .table(self.sim.mode, self.config.night_vision);
+        self.sim.scoring.high_score = table.first().map_or(0, |best| best.score);
+        Ok(())
+    }
 
-                // make explosion by recycling the interceptor code
-                let mut explosion = create_interceptor();
-                explosion.pos = rocket.pos;
-                self.interceptors.push(explosion);
-            }
-            if rocket.pos.x > screen_x || rocket.pos.x < -screen_x {
-                // hit side
-                rocket.life = 0.0; // kill missile
-            }
-        }
+    fn toggle_fullscreen(&mut self, ctx: &mut Context) -> GameResult {
+        self.config.fullscreen = !self.config.fullscreen;
+        ctx.gfx
+            .set_fullscreen(fullscreen_type(self.config.fullscreen))?;
+
+        // the resize event catches up too, but don't draw a frame at the
+        // old size in the meantime
+        let (width, height) = ctx.gfx.drawable_size();
+        self.resize_window(width, height);
         Ok(())
     }
 
-    // Handle collisions between interceptors and missiles
-    fn handle_interceptions(&mut self) -> GameResult {
-        for rocket in &mut self.rockets {
-            for interceptor in &mut self.interceptors {
-                let dist = rocket.pos - interceptor.pos;
-                if dist.length() < interceptor.radius {
-                    // collision
-                    rocket.life = 0.0; // kill rocket, interceptor will be killed by elapse system
-                    self.score += 150;
-                }
+    fn resize_window(&mut self, width: f32, height: f32) {
+        self.window_width = width;
+        self.window_height = height;
+        self.update_internal_size();
+        self.update_viewport();
+    }
+
+    // Whole pixels of the pixel scale, any left over at the right and bottom
+    // edges of the window go unused so every pixel blows up to the same size
+    fn update_internal_size(&mut self) {
+        let pixel_scale = self.config.runtime.pixel_scale.max(1) as f32;
+        self.internal_width = (self.window_width / pixel_scale).floor().max(1.0);
+        self.internal_height = (self.window_height / pixel_scale).floor().max(1.0);
+    }
+
+    // Fit the play field to the window, letterboxed in cinematic mode
+    fn update_viewport(&mut self) {
+        self.viewport = if self.cinematic {
+            letterbox_viewport(self.window_width, self.window_height)
+        } else {
+            graphics::Rect::new(0.0, 0.0, self.window_width, self.window_height)
+        };
+
+        // the world is sized to the viewport, so all the bounds follow it,
+        // a replay keeps the size it was recorded at
+        self.command(Command::Resize(self.viewport.w, self.viewport.h));
+    }
+
+    // Play a sound for each kind of event that happened since the counts in
+    // before were taken, once per tick is plenty even if several happened
+    fn play_sounds(&mut self, ctx: &Context, before: (u32, u32, u32)) {
+        let (shots, destroyed, hits) = before;
+        if self.sim.shots_fired > shots {
+            self.assets.play_sound(ctx, SoundId::Fire);
+        }
+        if self.sim.rockets_destroyed + self.sim.turret_kills > destroyed {
+            self.assets.play_sound(ctx, SoundId::Intercept);
+        }
+        if self.sim.ground_hits > hits {
+            self.assets.play_sound(ctx, SoundId::Impact);
+        }
+    }
+
+    // Only single player games are saved, chaos mode and timed challenges are
+    // too short to bother
+    // and a networked game can't be resumed alone
+    fn autosaves(&self) -> bool {
+        self.sim.mode == GameMode::Normal
+            && self.net.is_none()
+            && self.input2.is_none()
+            && !self.replaying()
+    }
+
+    fn replaying(&self) -> bool {
+        self.playback.is_some()
+    }
+
+    // Change the game from outside the tick, kept in the replay so it plays
+    // back the same. A replay only takes the commands it recorded, so this
+    // does nothing while one plays. Returns what the simulation's apply did.
+    fn command(&mut self, command: Command) -> bool {
+        if self.replaying() {
+            return false;
+        }
+        self.replay.command(command);
+        self.sim.apply(command)
+    }
+
+    // Keep the finished game as the last replay. Only the first player's
+    // input is recorded, so two player games aren't kept, and playing a
+    // replay back leaves the file as it is.
+    fn save_replay(&self) {
+        if self.net.is_some() || self.input2.is_some() || self.replaying() {
+            return;
+        }
+        if let Err(e) = save_replay(&self.replay) {
+            println!("Could not save replay: {}", e);
+        }
+    }
+
+    // Leave the HUD editor and keep the new layout for next time
+    fn close_hud_editor(&mut self) {
+        self.hud_editor = false;
+        self.hud_drag = None;
+        if let Err(e) = Config::save_hud(self.config.runtime.hud) {
+            println!("Could not save the HUD layout: {}", e);
+        }
+    }
+
+    // Pick up changes to the settings file without restarting. The window
+    // size and fullscreen need a new window, so they wait for the next run.
+    fn reload_config(&mut self) {
+        let runtime = match Config::reload_runtime() {
+            Ok(runtime) => runtime,
+            Err(e) => {
+                println!("Invalid config, not reloaded: {}", e);
+                return;
             }
+        };
+
+        self.assets.set_volume(runtime.master_volume);
+        self.bindings = runtime.bindings.clone();
+        // the same games that took it from the config when they started
+        if self.net.is_none() {
+            self.command(Command::SetMissPenalty(runtime.waste_penalty));
         }
-        Ok(())
+        // the health bars pick their colors when they are made
+        if runtime.colorblind_mode != self.config.runtime.colorblind_mode {
+            self.ui = UiManager::new(
+                self.config.reduced_motion,
+                runtime.colorblind_mode,
+                self.net.is_none(),
+                &runtime.hud,
+            );
+            self.ui.sync(&self.sim);
+        } else {
+            self.ui.set_layout(&runtime.hud);
+        }
+        self.config.runtime = runtime;
+        self.update_internal_size();
+        self.flash_message = Some(("Config reloaded".to_string(), FLASH_MESSAGE_TIME));
+    }
+
+    // The city whose health bar the HUD editor grabs them all by, in screen
+    // coordinates
+    fn healthbar_city(&self) -> Vec2 {
+        let city = self.sim.cities.first().map_or(Point2::ZERO, |c| c.pos);
+        world_to_screen_coords(self.sim.screen_width, self.sim.screen_height, city)
     }
 
-    // Fire a new interceptor by adding it to state
-    fn fire_interceptor(&mut self) {
-        self.shot_timeout = SHOT_TIMEOUT;
-        let mut shot = create_interceptor();
+    // Move on to the next step of the tutorial. Once it's done the game
+    // starts over for real, with the rockets let go.
+    fn advance_tutorial(&mut self, step: TutorialStep) {
+        if step != TutorialStep::Done {
+            self.tutorial = Some(step);
+            return;
+        }
 
-        shot.pos = self.player.pos;
-        self.interceptors.push(shot);
+        self.tutorial = None;
+        if let Err(e) = Config::save_tutorial_done() {
+            println!("Could not save that the tutorial is done: {}", e);
+        }
+        self.restart();
+        let message = step.instruction(&self.bindings);
+        self.flash_message = Some((message, FLASH_MESSAGE_TIME));
     }
 
-    // create a wave of rockets, adapted from the ggez example create_rock method
-    fn create_rockets(&mut self, num: u32, x: f32, y: f32) -> Vec<Actor> {
-        self.rocket_delay = ROCKET_DELAY;
+    // The image to draw into at the pixel scale, made again whenever the
+    // window or the scale changes size
+    fn pixel_image(&mut self, ctx: &Context) -> graphics::Image {
+        let (width, height) = (self.internal_width as u32, self.internal_height as u32);
+        match &self.pixel_image {
+            Some(image) if image.width() == width && image.height() == height => image.clone(),
+            _ => {
+                let format = ctx.gfx.surface_format();
+                let image = graphics::Image::new_canvas_image(ctx, format, width, height, 1);
+                self.pixel_image = Some(image.clone());
+                image
+            }
+        }
+    }
 
-        let screen_x = x / 2.0;
-        let screen_y = y / 2.0;
+    // Put the cursor under a point in the window, window coordinates are
+    // taken back through the viewport into the world
+    fn point_cursor_at(&mut self, x: f32, y: f32) {
+        let screen_pos = Point2::new(x - self.viewport.x, y - self.viewport.y);
+        self.input.mouse = Some(screen_to_world_coords(
+            self.viewport.w,
+            self.viewport.h,
+            screen_pos,
+        ));
+    }
 
-        let new_rocket = |_| {
-            let mut rocket = create_rocket();
-            // random starting pos at top of screen
-            let start_pos = Vec2::new(self.rng.rand_float() * x - screen_x, screen_y);
-            // generate a random angle between 0.75 PI and 1.25 PI
-            // an angle of PI sends the rocket straight downward
-            let angle =
-                self.rng.rand_float() * 0.5 * std::f32::consts::PI + 0.75 * std::f32::consts::PI;
-            rocket.pos = start_pos;
-            rocket.initial_pos = start_pos;
-            rocket.angle = angle;
-            rocket
+    // Fire from the keyboard, the mouse or a second finger
+    fn press_fire(&mut self, repeated: bool) {
+        if self.paused || self.hud_editor || self.phase != GamePhase::Playing || self.replaying() {
+            return;
+        }
+
+        // holding fire keeps firing, except in the upgrade shop, a fresh
+        // press might be taken up by the shop or a traveling interceptor
+        let fire = if repeated {
+            self.sim.shop.is_none()
+        } else {
+            self.command(Command::Fire)
         };
-        (0..num).map(new_rocket).collect()
+        if fire {
+            self.input.press(GameAction::Fire);
+        }
     }
-}
 
-fn draw_ground(canvas: &mut graphics::Canvas, world_coords: (f32, f32)) {
-    let (screen_w, screen_h) = world_coords;
-    let rect = graphics::Rect::new(0.0, screen_h - GROUND_HEIGHT, screen_w, GROUND_HEIGHT);
-    canvas.draw(
-        &graphics::Quad,
-        graphics::DrawParam::new()
-            .dest(rect.point())
-            .scale(rect.size())
-            .color(Color::WHITE),
-    );
-}
+    // The EMP and the nuke aren't sent over the network, so they only work in
+    // a local game
+    fn specials_allowed(&self) -> bool {
+        !self.paused && self.phase == GamePhase::Playing && self.net.is_none()
+    }
 
-fn draw_cursor(canvas: &mut graphics::Canvas, actor: &Actor, world_coords: (f32, f32)) {
-    let (screen_w, screen_h) = world_coords;
-    let pos = world_to_screen_coords(screen_w, screen_h, actor.pos);
-    let rect1 = graphics::Rect::new(pos.x, pos.y, CURSOR_WIDTH, CURSOR_HEIGHT);
-    canvas.draw(
-        &graphics::Quad,
-        graphics::DrawParam::new()
-            .dest(rect1.point())
-            .scale(rect1.size())
-            .color(Color::WHITE),
-    );
-
-    let rect2 = graphics::Rect::new(
-        pos.x + CURSOR_WIDTH / 2.0 - CURSOR_HEIGHT / 2.0,
-        pos.y + CURSOR_HEIGHT / 2.0 - CURSOR_WIDTH / 2.0,
-        CURSOR_HEIGHT,
-        CURSOR_WIDTH,
-    );
-
-    canvas.draw(
-        &graphics::Quad,
-        graphics::DrawParam::new()
-            .dest(rect2.point())
-            .scale(rect2.size())
-            .color(Color::WHITE),
-    );
-}
+    // Shift and fire drops an EMP instead
+    fn press_emp(&mut self) {
+        if self.specials_allowed() {
+            self.command(Command::FireEmp);
+        }
+    }
 
-fn draw_rocket(
-    canvas: &mut graphics::Canvas,
-    ctx: &mut Context,
-    actor: &Actor,
-    world_coords: (f32, f32),
-    level: u32,
-) {
-    let (screen_w, screen_h) = world_coords;
+    // Start from the menu with a second player on the same keyboard, their
+    // cursor is the one a networked player would have
+    fn start_coop(&mut self) {
+        self.input2 = Some(InputState::default());
+        self.restart();
+    }
 
-    let endpoint = Vec2::new(
-        actor.pos.x + ROCKET_WIDTH / 2.0,
-        actor.pos.y - ROCKET_HEIGHT / 2.0,
-    );
+    // Start a fresh local game in the same window, with the second player
+    // again if there was one
+    fn restart(&mut self) {
+        let mut sim = GameSimulation::new(
+            self.viewport.w,
+            self.viewport.h,
+            self.sim.mode,
+            self.local_seed,
+            self.input2.is_some(),
+        );
+        sim.scoring.high_score = self.sim.scoring.high_score;
+        sim.shop_enabled = self.sim.mode == GameMode::Normal;
+        sim.adaptive = !self.config.fixed_difficulty;
+        sim.scoring.miss_penalty = self.config.runtime.waste_penalty;
+
+        self.sim = sim;
+        self.ui = UiManager::new(
+            self.config.reduced_motion,
+            self.config.runtime.colorblind_mode,
+            true,
+            &self.config.runtime.hud,
+        );
+        self.input = InputState::default();
+        if self.input2.is_some() {
+            self.input2 = Some(InputState::default());
+        }
+        self.paused = false;
+        self.burst_trigger = false;
+        self.autosave_timer = AUTOSAVE_PERIOD;
+        self.shake_timer = 0.0;
+        self.particles.clear();
+        self.warnings.clear();
+        self.cursor_idle_timer = 0.0;
+        self.start = ReplayStart::NewGame;
+        self.playback = None;
+        self.start_game();
+    }
 
-    let points = &[
-        world_to_screen_coords(screen_w, screen_h, actor.initial_pos),
-        world_to_screen_coords(screen_w, screen_h, endpoint),
-    ];
+    // Save a checkpoint once the break before every fifth level is over
+    fn keep_checkpoint(&mut self) {
+        let level = self.sim.level.level;
+        let kept = self.checkpoint.is_some_and(|c| c.save.level == level);
+        if self.sim.transitioning || !level.is_multiple_of(CHECKPOINT_LEVELS) || kept {
+            return;
+        }
 
-    // tracer line
-    let mut modifier = level as f32 / 10.0;
+        let checkpoint = Checkpoint::capture(&self.sim);
+        self.checkpoint = Some(checkpoint);
+        match save_checkpoint(checkpoint) {
+            Ok(()) => {
+                self.flash_message = Some(("Checkpoint saved".to_string(), FLASH_MESSAGE_TIME));
+            }
+            Err(e) => println!("Could not save checkpoint: {}", e),
+        }
+    }
 
-    if modifier > 1.0 {
-        modifier = 1.0;
+    // Pick the game up from the start of the checkpoint's level
+    fn continue_from_checkpoint(&mut self) {
+        if let Some(checkpoint) = self.checkpoint {
+            checkpoint.restore(&mut self.sim);
+            self.start = ReplayStart::Checkpoint(checkpoint);
+            self.start_game();
+        }
     }
 
-    let tracer_color = Color::new(modifier, 1.0 - modifier, 0.0, 1.0);
-    let line = graphics::Mesh::new_line(ctx, points, 5.0, tracer_color).unwrap();
+    // The first press asks, the second wipes the checkpoint
+    fn press_delete_checkpoint(&mut self) {
+        if self.confirm_wipe {
+            delete_checkpoint();
+            self.checkpoint = None;
+            self.confirm_wipe = false;
+        } else {
+            self.confirm_wipe = self.checkpoint.is_some();
+        }
+    }
 
-    canvas.draw(&line, Vec2::new(0.0, 0.0));
+    fn start_game(&mut self) {
+        self.sim.set_difficulty(self.difficulty);
+        self.sim.rockets_held = self.tutorial.is_some();
+        self.replay = Replay::new(self.local_seed, &self.sim, self.difficulty, self.start);
+        self.phase = GamePhase::Playing;
+        self.session_start = Instant::now();
+    }
 
-    let pos = world_to_screen_coords(screen_w, screen_h, actor.pos);
-    let rect = graphics::Rect::new(pos.x, pos.y, ROCKET_WIDTH, ROCKET_HEIGHT);
+    // Every interceptor and ground impact added since the last tick bursts
+    // into particles, reduced motion keeps the plain flash instead
+    fn spawn_explosion_particles(&mut self) {
+        if self.config.reduced_motion {
+            return;
+        }
 
-    // rocket body
-    canvas.draw(
-        &graphics::Quad,
-        graphics::DrawParam::new()
-            .dest(rect.point())
-            .scale(rect.size())
-            .color(Color::WHITE),
-    );
-}
+        // ids only go up, so the ones past the newest seen last time are new
+        let mut newest = None;
+        for interceptor in self
+            .sim
+            .interceptors
+            .active_iter()
+            .filter(|i| i.id >= self.burst_from)
+        {
+            spawn_burst(
+                &mut self.particles,
+                &mut self.effects_rng,
+                interceptor.pos,
+                INTERCEPTOR_PEAK_SCALE * self.sim.blast_radius(interceptor),
+                interceptor.explosion_kind(),
+                self.palette.explosion,
+            );
+            newest = newest.max(Some(interceptor.id));
+        }
+        if let Some(id) = newest {
+            self.burst_from = ActorId(id.0 + 1);
+        }
+    }
 
-fn draw_interceptor(
-    canvas: &mut graphics::Canvas,
-    ctx: &mut Context,
-    actor: &Actor,
-    world_coords: (f32, f32),
-) {
-    let (screen_w, screen_h) = world_coords;
-    let pos = world_to_screen_coords(screen_w, screen_h, actor.pos);
-
-    let points = &[
-        Vec2::new(screen_w / 2.0, screen_h - GROUND_HEIGHT),
-        world_to_screen_coords(screen_w, screen_h, actor.pos),
-    ];
-
-    let tracer_color: Color = Color::new(1.0, 1.0, 1.0, actor.elapsed / INTERCEPTOR_PERIOD);
-    // tracer line
-    let line = graphics::Mesh::new_line(ctx, points, 5.0, tracer_color).unwrap();
-
-    canvas.draw(&line, Vec2::new(0.0, 0.0));
-
-    let circle = graphics::Mesh::new_circle(
-        ctx,
-        graphics::DrawMode::fill(),
-        pos,
-        actor.radius,
-        10.0, // for weird pixellated polygon action
-        Color::WHITE,
-    )
-    .unwrap();
-
-    // explosion
-    canvas.draw(&circle, Vec2::new(0.0, 0.0));
-}
+    // Give every rocket newly held back from its wave a warning, timed to
+    // fade once it comes in, and count the rest down
+    fn update_warnings(&mut self, dt: f32) {
+        for (_, time) in &mut self.warnings {
+            *time -= dt;
+        }
+        self.warnings.retain(|(_, time)| *time > 0.0);
+        // the end of a level takes away the rockets that hadn't come in yet
+        if self.sim.transitioning {
+            self.warnings.retain(|(_, time)| *time <= WARNING_FADE);
+        }
+
+        let mut newest = None;
+        for (rocket, delay) in &self.sim.pending_rockets {
+            if rocket.id >= self.warned_from {
+                self.warnings.push((rocket.pos.x, delay + WARNING_FADE));
+                newest = newest.max(Some(rocket.id));
+            }
+        }
+        if let Some(id) = newest {
+            self.warned_from = ActorId(id.0 + 1);
+        }
+    }
 
-const HEALTHBAR_WIDTH: f32 = 200.0;
-const HEALTHBAR_HEIGHT: f32 = 50.0;
-
-fn draw_healthbar(canvas: &mut graphics::Canvas, actor: &Actor, screen_h: f32) {
-    let container = graphics::Rect::new(25.0, screen_h - 75.0, HEALTHBAR_WIDTH, HEALTHBAR_HEIGHT);
-
-    let bar_width = (actor.life / GROUND_LIFE) * (HEALTHBAR_WIDTH - 10.0);
-    let bar_color = Color::new(
-        1.0 - actor.life / GROUND_LIFE,
-        actor.life / GROUND_LIFE,
-        0.0,
-        1.0,
-    );
-    let health_bar = graphics::Rect::new(
-        25.0 + 5.0,
-        screen_h - 75.0 + 5.0,
-        bar_width,
-        HEALTHBAR_HEIGHT - 10.0,
-    );
-
-    canvas.draw(
-        &graphics::Quad,
-        graphics::DrawParam::new()
-            .dest(container.point())
-            .scale(container.size())
-            .color(Color::BLACK),
-    );
-
-    canvas.draw(
-        &graphics::Quad,
-        graphics::DrawParam::new()
-            .dest(health_bar.point())
-            .scale(health_bar.size())
-            .color(bar_color),
-    );
+    // Put the finished game in the table for its mode, true if it beat the
+    // best score
+    fn record_high_score(&mut self) -> bool {
+        println!("Score: {}", self.sim.scoring.score);
+        // a replayed score is already in the table
+        if self.replaying() {
+            return false;
+        }
+
+        let new_record = self.sim.scoring.score > self.sim.scoring.high_score;
+        if new_record {
+            self.sim.scoring.high_score = self.sim.scoring.score;
+        }
+        let entry = HighScoreEntry::capture(&self.sim);
+        let table = self
+            .high_scores
+            .table_mut(self.sim.mode, self.config.night_vision);
+        if insert_high_score(table, entry) {
+            if let Err(e) = save_high_scores(&self.high_scores) {
+                println!("Could not save high scores: {}", e);
+            }
+        }
+        new_record
+    }
+
+    // Stop the simulation and put up the game over screen
+    fn end_game(&mut self, new_record: bool) {
+        // a replayed game was counted when it was played
+        if !self.replaying() {
+            let play_time = self.session_start.elapsed().as_secs_f64();
+            self.stats.record_game(&self.sim, play_time);
+            if let Err(e) = save_stats(self.stats) {
+                println!("Could not save statistics: {}", e);
+            }
+        }
+
+        self.phase = GamePhase::GameOver {
+            final_score: self.sim.scoring.score,
+            final_level: self.sim.level.level,
+            new_record,
+        };
+        // nothing left to warn about
+        self.radar.set_distance(f32::MAX);
+        self.radar.set_health(1.0);
+        self.shake_timer = 0.0;
+    }
+
+    // A networked game can't be paused, the other player would carry on
+    // without us
+    fn can_pause(&self) -> bool {
+        self.net.is_none()
+    }
+
+    // Take the first gamepad that sends anything as the player's, true if
+    // id is that gamepad
+    fn claim_gamepad(&mut self, id: GamepadId) -> bool {
+        *self.gamepad.get_or_insert(id) == id
+    }
+
+    // Trade inputs with the other machine, the simulation moves the second
+    // cursor from whatever arrived last
+    fn exchange_input(&mut self) {
+        let net = match &self.net {
+            Some(net) => net,
+            None => return,
+        };
+
+        net.send(&self.input, self.sim.player.pos);
+        if let Some(packet) = net.receive() {
+            self.sim.remote_input = packet.input;
+            self.sim.remote_pos = Some(packet.pos);
+        }
+    }
 }
 
 impl EventHandler for MainState {
     fn update(&mut self, ctx: &mut Context) -> GameResult {
-        const DESIRED_FPS: u32 = 60;
-
         while ctx.time.check_update_time(DESIRED_FPS) {
             let seconds = 1.0 / (DESIRED_FPS as f32);
 
-            self.level_timer -= seconds;
-            if self.level_timer <= 0.0 {
-                self.level += 1;
-                self.level_timer = LEVEL_TIME;
+            // keep draining the timer so there is no catching up on resume
+            if self.paused || self.hud_editor || self.phase != GamePhase::Playing {
+                continue;
             }
 
-            cursor_move(
-                &mut self.player,
-                self.screen_width,
-                self.screen_height,
-                &self.input,
-                seconds,
-            );
+            // a replay feeds its recorded inputs in place of the real ones
+            if let Some(frames) = &mut self.playback {
+                match frames.next() {
+                    Some(frame) => {
+                        self.input = frame.input;
+                        for command in frame.commands {
+                            self.sim.apply(command);
+                        }
+                    }
+                    None => {
+                        println!("Replay finished");
+                        self.end_game(false);
+                        break;
+                    }
+                }
+            } else {
+                self.replay.record(seconds, &self.input);
+            }
 
-            self.shot_timeout -= seconds;
+            self.exchange_input();
+            if let Some(input2) = &self.input2 {
+                self.sim.remote_input = input2.clone();
+            }
 
-            if self.input.fire && self.shot_timeout <= 0.0 {
-                self.fire_interceptor();
+            let before = (
+                self.sim.shots_fired,
+                self.sim.rockets_destroyed + self.sim.turret_kills,
+                self.sim.ground_hits,
+            );
+            let cursor_before = self.sim.player.pos;
+            let perfect_before = self.sim.perfect_waves;
+            let outcome = self.sim.tick(seconds, &self.input);
+            if self.sim.player.pos == cursor_before {
+                self.cursor_idle_timer += seconds;
+            } else {
+                self.cursor_idle_timer = 0.0;
             }
+            self.play_sounds(ctx, before);
 
-            self.rocket_delay -= seconds;
+            self.shake_timer = (self.shake_timer - seconds).max(0.0);
+            if self.sim.ground_hits > before.2 && !self.config.reduced_motion {
+                self.shake_timer = SHAKE_TIME;
+                self.shake_intensity = SHAKE_INTENSITY;
+            }
 
-            let num_rockets = self.rng.rand_range((1 + self.level)..(3 + self.level));
+            if let Some((_, timer)) = &mut self.flash_message {
+                *timer -= seconds;
+            }
+            self.flash_message = self.flash_message.take().filter(|(_, timer)| *timer > 0.0);
+            if self.sim.perfect_waves > perfect_before {
+                self.flash_message = Some(("Perfect Wave!".to_string(), FLASH_MESSAGE_TIME));
+            }
 
-            if self.rocket_delay <= 0.0 {
-                for rocket in
-                    self.create_rockets(num_rockets, self.screen_width, self.screen_height)
-                {
-                    self.rockets.push(rocket);
+            if let Some(step) = self.tutorial {
+                let moved = self.sim.player.pos - cursor_before;
+                let fired = self.sim.shots_fired > before.0;
+                let exploding = !self.sim.interceptors.is_empty()
+                    || !self.sim.traveling.is_empty()
+                    || !self.sim.in_flight.is_empty()
+                    || !self.sim.warp_gates.is_empty();
+                if step.completed(moved, fired, exploding) {
+                    self.advance_tutorial(step.next());
                 }
             }
 
-            for rocket in &mut self.rockets {
-                rocket_move(rocket, seconds);
+            // the timer counts down, so what is left of the second falls
+            // from 1.0 to 0.0 in step with the digit changing
+            let timer = self.sim.countdown_timer();
+            self.countdown_pulse = if timer <= COUNTDOWN_TIME {
+                timer.fract()
+            } else {
+                0.0
+            };
+
+            self.spawn_explosion_particles();
+            self.update_warnings(seconds);
+            update_particles(&mut self.particles, seconds);
+
+            self.ui.sync(&self.sim);
+            self.ui.update(seconds);
+
+            // Tell the radar how close the lowest live rocket is to the ground
+            self.radar.set_distance(self.sim.lowest_rocket_distance());
+            // chaos mode has no cities to lose
+            if self.sim.mode != GameMode::Chaos {
+                self.radar.set_health(self.sim.city_health());
             }
 
-            for interceptor in &mut self.interceptors {
-                interceptor_elapse(interceptor, seconds);
+            let mut music_volume = self.config.runtime.master_volume;
+            if self.sim.emp_flash > 0.0 {
+                music_volume *= MUSIC_DUCK;
             }
+            self.assets.set_music_volume(music_volume);
 
-            self.handle_border_collisions()?;
-            self.handle_interceptions()?;
-
-            // kill dead missiles and elapsed interceptors
-            self.rockets.retain(|r| r.life > 0.0);
-            self.interceptors.retain(|i| i.elapsed > 0.0);
+            if self.autosaves() {
+                self.autosave_timer -= seconds;
+                if self.autosave_timer <= 0.0 {
+                    self.autosave_timer = AUTOSAVE_PERIOD;
+                    autosave(SaveState::capture(&self.sim));
+                }
+                self.keep_checkpoint();
+            }
 
-            if self.player.life <= 0.0 {
-                println!("Game Over!");
-                println!("Score: {}", self.score);
-                ctx.request_quit();
+            match outcome {
+                Outcome::Running => (),
+                Outcome::GameOver => {
+                    println!("Game Over!");
+                    if self.autosaves() {
+                        delete_autosave();
+                        delete_checkpoint();
+                        self.checkpoint = None;
+                    }
+                    let new_record = self.record_high_score();
+                    self.save_replay();
+                    self.end_game(new_record);
+                    break;
+                }
+                Outcome::TimeUp if self.sim.mode == GameMode::TimedChallenge => {
+                    println!("Time's up!");
+                    let new_record = self.record_high_score();
+                    self.save_replay();
+                    self.end_game(new_record);
+                    break;
+                }
+                Outcome::TimeUp => {
+                    println!("Time's up!");
+                    println!("Score: {}", self.sim.scoring.score);
+                    // a replayed score is already on the leaderboard
+                    let new_record = if self.replaying() {
+                        false
+                    } else {
+                        match submit_chaos_score(self.sim.scoring.score) {
+                            Ok(scores) => {
+                                println!("Chaos leaderboard:");
+                                for (i, score) in scores.iter().enumerate() {
+                                    println!("{}. {}", i + 1, score);
+                                }
+                                scores.first() == Some(&self.sim.scoring.score)
+                            }
+                            Err(e) => {
+                                println!("Could not save chaos score: {}", e);
+                                false
+                            }
+                        }
+                    };
+                    self.save_replay();
+                    self.end_game(new_record);
+                    break;
+                }
             }
         }
 
@@ -492,98 +1284,460 @@ impl EventHandler for MainState {
     }
 
     fn draw(&mut self, ctx: &mut Context) -> GameResult {
-        let mut canvas = graphics::Canvas::from_frame(ctx, Color::BLACK);
+        if self.debug_overlay {
+            self.ring_buffer[self.buf_head] = ctx.time.delta().as_secs_f32() * 1000.0;
+            self.buf_head = (self.buf_head + 1) % FRAME_SAMPLES;
+        }
 
-        let coords = (self.screen_width, self.screen_height);
+        // a pixel scale draws the whole game into a smaller image first
+        let pixel_scale = self.config.runtime.pixel_scale;
+        let pixel_image = (pixel_scale > 1).then(|| self.pixel_image(ctx));
+        let mut canvas = match &pixel_image {
+            Some(image) => {
+                graphics::Canvas::from_image(ctx, image.clone(), self.palette.background)
+            }
+            None => graphics::Canvas::from_frame(ctx, self.palette.background),
+        };
 
-        draw_ground(&mut canvas, coords);
+        let renderer = Renderer::new(
+            self.sim.screen_width,
+            self.sim.screen_height,
+            &self.config,
+            &self.palette,
+        );
+        // the shake dies down as its timer runs out
+        let shake = if self.shake_timer > 0.0 {
+            let intensity = self.shake_intensity * self.shake_timer / SHAKE_TIME;
+            Vec2::new(
+                self.effects_rng.rand_float() * 2.0 - 1.0,
+                self.effects_rng.rand_float() * 2.0 - 1.0,
+            ) * intensity
+        } else {
+            Vec2::ZERO
+        };
+        renderer.set_viewport(&mut canvas, self, shake);
+        renderer.draw_stars(&mut canvas, ctx, self)?;
+        match self.phase {
+            GamePhase::MainMenu => renderer.draw_main_menu(&mut canvas, ctx, self)?,
+            GamePhase::Statistics => renderer.draw_statistics(&mut canvas, ctx, self)?,
+            GamePhase::HighScores => renderer.draw_high_scores(&mut canvas, ctx, self)?,
+            GamePhase::Playing => {
+                renderer.draw_frame(&mut canvas, ctx, self)?;
+                self.ui.draw(&mut canvas, ctx);
+            }
+            GamePhase::GameOver { .. } => renderer.draw_game_over(&mut canvas, ctx, self)?,
+        }
+        renderer.draw_overlay(&mut canvas, ctx, self)?;
+        if self.debug_overlay {
+            renderer.draw_debug_overlay(&mut canvas, ctx, self)?;
+        }
 
-        draw_cursor(&mut canvas, &self.player, coords);
+        canvas.finish(ctx)?;
 
-        for rocket in &self.rockets {
-            draw_rocket(&mut canvas, ctx, rocket, coords, self.level);
+        // then blows it up to fill the window without smoothing the pixels
+        if let Some(image) = pixel_image {
+            let mut frame = graphics::Canvas::from_frame(ctx, self.palette.background);
+            frame.set_sampler(graphics::Sampler::nearest_clamp());
+            let scale = Vec2::splat(pixel_scale as f32);
+            frame.draw(&image, graphics::DrawParam::new().scale(scale));
+            frame.finish(ctx)?;
         }
 
-        for interceptor in &self.interceptors {
-            draw_interceptor(&mut canvas, ctx, interceptor, coords);
+        timer::yield_now();
+        Ok(())
+    }
+
+    // input handler keydown adapted from ggez example
+    fn key_down_event(&mut self, ctx: &mut Context, input: KeyInput, repeated: bool) -> GameResult {
+        // the second player's keys only ever move their own cursor
+        if let (Some(input2), Some(keycode)) = (&mut self.input2, input.keycode) {
+            if let Some(action) = player2_action(keycode, &self.bindings) {
+                if self.phase == GamePhase::Playing && !self.paused {
+                    input2.press(action);
+                }
+                return Ok(());
+            }
         }
 
-        draw_healthbar(&mut canvas, &self.player, coords.1);
+        // any key at all leaves the high score table
+        if self.phase == GamePhase::HighScores {
+            if !repeated {
+                self.phase = GamePhase::MainMenu;
+            }
+            return Ok(());
+        }
 
-        canvas.draw(
-            &graphics::Text::new(format!("Score: {}", self.score)),
-            graphics::DrawParam::new()
-                .dest(Vec2::new(self.screen_width / 2.0, 10.0))
-                .color(Color::WHITE),
-        );
+        let action = match input
+            .keycode
+            .and_then(|k| process_keydown(k, &self.bindings))
+        {
+            Some(action) => action,
+            None => return Ok(()),
+        };
 
-        canvas.draw(
-            &graphics::Text::new(format!("Level: {}", self.level)),
-            graphics::DrawParam::new()
-                .dest(Vec2::new(20.0, 10.0))
-                .color(Color::WHITE),
-        );
+        // works on every screen
+        if action == GameAction::ToggleDebug {
+            self.debug_overlay = !self.debug_overlay;
+            return Ok(());
+        }
 
-        canvas.finish(ctx)?;
+        match self.phase {
+            GamePhase::MainMenu => {
+                // anything else backs out of wiping the checkpoint
+                if action != GameAction::DeleteCheckpoint {
+                    self.confirm_wipe = false;
+                }
+                match action {
+                    GameAction::Fire if !repeated => self.start_game(),
+                    GameAction::ContinueCheckpoint if !repeated => self.continue_from_checkpoint(),
+                    GameAction::DeleteCheckpoint if !repeated => self.press_delete_checkpoint(),
+                    GameAction::MoveLeft => self.difficulty = self.difficulty.easier(),
+                    GameAction::MoveRight => self.difficulty = self.difficulty.harder(),
+                    GameAction::StartCoop => self.start_coop(),
+                    GameAction::ShowStats => self.phase = GamePhase::Statistics,
+                    GameAction::ShowHighScores => self.phase = GamePhase::HighScores,
+                    GameAction::ToggleNightVision => self.toggle_night_vision(ctx)?,
+                    GameAction::ToggleCinematic => self.toggle_cinematic(),
+                    GameAction::ToggleFullscreen => self.toggle_fullscreen(ctx)?,
+                    GameAction::Quit => ctx.request_quit(),
+                    _ => (),
+                }
+                return Ok(());
+            }
+            GamePhase::Statistics => {
+                if matches!(
+                    action,
+                    GameAction::ShowStats | GameAction::Fire | GameAction::Quit
+                ) {
+                    self.phase = GamePhase::MainMenu;
+                }
+                return Ok(());
+            }
+            // already left above, before the key was looked up
+            GamePhase::HighScores => return Ok(()),
+            GamePhase::GameOver { .. } => {
+                match action {
+                    // the other player can't be made to restart with us
+                    GameAction::Restart if self.net.is_none() => {
+                        if !self.config.fixed_seed {
+                            self.local_seed = fresh_seed();
+                        }
+                        self.restart();
+                    }
+                    GameAction::ToggleCinematic => self.toggle_cinematic(),
+                    GameAction::ToggleFullscreen => self.toggle_fullscreen(ctx)?,
+                    GameAction::Quit => ctx.request_quit(),
+                    _ => (),
+                }
+                return Ok(());
+            }
+            GamePhase::Playing => (),
+        }
 
-        timer::yield_now();
+        if self.hud_editor {
+            if action == GameAction::ToggleHudEditor {
+                self.close_hud_editor();
+            }
+            return Ok(());
+        }
+
+        match action {
+            GameAction::Fire if input.mods.contains(KeyMods::SHIFT) => {
+                if !repeated {
+                    self.press_emp();
+                }
+            }
+            GameAction::Fire => self.press_fire(repeated),
+            GameAction::BuyUpgrade(choice) if !self.paused => {
+                self.command(Command::BuyUpgrade(choice));
+            }
+            GameAction::RepairBase if !self.paused => {
+                self.command(Command::RepairBase);
+            }
+            GameAction::FireNuke if !repeated && self.specials_allowed() => {
+                self.command(Command::FireNuke);
+            }
+            GameAction::PlaceTurret if !repeated && self.specials_allowed() => {
+                self.command(Command::PlaceTurret);
+            }
+            GameAction::CycleInterceptor => {
+                self.command(Command::CycleInterceptor);
+            }
+            GameAction::ToggleCinematic => self.toggle_cinematic(),
+            GameAction::ToggleFullscreen => self.toggle_fullscreen(ctx)?,
+            GameAction::ReloadConfig => self.reload_config(),
+            GameAction::ToggleHudEditor if self.can_pause() && !self.paused => {
+                self.hud_editor = true;
+                // the game won't see the keys let go while it's stopped
+                self.input = InputState::default();
+            }
+            GameAction::Pause if self.can_pause() => self.paused = !self.paused,
+            GameAction::Quit if self.can_pause() && !self.paused => self.paused = true,
+            GameAction::Quit => ctx.request_quit(),
+            _ if self.paused => (),
+            _ => self.input.press(action),
+        }
         Ok(())
     }
 
-    // input handler keydown adapted from ggez example
-    fn key_down_event(
+    // The cursor follows the mouse, or in the HUD editor whatever is being
+    // dragged does
+    fn mouse_motion_event(
         &mut self,
-        ctx: &mut Context,
-        input: KeyInput,
-        _repeated: bool,
+        _ctx: &mut Context,
+        x: f32,
+        y: f32,
+        dx: f32,
+        dy: f32,
     ) -> GameResult {
-        match input.keycode {
-            Some(KeyCode::Up) => {
-                self.input.yaxis = 1.0;
+        if self.hud_editor {
+            if let Some(element) = self.hud_drag {
+                let screen = Vec2::new(self.sim.screen_width, self.sim.screen_height);
+                self.config
+                    .runtime
+                    .hud
+                    .drag(element, Vec2::new(dx, dy), screen);
+                self.ui.set_layout(&self.config.runtime.hud);
             }
-            Some(KeyCode::Down) => {
-                self.input.yaxis = -1.0;
+            return Ok(());
+        }
+        self.point_cursor_at(x, y);
+        Ok(())
+    }
+
+    // One finger drags the cursor and a second one down fires at it. ggez
+    // doesn't tell the fingers apart, so while two are down neither moves
+    // the cursor.
+    fn touch_event(&mut self, _ctx: &mut Context, phase: TouchPhase, x: f64, y: f64) -> GameResult {
+        match phase {
+            TouchPhase::Started => {
+                self.touches += 1;
+                if self.touches == 1 {
+                    self.point_cursor_at(x as f32, y as f32);
+                } else if self.touches == 2 {
+                    self.press_fire(false);
+                }
             }
-            Some(KeyCode::Left) => {
-                self.input.xaxis = -1.0;
+            TouchPhase::Moved if self.touches == 1 => self.point_cursor_at(x as f32, y as f32),
+            TouchPhase::Moved => (),
+            TouchPhase::Ended | TouchPhase::Cancelled => {
+                self.touches = self.touches.saturating_sub(1);
+                if self.touches == 1 {
+                    self.input.release(GameAction::Fire);
+                }
             }
-            Some(KeyCode::Right) => {
-                self.input.xaxis = 1.0;
+        }
+        Ok(())
+    }
+
+    fn mouse_button_down_event(
+        &mut self,
+        _ctx: &mut Context,
+        button: MouseButton,
+        x: f32,
+        y: f32,
+    ) -> GameResult {
+        if button != MouseButton::Left {
+            return Ok(());
+        }
+        if self.hud_editor {
+            let point = Vec2::new(x - self.viewport.x, y - self.viewport.y);
+            let screen = Vec2::new(self.sim.screen_width, self.sim.screen_height);
+            self.hud_drag =
+                self.config
+                    .runtime
+                    .hud
+                    .element_at(point, screen, self.healthbar_city());
+        } else {
+            self.press_fire(false);
+        }
+        Ok(())
+    }
+
+    fn mouse_button_up_event(
+        &mut self,
+        _ctx: &mut Context,
+        button: MouseButton,
+        _x: f32,
+        _y: f32,
+    ) -> GameResult {
+        if button == MouseButton::Left {
+            self.input.release(GameAction::Fire);
+            self.hud_drag = None;
+        }
+        Ok(())
+    }
+
+    // The left stick moves the cursor. The left trigger fires at a rate set
+    // by how far it is pulled, a full pull of the right trigger fires a burst.
+    fn gamepad_axis_event(
+        &mut self,
+        _ctx: &mut Context,
+        axis: Axis,
+        value: f32,
+        id: GamepadId,
+    ) -> GameResult {
+        if !self.claim_gamepad(id) {
+            return Ok(());
+        }
+
+        match axis {
+            Axis::LeftStickX | Axis::LeftStickY => {
+                let value = apply_deadzone(value);
+                if value != 0.0 {
+                    self.input.mouse = None;
+                }
+                if axis == Axis::LeftStickX {
+                    self.input.xaxis = value;
+                } else {
+                    self.input.yaxis = value;
+                }
             }
-            Some(KeyCode::Space) => {
-                self.input.fire = true;
+            Axis::LeftZ => self.input.trigger = value.clamp(0.0, 1.0),
+            Axis::RightZ => {
+                let pulled = value > BURST_TRIGGER;
+                if pulled && !self.burst_trigger {
+                    self.command(Command::Burst);
+                }
+                self.burst_trigger = pulled;
             }
-            Some(KeyCode::Escape) => ctx.request_quit(),
             _ => (),
         }
         Ok(())
     }
 
+    // The south face button (A or Cross) fires
+    fn gamepad_button_down_event(
+        &mut self,
+        _ctx: &mut Context,
+        btn: Button,
+        id: GamepadId,
+    ) -> GameResult {
+        if self.claim_gamepad(id) && btn == Button::South {
+            self.press_fire(false);
+        }
+        Ok(())
+    }
+
+    fn gamepad_button_up_event(
+        &mut self,
+        _ctx: &mut Context,
+        btn: Button,
+        id: GamepadId,
+    ) -> GameResult {
+        if self.claim_gamepad(id) && btn == Button::South {
+            self.input.release(GameAction::Fire);
+        }
+        Ok(())
+    }
+
+    fn resize_event(&mut self, _ctx: &mut Context, width: f32, height: f32) -> GameResult {
+        self.resize_window(width, height);
+        Ok(())
+    }
+
     // input handler keyup adapted from ggez example
     fn key_up_event(&mut self, _ctx: &mut Context, input: KeyInput) -> GameResult {
-        match input.keycode {
-            Some(KeyCode::Up | KeyCode::Down) => {
-                self.input.yaxis = 0.0;
-            }
-            Some(KeyCode::Left | KeyCode::Right) => {
-                self.input.xaxis = 0.0;
+        if let (Some(input2), Some(keycode)) = (&mut self.input2, input.keycode) {
+            if let Some(action) = player2_action(keycode, &self.bindings) {
+                input2.release(action);
+                return Ok(());
             }
-            Some(KeyCode::Space) => {
-                self.input.fire = false;
-            }
-            _ => (),
+        }
+
+        if let Some(action) = input
+            .keycode
+            .and_then(|k| process_keydown(k, &self.bindings))
+        {
+            self.input.release(action);
         }
         Ok(())
     }
 }
 
 pub fn main() -> GameResult {
+    let args: Vec<String> = std::env::args().collect();
+    if args.iter().any(|arg| arg == "--help") {
+        println!("{}", USAGE);
+        return Ok(());
+    }
+    if args.iter().any(|arg| arg == "--benchmark") {
+        run_benchmark();
+        return Ok(());
+    }
+    let config = Config::from_args(&args);
+
+    let playback = if args.iter().any(|arg| arg == "--replay") {
+        Some(load_replay()?)
+    } else {
+        None
+    };
+
+    let mode = if let Some(replay) = &playback {
+        replay.mode
+    } else if args.iter().any(|arg| arg == "--chaos") {
+        GameMode::Chaos
+    } else if args.iter().any(|arg| arg == "--timed") {
+        GameMode::TimedChallenge
+    } else {
+        GameMode::Normal
+    };
+
+    let arg_value = |name: &str| {
+        args.iter()
+            .position(|arg| arg == name)
+            .and_then(|i| args.get(i + 1))
+    };
+
+    let seed = arg_value("--seed").map_or(SINGLE_PLAYER_SEED, |seed| {
+        seed.parse().expect("--seed takes a whole number")
+    });
+
+    if args.iter().any(|arg| arg == "--headless") {
+        run_headless(&mut headless_game(&config, mode, seed));
+        return Ok(());
+    }
+
+    let net = if playback.is_some() {
+        None
+    } else if let Some(port) = arg_value("--host") {
+        let port = port.parse().expect("--host takes a port number");
+        Some(NetSession::host(port)?)
+    } else if let Some(addr) = arg_value("--join") {
+        Some(NetSession::join(addr)?)
+    } else {
+        None
+    };
+
+    let resume = if mode == GameMode::Normal && net.is_none() && playback.is_none() {
+        prompt_resume()
+    } else {
+        None
+    };
+
+    // a replay keeps the play field it was recorded on, so open the window
+    // at that size to show all of it
+    let (width, height) = playback
+        .as_ref()
+        .map_or((config.window_width, config.window_height), |replay| {
+            replay.screen_size
+        });
+
+    // ggez only looks next to the executable, so point it at the folders
+    // next to Cargo.toml as well
+    let manifest_dir = std::path::Path::new(env!("CARGO_MANIFEST_DIR"));
     let cb = ContextBuilder::new("rust_command", "Reid Luttrell")
+        .add_resource_path(manifest_dir.join(assets::ASSETS_DIR))
+        .add_resource_path(manifest_dir.join("resources"))
         .window_setup(conf::WindowSetup::default().title("rust_command"))
-        .window_mode(conf::WindowMode::default().dimensions(1280.0, 760.0));
+        .window_mode(
+            conf::WindowMode::default()
+                .dimensions(width, height)
+                .fullscreen_type(fullscreen_type(config.fullscreen)),
+        );
 
     let (mut ctx, events_loop) = cb.build()?;
 
-    let game = MainState::new(&mut ctx)?;
+    let game = MainState::new(&mut ctx, mode, config, seed, net, resume, playback)?;
     event::run(ctx, events_loop, game)
 }
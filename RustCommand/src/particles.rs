@@ -0,0 +1,66 @@
+use ggez::glam::*;
+use ggez::graphics::Color;
+use oorandom::Rand32;
+
+use crate::actor::ExplosionKind;
+use crate::physics::vec_from_angle;
+use crate::simulation::Point2;
+
+const MIN_PARTICLES: u32 = 30;
+const MAX_PARTICLES: u32 = 60;
+pub const PARTICLE_LIFE: f32 = 1.6; // about as long as an interceptor lasts
+pub const PARTICLE_RADIUS: f32 = 3.0;
+const PARTICLE_DRAG: f32 = 2.0; // fraction of its speed a particle loses per second
+const EMBER_COLOR: Color = Color::new(1.0, 0.55, 0.1, 1.0); // orange
+
+// A spark thrown out by an explosion. Purely for looks, the simulation still
+// uses the interceptor's radius for hits.
+#[derive(Debug, Clone, Copy)]
+pub struct Particle {
+    pub pos: Point2,
+    pub vel: Vec2,
+    pub life: f32,
+    pub color: Color,
+    pub kind: ExplosionKind, // particles take the shape of their explosion
+}
+
+// Throw a burst of particles out from pos. Drag brings the fastest ones to
+// rest about radius away, the colors run from orange to hot.
+pub fn spawn_burst(
+    particles: &mut Vec<Particle>,
+    rng: &mut Rand32,
+    pos: Point2,
+    radius: f32,
+    kind: ExplosionKind,
+    hot: Color,
+) {
+    let count = rng.rand_range(MIN_PARTICLES..MAX_PARTICLES + 1);
+    for _ in 0..count {
+        let angle = rng.rand_float() * std::f32::consts::TAU;
+        let speed = rng.rand_float() * radius * PARTICLE_DRAG;
+        let heat = rng.rand_float();
+
+        particles.push(Particle {
+            pos,
+            vel: vec_from_angle(angle) * speed,
+            life: PARTICLE_LIFE * (0.5 + 0.5 * rng.rand_float()),
+            color: Color::new(
+                EMBER_COLOR.r + (hot.r - EMBER_COLOR.r) * heat,
+                EMBER_COLOR.g + (hot.g - EMBER_COLOR.g) * heat,
+                EMBER_COLOR.b + (hot.b - EMBER_COLOR.b) * heat,
+                1.0,
+            ),
+            kind,
+        });
+    }
+}
+
+// Move every particle along, slow it down and prune the dead ones
+pub fn update_particles(particles: &mut Vec<Particle>, dt: f32) {
+    for particle in particles.iter_mut() {
+        particle.pos += particle.vel * dt;
+        particle.vel *= (1.0 - PARTICLE_DRAG * dt).max(0.0);
+        particle.life -= dt;
+    }
+    particles.retain(|p| p.life > 0.0);
+}
@@ -0,0 +1,286 @@
+use glam::*;
+
+use crate::actor::{CURSOR_HEIGHT, CURSOR_WIDTH};
+use crate::simulation::GROUND_HEIGHT;
+
+// World coordinates have the origin at the center of the screen with y up,
+// screen coordinates have it in the top left corner with y down
+
+// convert an angle into a vector, from ggez example
+pub fn vec_from_angle(angle: f32) -> Vec2 {
+    let vx = angle.sin();
+    let vy = angle.cos();
+    Vec2::new(vx, vy)
+}
+
+// get screen coordinates from world coordinates, from ggez example
+pub fn world_to_screen_coords(screen_width: f32, screen_height: f32, point: Vec2) -> Vec2 {
+    let x = point.x + screen_width / 2.0;
+    let y = screen_height - (point.y + screen_height / 2.0);
+    Vec2::new(x, y)
+}
+
+// the inverse of world_to_screen_coords
+pub fn screen_to_world_coords(screen_width: f32, screen_height: f32, point: Vec2) -> Vec2 {
+    let x = point.x - screen_width / 2.0;
+    let y = screen_height / 2.0 - point.y;
+    Vec2::new(x, y)
+}
+
+// Where something flying in a straight line at the given heading and speed,
+// pushed sideways by the wind, will reach ground_y. None if it never comes
+// down that far.
+pub fn predict_impact(pos: Vec2, angle: f32, vel: f32, wind: f32, ground_y: f32) -> Option<Vec2> {
+    let velocity = vec_from_angle(angle) * vel + Vec2::new(wind, 0.0);
+    if pos.y <= ground_y {
+        return Some(pos);
+    }
+    if velocity.y >= 0.0 {
+        return None;
+    }
+    let time = (ground_y - pos.y) / velocity.y;
+    Some(pos + velocity * time)
+}
+
+// How far a jammed radar blip is thrown off its true position, a mix of
+// out of step waves so it jitters without a pattern. Each position gets its
+// own jitter and neither axis is ever off by more than strength.
+pub fn compute_jammer_noise(pos: Vec2, time: f32, strength: f32) -> Vec2 {
+    let phase = pos.x * 12.9898 + pos.y * 78.233;
+    let x = (time * 31.0 + phase).sin() * 0.6 + (time * 57.0 + phase * 1.7).sin() * 0.4;
+    let y = (time * 43.0 + phase * 0.3).sin() * 0.6 + (time * 71.0 + phase * 2.3).sin() * 0.4;
+    Vec2::new(x, y) * strength
+}
+
+// Top left and bottom right corners of the sky in world coordinates
+fn sky_corners(screen_width: f32, screen_height: f32) -> (Vec2, Vec2) {
+    let top_left = screen_to_world_coords(screen_width, screen_height, Vec2::ZERO);
+    let bottom_right = screen_to_world_coords(
+        screen_width,
+        screen_height,
+        Vec2::new(screen_width, screen_height - GROUND_HEIGHT),
+    );
+    (top_left, bottom_right)
+}
+
+// Keeps the cursor inside the sky. Returns the position nudged back toward
+// the play area and whether it was in bounds to begin with, a cursor out of
+// bounds shouldn't take input until it is back inside.
+pub fn check_cursor_bound(pos: Vec2, screen_width: f32, screen_height: f32) -> (Vec2, bool) {
+    let (top_left, bottom_right) = sky_corners(screen_width, screen_height);
+
+    // can't let the cursor get stuck, so adjust for each case
+    if pos.x + CURSOR_WIDTH > bottom_right.x {
+        return (pos - Vec2::new(1.0, 0.0), false);
+    } else if pos.x < top_left.x {
+        return (pos + Vec2::new(1.0, 0.0), false);
+    }
+
+    if pos.y > top_left.y {
+        return (pos - Vec2::new(0.0, 1.0), false);
+    } else if pos.y - CURSOR_HEIGHT < bottom_right.y {
+        return (pos + Vec2::new(0.0, 1.0), false);
+    }
+    (pos, true)
+}
+
+// The closest in bounds position to pos, for input that jumps the cursor
+// straight to a point like the mouse
+pub fn clamp_cursor(pos: Vec2, screen_width: f32, screen_height: f32) -> Vec2 {
+    let (top_left, bottom_right) = sky_corners(screen_width, screen_height);
+    let min = Vec2::new(top_left.x, bottom_right.y + CURSOR_HEIGHT);
+    let max = Vec2::new(bottom_right.x - CURSOR_WIDTH, top_left.y);
+    pos.clamp(min, max)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn screen_coords_round_trip() {
+        let (w, h) = (1280.0, 760.0);
+        for i in 0..=64 {
+            for j in 0..=38 {
+                let p = Vec2::new(-w / 2.0 + i as f32 * 20.0, -h / 2.0 + j as f32 * 20.0);
+                assert_eq!(
+                    screen_to_world_coords(w, h, world_to_screen_coords(w, h, p)),
+                    p
+                );
+            }
+        }
+    }
+
+    // angles are measured clockwise from straight up, so PI is straight
+    // down in the world and down the screen once converted
+    #[test]
+    fn angle_pi_points_down() {
+        let dir = vec_from_angle(std::f32::consts::PI);
+        assert!(dir.x.abs() < 1e-6);
+        assert!((dir.y + 1.0).abs() < 1e-6);
+
+        let (w, h) = (1280.0, 760.0);
+        let from = world_to_screen_coords(w, h, Vec2::ZERO);
+        let to = world_to_screen_coords(w, h, dir);
+        assert!(to.y > from.y);
+    }
+
+    fn close(a: Vec2, b: Vec2) -> bool {
+        (a - b).length() < 1e-3
+    }
+
+    #[test]
+    fn predict_impact_follows_heading_and_wind() {
+        let down = std::f32::consts::PI;
+        let hit = predict_impact(Vec2::new(100.0, 300.0), down, 50.0, 0.0, -200.0).unwrap();
+        assert!(close(hit, Vec2::new(100.0, -200.0)));
+
+        // 500 down at 100 a second is 5 seconds of a 20 a second wind
+        let hit = predict_impact(Vec2::new(100.0, 300.0), down, 100.0, 20.0, -200.0).unwrap();
+        assert!(close(hit, Vec2::new(200.0, -200.0)));
+
+        // 45 degrees covers as much ground sideways as it drops
+        let hit = predict_impact(Vec2::new(0.0, 100.0), down * 0.75, 80.0, 0.0, -100.0).unwrap();
+        assert!(close(hit, Vec2::new(200.0, -100.0)));
+    }
+
+    #[test]
+    fn predict_impact_never_lands_without_falling() {
+        let pos = Vec2::new(0.0, 100.0);
+        assert_eq!(predict_impact(pos, 0.0, 0.0, 0.0, -200.0), None);
+        // wind alone only pushes it sideways
+        assert_eq!(predict_impact(pos, 0.0, 0.0, 30.0, -200.0), None);
+        // climbing
+        assert_eq!(predict_impact(pos, 0.0, 50.0, 0.0, -200.0), None);
+        // not moving but already down there
+        let low = Vec2::new(0.0, -250.0);
+        assert_eq!(predict_impact(low, 0.0, 0.0, 0.0, -200.0), Some(low));
+    }
+
+    #[test]
+    fn predict_impact_out_of_bounds() {
+        let down = std::f32::consts::PI;
+        // at or below the ground already, it lands where it is
+        for pos in [Vec2::new(30.0, -200.0), Vec2::new(-2000.0, -900.0)] {
+            assert_eq!(predict_impact(pos, down, 60.0, 10.0, -200.0), Some(pos));
+        }
+        // off the side of the screen still lands, past the edge
+        let hit = predict_impact(Vec2::new(-900.0, 300.0), down, 100.0, -40.0, -200.0).unwrap();
+        assert!(close(hit, Vec2::new(-1100.0, -200.0)));
+    }
+
+    #[test]
+    fn cursor_in_the_sky_is_in_bounds() {
+        let (w, h) = (1280.0, 760.0);
+        let (top_left, bottom_right) = sky_corners(w, h);
+        for pos in [
+            Vec2::ZERO,
+            Vec2::new(top_left.x, top_left.y),
+            Vec2::new(
+                bottom_right.x - CURSOR_WIDTH,
+                bottom_right.y + CURSOR_HEIGHT,
+            ),
+        ] {
+            assert_eq!(check_cursor_bound(pos, w, h), (pos, true));
+            assert_eq!(clamp_cursor(pos, w, h), pos);
+        }
+    }
+
+    #[test]
+    fn cursor_past_an_edge_is_nudged_back() {
+        let (w, h) = (1280.0, 760.0);
+        let (top_left, bottom_right) = sky_corners(w, h);
+        let cases = [
+            (Vec2::new(bottom_right.x, 0.0), Vec2::new(-1.0, 0.0)),
+            (Vec2::new(top_left.x - 5.0, 0.0), Vec2::new(1.0, 0.0)),
+            (Vec2::new(0.0, top_left.y + 5.0), Vec2::new(0.0, -1.0)),
+            (Vec2::new(0.0, bottom_right.y), Vec2::new(0.0, 1.0)),
+        ];
+        for (pos, nudge) in cases {
+            assert_eq!(check_cursor_bound(pos, w, h), (pos + nudge, false));
+        }
+    }
+
+    #[test]
+    fn cursor_in_a_corner_walks_back_in() {
+        let (w, h) = (1280.0, 760.0);
+        let (top_left, bottom_right) = sky_corners(w, h);
+        for corner in [
+            Vec2::new(top_left.x - 3.0, top_left.y + 3.0),
+            Vec2::new(bottom_right.x + 3.0, top_left.y + 3.0),
+            Vec2::new(top_left.x - 3.0, bottom_right.y - 3.0),
+            Vec2::new(bottom_right.x + 3.0, bottom_right.y - 3.0),
+        ] {
+            let mut pos = corner;
+            let mut steps = 0;
+            loop {
+                let (next, in_bounds) = check_cursor_bound(pos, w, h);
+                if in_bounds {
+                    break;
+                }
+                pos = next;
+                steps += 1;
+                assert!(steps < 100, "stuck at {:?}", pos);
+            }
+            assert_eq!(pos, clamp_cursor(corner, w, h));
+        }
+    }
+
+    #[test]
+    fn clamp_cursor_stops_at_the_edges() {
+        let (w, h) = (1280.0, 760.0);
+        let (top_left, bottom_right) = sky_corners(w, h);
+        let min = Vec2::new(top_left.x, bottom_right.y + CURSOR_HEIGHT);
+        let max = Vec2::new(bottom_right.x - CURSOR_WIDTH, top_left.y);
+
+        assert_eq!(
+            clamp_cursor(Vec2::new(5000.0, 10.0), w, h),
+            Vec2::new(max.x, 10.0)
+        );
+        assert_eq!(
+            clamp_cursor(Vec2::new(-5000.0, 10.0), w, h),
+            Vec2::new(min.x, 10.0)
+        );
+        assert_eq!(
+            clamp_cursor(Vec2::new(10.0, 5000.0), w, h),
+            Vec2::new(10.0, max.y)
+        );
+        assert_eq!(
+            clamp_cursor(Vec2::new(10.0, -5000.0), w, h),
+            Vec2::new(10.0, min.y)
+        );
+        assert_eq!(
+            clamp_cursor(Vec2::new(-5000.0, 5000.0), w, h),
+            Vec2::new(min.x, max.y)
+        );
+        assert_eq!(
+            clamp_cursor(Vec2::new(5000.0, -5000.0), w, h),
+            Vec2::new(max.x, min.y)
+        );
+        assert_eq!(clamp_cursor(Vec2::splat(5000.0), w, h), max);
+        assert_eq!(clamp_cursor(Vec2::splat(-5000.0), w, h), min);
+        assert!(check_cursor_bound(max, w, h).1);
+        assert!(check_cursor_bound(min, w, h).1);
+    }
+
+    #[test]
+    fn jammer_noise_stays_within_strength() {
+        for i in 0..100 {
+            let pos = Vec2::new(i as f32 * 7.3 - 300.0, i as f32 * -3.1 + 200.0);
+            let time = i as f32 * 0.037;
+            assert_eq!(compute_jammer_noise(pos, time, 0.0), Vec2::ZERO);
+            let noise = compute_jammer_noise(pos, time, 12.0);
+            assert!(noise.x.abs() <= 12.0 && noise.y.abs() <= 12.0);
+            assert_eq!(noise, compute_jammer_noise(pos, time, 12.0));
+        }
+    }
+
+    #[test]
+    fn jammer_noise_moves_over_time() {
+        let pos = Vec2::new(40.0, 120.0);
+        let first = compute_jammer_noise(pos, 0.0, 10.0);
+        assert!((1..10).any(|i| compute_jammer_noise(pos, i as f32 * 0.05, 10.0) != first));
+        let elsewhere = compute_jammer_noise(Vec2::new(-40.0, 120.0), 0.0, 10.0);
+        assert_ne!(first, elsewhere);
+    }
+}
@@ -0,0 +1,1773 @@
+use ggez::glam::*;
+use ggez::graphics::{self, Color};
+use ggez::{Context, GameResult};
+use oorandom::Rand32;
+
+use crate::actor::{
+    Actor, ExplosionKind, RocketKind, CURSOR_HEIGHT, CURSOR_WIDTH, EMP_FUSE,
+    INTERCEPTOR_PEAK_SCALE, INTERCEPTOR_PERIOD, WARP_GATE_TIME,
+};
+use crate::config::Config;
+use crate::particles::{Particle, PARTICLE_LIFE, PARTICLE_RADIUS};
+use crate::physics::{compute_jammer_noise, vec_from_angle, world_to_screen_coords};
+use crate::simulation::{
+    FloatingText, GameMode, InterceptorMode, Point2, TravelingInterceptor, Turret, CHARGE_LARGE,
+    EMP_FLASH_TIME, EMP_FREEZE_TIME, FLOATING_TEXT_TIME, FOG_CEILING, GROUND_HEIGHT,
+    GROUND_SEGMENTS, LEVEL_TRANSITION_TIME, METEOR_VEL, SEGMENT_DESTROYED_HEIGHT,
+};
+use crate::ui::{HUD_ELEMENTS, HUD_HANDLE_SIZE};
+use crate::upgrades::UpgradeKind;
+use crate::{
+    GamePhase, MainState, COUNTDOWN_TIME, FLASH_MESSAGE_TIME, FRAME_SAMPLES, WARNING_FADE,
+    WARNING_LEAD,
+};
+
+const CINEMATIC_ASPECT: f32 = 16.0 / 9.0;
+
+const CITY_WIDTH: f32 = 50.0;
+const CITY_HEIGHT: f32 = 20.0;
+const BASE_WIDTH: f32 = 30.0;
+const BASE_HEIGHT: f32 = 10.0;
+const TURRET_WIDTH: f32 = 14.0;
+const TURRET_HEIGHT: f32 = 8.0;
+const TURRET_BARREL: f32 = 14.0;
+const WEAVER_TRACER: Color = Color::new(0.6, 0.4, 1.0, 1.0); // violet
+
+// tracers only show the last stretch of a rocket's flight, measured in a
+// straight line back toward where it came from
+const TRAIL_MAX_LENGTH: f32 = 200.0;
+// the skyline stands on a strip of ground and reaches up to the ground line
+const SKYLINE_HEIGHT: f32 = GROUND_HEIGHT * 0.8;
+const SKYLINE_BUILDINGS: std::ops::Range<u32> = 20..41;
+const BUILDING_MIN_HEIGHT: f32 = 10.0;
+const RUINED_BUILDING_SCALE: f32 = 0.3; // of the height, once the ground under it is destroyed
+const WINDOW_SIZE: f32 = 3.0;
+const WINDOW_SPACING: f32 = 8.0;
+const RUBBLE_COLOR: Color = Color::new(0.4, 0.4, 0.4, 1.0);
+
+const TRANSITION_FADE_TIME: f32 = 0.5;
+
+// the shot preview shows once the cursor has been still this long
+const PREVIEW_DELAY: f32 = 0.2;
+const PREVIEW_ALPHA: f32 = 0.3;
+const PREVIEW_DASH: f32 = 8.0;
+
+// the line from each rocket to the city it is locked on to
+const LOCK_ON_ALPHA: f32 = 0.2;
+const LOCK_ON_DASH: f32 = 6.0;
+const LOCK_ON_JAMMING: f32 = 24.0; // pixels the lock jitters by just after an EMP
+
+const EMP_COLOR: Color = Color::new(0.3, 0.6, 1.0, 1.0);
+const EMP_FLASH_ALPHA: f32 = 0.5;
+const EMP_RING_RADIUS: f32 = 30.0;
+const EMP_PULSE_RADIUS: f32 = 150.0; // the triangle's size when the flash is over
+
+const COUNTDOWN_SIZE: f32 = 120.0;
+const COUNTDOWN_PULSE: f32 = 0.5; // extra scale at the start of each second
+const COUNTDOWN_RED: f32 = 3.0; // seconds left when the digit turns red
+
+const WARNING_COLOR: Color = Color::new(1.0, 0.1, 0.1, 1.0);
+const WARNING_WIDTH: f32 = 16.0;
+const WARNING_HEIGHT: f32 = 12.0;
+
+const CHARGE_RING_RADIUS: f32 = 24.0; // once charged up to a large shot
+
+const DEBUG_PANEL_WIDTH: f32 = 200.0;
+const DEBUG_PANEL_HEIGHT: f32 = 150.0;
+const DEBUG_PANEL_TOP: f32 = 75.0; // below the HUD in the top right
+const DEBUG_PANEL_COLOR: Color = Color::new(0.0, 0.0, 0.0, 0.5);
+const DEBUG_GRAPH_HEIGHT: f32 = 30.0;
+const DEBUG_GRAPH_MAX_MS: f32 = 50.0; // frame time at the top of the graph
+
+const UNAFFORDABLE_COLOR: Color = Color::new(0.4, 0.4, 0.4, 1.0);
+
+// fraction of the cursor's distance from center that the dimmest and the
+// brightest stars shift by
+const STAR_MIN_PARALLAX: f32 = 0.02;
+const STAR_MAX_PARALLAX: f32 = 0.05;
+
+// fractions of the screen height for the statistics screen lines
+const STATS_TOP: f32 = 0.32;
+const STATS_LINE_SPACING: f32 = 0.08;
+
+// colorblind mode swaps the red and green cues for orange and blue
+const COLORBLIND_ROCKET_TRACER: Color = Color::new(1.0, 0.6, 0.0, 1.0);
+const COLORBLIND_INTERCEPTOR_TRACER: Color = Color::new(0.2, 0.5, 1.0, 1.0);
+// rocket tracers thicken with the level instead of turning red
+const COLORBLIND_TRACER_MIN_WIDTH: f32 = 2.5;
+const COLORBLIND_TRACER_MAX_WIDTH: f32 = 7.5;
+const OUTLINE_DASHES: usize = 16;
+
+// the high score table, heights are fractions of the screen and each column
+// is placed by its right edge from the center of the screen
+const TABLE_TOP: f32 = 0.2;
+const TABLE_ROW_SPACING: f32 = 0.06;
+const TABLE_TEXT_SCALE: f32 = 24.0;
+const TABLE_COLUMNS: [f32; 5] = [-230.0, -90.0, 0.0, 120.0, 280.0];
+
+const PAUSE_DIM: Color = Color::new(0.0, 0.0, 0.0, 0.6);
+const RECORD_COLOR: Color = Color::new(1.0, 0.84, 0.0, 1.0); // gold
+
+// Largest 16:9 rect that fits centered in the window, the rest is letterboxed
+pub fn letterbox_viewport(window_width: f32, window_height: f32) -> graphics::Rect {
+    if window_width / window_height > CINEMATIC_ASPECT {
+        // window is wider than 16:9, bars on the left and right
+        let width = window_height * CINEMATIC_ASPECT;
+        graphics::Rect::new((window_width - width) / 2.0, 0.0, width, window_height)
+    } else {
+        // window is taller than 16:9, bars on the top and bottom
+        let height = window_width / CINEMATIC_ASPECT;
+        graphics::Rect::new(0.0, (window_height - height) / 2.0, window_width, height)
+    }
+}
+
+// Colors used to draw the playfield
+#[derive(Debug, Clone, Copy)]
+pub struct Palette {
+    pub background: Color,
+    pub ground: Color,
+    pub city: Color,
+    pub cursor: Color,
+    pub rocket: Color,
+    pub explosion: Color,
+    pub tracer: Option<Color>, // None grades the rocket tracer by level
+    pub skyline: Color,
+    pub window: Color,
+}
+
+pub const DEFAULT_PALETTE: Palette = Palette {
+    background: Color::BLACK,
+    ground: Color::WHITE,
+    city: Color::new(0.3, 0.6, 1.0, 1.0),
+    cursor: Color::WHITE,
+    rocket: Color::WHITE,
+    explosion: Color::WHITE,
+    tracer: None,
+    skyline: Color::new(0.35, 0.4, 0.5, 1.0),
+    window: Color::new(1.0, 0.9, 0.4, 1.0),
+};
+
+pub const NIGHT_VISION_PALETTE: Palette = Palette {
+    background: Color::new(0.02, 0.04, 0.02, 1.0),
+    ground: Color::new(0.0, 0.25, 0.05, 1.0),
+    city: Color::new(0.5, 0.9, 0.5, 1.0),
+    cursor: Color::new(0.85, 1.0, 0.85, 1.0),
+    rocket: Color::new(0.7, 1.0, 0.0, 1.0),
+    explosion: Color::new(0.1, 1.0, 0.2, 1.0),
+    tracer: Some(Color::new(0.4, 0.8, 0.0, 1.0)),
+    skyline: Color::new(0.0, 0.15, 0.03, 1.0),
+    window: Color::new(0.4, 0.9, 0.3, 1.0),
+};
+
+// Faint green horizontal scanlines laid over the whole frame in night vision
+pub const SCANLINE_SHADER: &str = "
+struct VertexOutput {
+    @builtin(position) position: vec4<f32>,
+    @location(0) uv: vec2<f32>,
+    @location(1) color: vec4<f32>,
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    if (u32(in.position.y) % 3u == 0u) {
+        return vec4<f32>(0.0, 1.0, 0.0, 0.08);
+    }
+    return vec4<f32>(0.0, 0.0, 0.0, 0.0);
+}
+";
+
+const LEADER_COLOR: Color = Color::new(1.0, 0.84, 0.0, 1.0);
+const PLAYER2_COLOR: Color = Color::CYAN;
+
+const METEOR_STREAK: f32 = 0.03;
+const RAIN_SIZE: f32 = 2.0;
+const FLOATING_TEXT_RISE: f32 = 40.0;
+
+// Draws a frame of the game. Everything is drawn in screen coordinates for a
+// playfield of the given size, letterboxing shifts the canvas to match.
+pub struct Renderer<'a> {
+    screen_width: f32,
+    screen_height: f32,
+    config: &'a Config,
+    palette: &'a Palette,
+}
+
+impl<'a> Renderer<'a> {
+    pub fn new(
+        screen_width: f32,
+        screen_height: f32,
+        config: &'a Config,
+        palette: &'a Palette,
+    ) -> Renderer<'a> {
+        Renderer {
+            screen_width,
+            screen_height,
+            config,
+            palette,
+        }
+    }
+
+    fn to_screen(&self, point: Point2) -> Point2 {
+        world_to_screen_coords(self.screen_width, self.screen_height, point)
+    }
+
+    // Shift everything drawn in screen coordinates, which come from
+    // world_to_screen_coords, into the letterboxed viewport and by the
+    // screen shake offset. The pixel scale image spans internal_width by
+    // internal_height pixels, and each one covers pixel_scale screen
+    // coordinates, so nothing else needs to know.
+    pub fn set_viewport(&self, canvas: &mut graphics::Canvas, state: &MainState, shake: Vec2) {
+        let pixel_scale = state.config.runtime.pixel_scale.max(1) as f32;
+        if state.cinematic || shake != Vec2::ZERO || pixel_scale > 1.0 {
+            canvas.set_screen_coordinates(graphics::Rect::new(
+                -state.viewport.x - shake.x,
+                -state.viewport.y - shake.y,
+                state.internal_width * pixel_scale,
+                state.internal_height * pixel_scale,
+            ));
+        }
+    }
+
+    pub fn draw_frame(
+        &self,
+        canvas: &mut graphics::Canvas,
+        ctx: &mut Context,
+        state: &MainState,
+    ) -> GameResult {
+        self.draw_ground(canvas, ctx, state)?;
+
+        if state.sim.mode != GameMode::Chaos {
+            for city in &state.sim.cities {
+                self.draw_city(canvas, city);
+            }
+        }
+        for base in &state.sim.bases {
+            self.draw_base(canvas, base);
+        }
+        for turret in &state.sim.turrets {
+            let target = turret.target.and_then(|id| state.sim.rockets.get(id));
+            self.draw_turret(canvas, ctx, turret, target.map(|rocket| rocket.pos));
+        }
+
+        let base = state.sim.nearest_base(state.sim.player.pos);
+        self.draw_cursor(canvas, &state.sim.player, base, self.palette.cursor);
+
+        if state.sim.two_player {
+            let base = state.sim.nearest_base(state.sim.player2.pos);
+            self.draw_cursor(canvas, &state.sim.player2, base, PLAYER2_COLOR);
+        }
+        self.draw_shot_queue(canvas, ctx, &state.sim.player, state.sim.shot_queue.len());
+        self.draw_charge_ring(canvas, ctx, &state.sim.player, state.sim.fire_charge);
+        if state.sim.two_player {
+            let charge = state.sim.player2_fire_charge;
+            self.draw_charge_ring(canvas, ctx, &state.sim.player2, charge);
+        }
+
+        if state.cursor_idle_timer > PREVIEW_DELAY && state.sim.shot_timeout <= 0.0 {
+            // nothing to preview with every base destroyed
+            if let Some(base) = state.sim.nearest_base(state.sim.player.pos) {
+                let radius = state.sim.interceptor_radius();
+                self.draw_shot_preview(canvas, ctx, base, state.sim.player.pos, radius)?;
+            }
+        }
+
+        for rocket in state.sim.rockets.active_iter() {
+            if state.sim.fog_of_war && rocket.pos.y > FOG_CEILING {
+                continue;
+            }
+            let leader = state.sim.leader == Some(rocket.id) || rocket.formation_leader;
+            let target = rocket
+                .target_city_index
+                .and_then(|i| state.sim.cities.get(i))
+                .map(|city| city.pos)
+                // an EMP jams the lock until the rockets thaw
+                .map(|pos| {
+                    let strength =
+                        LOCK_ON_JAMMING * state.sim.frozen_timer.max(0.0) / EMP_FREEZE_TIME;
+                    pos + compute_jammer_noise(rocket.pos, state.sim.frozen_timer, strength)
+                });
+            self.draw_rocket(canvas, ctx, rocket, state.sim.level.level, leader, target)?;
+        }
+
+        // the fog would give away where they are
+        if !state.sim.fog_of_war {
+            for &(x, time) in &state.warnings {
+                self.draw_warning(canvas, ctx, x, time);
+            }
+        }
+
+        for interceptor in state.sim.interceptors.active_iter() {
+            let peak_radius = INTERCEPTOR_PEAK_SCALE * state.sim.blast_radius(interceptor);
+            self.draw_interceptor(canvas, ctx, interceptor, peak_radius);
+        }
+        self.draw_particles(canvas, ctx, &state.particles)?;
+
+        for gate in &state.sim.warp_gates {
+            self.draw_warp_gate(canvas, ctx, gate)?;
+        }
+
+        for emp in &state.sim.emps {
+            self.draw_emp(canvas, ctx, emp);
+        }
+        if state.sim.emp_flash > 0.0 {
+            self.draw_emp_pulse(canvas, ctx, state.sim.emp_pos, state.sim.emp_flash);
+        }
+
+        for interceptor in &state.sim.traveling {
+            self.draw_traveling(canvas, ctx, interceptor);
+        }
+        for shot in &state.sim.in_flight {
+            self.draw_interceptor_tracer(canvas, ctx, shot, 1.0);
+        }
+
+        for meteor in &state.sim.meteors {
+            self.draw_meteor(canvas, ctx, meteor);
+        }
+
+        for drop in &state.sim.raindrops {
+            self.draw_raindrop(canvas, drop);
+        }
+
+        self.draw_hud(canvas, ctx, state)?;
+
+        // the pulse lights up the sky, unless flashing is turned off
+        if state.sim.emp_flash > 0.0 && !self.config.reduced_motion {
+            let mut color = EMP_COLOR;
+            color.a = EMP_FLASH_ALPHA * state.sim.emp_flash / EMP_FLASH_TIME;
+            canvas.draw(
+                &graphics::Quad,
+                graphics::DrawParam::new()
+                    .scale(Vec2::new(self.screen_width, self.screen_height))
+                    .color(color),
+            );
+        }
+
+        Ok(())
+    }
+
+    // The star field behind everything. Brighter stars are closer, so they
+    // drift further against the cursor.
+    pub fn draw_stars(
+        &self,
+        canvas: &mut graphics::Canvas,
+        ctx: &mut Context,
+        state: &MainState,
+    ) -> GameResult {
+        let cursor_x = if self.config.reduced_motion {
+            0.0
+        } else {
+            state.sim.player.pos.x
+        };
+
+        let mut builder = graphics::MeshBuilder::new();
+        for &(pos, brightness) in &state.stars {
+            let parallax = STAR_MIN_PARALLAX + (STAR_MAX_PARALLAX - STAR_MIN_PARALLAX) * brightness;
+            let screen_pos = Vec2::new(
+                pos.x * self.screen_width - cursor_x * parallax,
+                pos.y * self.screen_height,
+            );
+            builder.circle(
+                graphics::DrawMode::fill(),
+                screen_pos,
+                1.0 + brightness,
+                0.5,
+                Color::new(1.0, 1.0, 1.0, brightness),
+            )?;
+        }
+
+        let mesh = graphics::Mesh::from_data(ctx, builder.build());
+        canvas.draw(&mesh, Vec2::ZERO);
+        Ok(())
+    }
+
+    // Title screen, waiting for the player to start
+    pub fn draw_main_menu(
+        &self,
+        canvas: &mut graphics::Canvas,
+        ctx: &mut Context,
+        state: &MainState,
+    ) -> GameResult {
+        self.draw_ground(canvas, ctx, state)?;
+        self.draw_banner(canvas, ctx, "RUST COMMAND", 0.3, self.palette.cursor)?;
+
+        if state.confirm_wipe {
+            let text = "Press Delete again to wipe the checkpoint";
+            self.draw_banner(canvas, ctx, text, 0.38, Color::RED)?;
+        } else if let Some(checkpoint) = state.checkpoint {
+            let text = format!(
+                "Press Return to continue from level {}, Delete to wipe it",
+                checkpoint.save.level
+            );
+            self.draw_banner(canvas, ctx, &text, 0.38, Color::GREEN)?;
+        }
+
+        match state.sim.mode {
+            GameMode::Normal => self.draw_banner(
+                canvas,
+                ctx,
+                &format!("High score: {}", state.sim.scoring.high_score),
+                0.45,
+                Color::WHITE,
+            )?,
+            GameMode::Chaos => self.draw_banner(canvas, ctx, "Chaos mode", 0.45, Color::MAGENTA)?,
+            GameMode::TimedChallenge => self.draw_banner(
+                canvas,
+                ctx,
+                &format!(
+                    "Timed challenge   High score: {}",
+                    state.sim.scoring.high_score
+                ),
+                0.45,
+                Color::YELLOW,
+            )?,
+        }
+
+        let difficulty = format!("< {} >", state.difficulty.name());
+        self.draw_banner(canvas, ctx, &difficulty, 0.52, Color::YELLOW)?;
+        self.draw_banner(canvas, ctx, "Press Space to start", 0.6, Color::WHITE)?;
+        self.draw_banner(
+            canvas,
+            ctx,
+            "Press C for two player co-op",
+            0.68,
+            PLAYER2_COLOR,
+        )?;
+        self.draw_banner(canvas, ctx, "Press I for statistics", 0.76, Color::WHITE)?;
+        self.draw_banner(canvas, ctx, "Press H for high scores", 0.84, Color::WHITE)?;
+        let night_vision = if state.config.night_vision {
+            "Night vision on, press V to turn it off"
+        } else {
+            "Press V for night vision"
+        };
+        self.draw_banner(canvas, ctx, night_vision, 0.92, self.palette.cursor)
+    }
+
+    // The top ten table. Every column is lined up on its right edge, so the
+    // numbers stay in line whatever the font.
+    pub fn draw_high_scores(
+        &self,
+        canvas: &mut graphics::Canvas,
+        ctx: &mut Context,
+        state: &MainState,
+    ) -> GameResult {
+        self.draw_ground(canvas, ctx, state)?;
+        let night_vision = state.config.night_vision;
+        let scores = state.high_scores.table(state.sim.mode, night_vision);
+        let title = if night_vision {
+            "NIGHT VISION HIGH SCORES"
+        } else if state.sim.mode == GameMode::TimedChallenge {
+            "TIMED CHALLENGE HIGH SCORES"
+        } else {
+            "HIGH SCORES"
+        };
+        self.draw_banner(canvas, ctx, title, 0.12, self.palette.cursor)?;
+
+        if scores.is_empty() {
+            self.draw_banner(canvas, ctx, "No high scores yet", 0.45, Color::WHITE)?;
+        }
+
+        let header = ["#", "Score", "Level", "Accuracy", "Date"].map(String::from);
+        let rows = scores.iter().enumerate().map(|(i, entry)| {
+            [
+                format!("{}.", i + 1),
+                entry.score.to_string(),
+                entry.level.to_string(),
+                format!("{:.0}%", entry.accuracy),
+                // just the day, the time would crowd the table
+                entry.date.chars().take(10).collect(),
+            ]
+        });
+
+        let center = self.screen_width / 2.0;
+        for (row, cells) in std::iter::once(header).chain(rows).enumerate() {
+            let y = self.screen_height * (TABLE_TOP + row as f32 * TABLE_ROW_SPACING);
+            let color = if row == 0 {
+                Color::YELLOW
+            } else {
+                Color::WHITE
+            };
+            for (cell, right) in cells.iter().zip(TABLE_COLUMNS) {
+                let mut text = graphics::Text::new(cell.as_str());
+                text.set_scale(TABLE_TEXT_SCALE);
+                let size = text.measure(ctx)?;
+                canvas.draw(
+                    &text,
+                    graphics::DrawParam::new()
+                        .dest(Vec2::new(center + right - size.x, y))
+                        .color(color),
+                );
+            }
+        }
+
+        self.draw_banner(canvas, ctx, "Press any key to go back", 0.92, Color::WHITE)
+    }
+
+    // Lifetime totals from the statistics file
+    pub fn draw_statistics(
+        &self,
+        canvas: &mut graphics::Canvas,
+        ctx: &mut Context,
+        state: &MainState,
+    ) -> GameResult {
+        self.draw_ground(canvas, ctx, state)?;
+        self.draw_banner(canvas, ctx, "STATISTICS", 0.2, self.palette.cursor)?;
+
+        let stats = &state.stats;
+        let seconds = stats.total_play_time_seconds as u64;
+        let lines = [
+            format!("Games played: {}", stats.games_played),
+            format!("Rockets destroyed: {}", stats.total_rockets_destroyed),
+            format!("Shots fired: {}", stats.total_shots_fired),
+            format!("Best score: {}", stats.best_score),
+            format!("Best level: {}", stats.best_level),
+            format!(
+                "Time played: {}h {:02}m {:02}s",
+                seconds / 3600,
+                seconds / 60 % 60,
+                seconds % 60
+            ),
+        ];
+        for (i, line) in lines.iter().enumerate() {
+            let height = STATS_TOP + i as f32 * STATS_LINE_SPACING;
+            self.draw_banner(canvas, ctx, line, height, Color::WHITE)?;
+        }
+
+        self.draw_banner(canvas, ctx, "Press I to go back", 0.85, Color::WHITE)
+    }
+
+    // Final results, with a way back into another game
+    pub fn draw_game_over(
+        &self,
+        canvas: &mut graphics::Canvas,
+        ctx: &mut Context,
+        state: &MainState,
+    ) -> GameResult {
+        let GamePhase::GameOver {
+            final_score,
+            final_level,
+            new_record,
+        } = state.phase
+        else {
+            return Ok(());
+        };
+
+        self.draw_ground(canvas, ctx, state)?;
+
+        let (title, results) = match state.sim.mode {
+            GameMode::Normal => (
+                "GAME OVER",
+                format!("Score: {}   Level: {}", final_score, final_level),
+            ),
+            GameMode::Chaos => ("TIME'S UP", format!("Score: {}", final_score)),
+            GameMode::TimedChallenge => (
+                "TIME'S UP",
+                format!("Score: {}   Level: {}", final_score, final_level),
+            ),
+        };
+        self.draw_banner(canvas, ctx, title, 0.3, Color::RED)?;
+        self.draw_banner(canvas, ctx, &results, 0.45, Color::WHITE)?;
+        let mut accuracy = format!("Accuracy: {}%", state.sim.accuracy());
+        if state.sim.two_player {
+            let [p1, p2] = state.sim.player_kills;
+            accuracy += &format!("   P1 kills: {}   P2 kills: {}", p1, p2);
+        }
+        self.draw_banner(canvas, ctx, &accuracy, 0.52, Color::WHITE)?;
+
+        if new_record {
+            self.draw_banner(canvas, ctx, "New record!", 0.6, RECORD_COLOR)?;
+        }
+
+        let prompt = if state.net.is_some() {
+            "Press Escape to quit"
+        } else {
+            "Press R to restart / Escape to quit"
+        };
+        self.draw_banner(canvas, ctx, prompt, 0.7, Color::WHITE)
+    }
+
+    // FPS, frame time, how many things are in play and a graph of the last
+    // second of frame times, oldest on the left
+    pub fn draw_debug_overlay(
+        &self,
+        canvas: &mut graphics::Canvas,
+        ctx: &mut Context,
+        state: &MainState,
+    ) -> GameResult {
+        let panel = graphics::Rect::new(
+            self.screen_width - DEBUG_PANEL_WIDTH - 10.0,
+            DEBUG_PANEL_TOP,
+            DEBUG_PANEL_WIDTH,
+            DEBUG_PANEL_HEIGHT,
+        );
+        canvas.draw(
+            &graphics::Quad,
+            graphics::DrawParam::new()
+                .dest(panel.point())
+                .scale(panel.size())
+                .color(DEBUG_PANEL_COLOR),
+        );
+
+        let newest = (state.buf_head + FRAME_SAMPLES - 1) % FRAME_SAMPLES;
+        let entities =
+            state.sim.rockets.len() + state.sim.interceptors.len() + state.particles.len();
+        let lines = [
+            format!("FPS: {:.0}", ctx.time.fps()),
+            format!("Frame: {:.1} ms", state.ring_buffer[newest]),
+            format!("Entities: {}", entities),
+            format!("Performance: {:+.1}", state.sim.performance_score),
+            format!("Wave size: {:+}", state.sim.waves.rocket_adjustment),
+        ];
+        for (i, line) in lines.iter().enumerate() {
+            canvas.draw(
+                &graphics::Text::new(line.as_str()),
+                graphics::DrawParam::new()
+                    .dest(Vec2::new(panel.x + 10.0, panel.y + 5.0 + i as f32 * 20.0))
+                    .color(Color::WHITE),
+            );
+        }
+
+        let graph_bottom = panel.y + panel.h - 5.0;
+        let step = (panel.w - 20.0) / (FRAME_SAMPLES - 1) as f32;
+        let points: Vec<Point2> = (0..FRAME_SAMPLES)
+            .map(|i| {
+                let ms = state.ring_buffer[(state.buf_head + i) % FRAME_SAMPLES];
+                let height = (ms / DEBUG_GRAPH_MAX_MS).min(1.0) * DEBUG_GRAPH_HEIGHT;
+                Vec2::new(panel.x + 10.0 + i as f32 * step, graph_bottom - height)
+            })
+            .collect();
+        let graph = graphics::Mesh::new_polyline(
+            ctx,
+            graphics::DrawMode::stroke(1.0),
+            &points,
+            Color::GREEN,
+        )?;
+        canvas.draw(&graph, Vec2::ZERO);
+        Ok(())
+    }
+
+    // Full screen effects and letterboxing, drawn last over everything else
+    pub fn draw_overlay(
+        &self,
+        canvas: &mut graphics::Canvas,
+        ctx: &mut Context,
+        state: &MainState,
+    ) -> GameResult {
+        if let Some(shader) = &state.scanlines {
+            canvas.set_shader(shader);
+            canvas.draw(
+                &graphics::Quad,
+                graphics::DrawParam::new()
+                    .dest(Vec2::new(0.0, 0.0))
+                    .scale(Vec2::new(self.screen_width, self.screen_height)),
+            );
+            canvas.set_default_shader();
+        }
+
+        if state.cinematic {
+            self.draw_letterbox(
+                canvas,
+                state.viewport,
+                (state.window_width, state.window_height),
+            );
+        }
+
+        if state.paused {
+            self.draw_pause(canvas, ctx, state)?;
+        }
+        if state.hud_editor {
+            self.draw_hud_editor(canvas, ctx, state)?;
+        }
+
+        Ok(())
+    }
+
+    // A labelled box around every part of the HUD that can be dragged, the
+    // one being dragged in yellow
+    fn draw_hud_editor(
+        &self,
+        canvas: &mut graphics::Canvas,
+        ctx: &mut Context,
+        state: &MainState,
+    ) -> GameResult {
+        let screen = Vec2::new(self.screen_width, self.screen_height);
+        for element in HUD_ELEMENTS {
+            let handle = state
+                .config
+                .runtime
+                .hud
+                .handle(element, screen, state.healthbar_city());
+            let color = if state.hud_drag == Some(element) {
+                Color::YELLOW
+            } else {
+                Color::WHITE
+            };
+            let size = HUD_HANDLE_SIZE * 2.0;
+            let rect = graphics::Rect::new(
+                handle.x - HUD_HANDLE_SIZE,
+                handle.y - HUD_HANDLE_SIZE,
+                size,
+                size,
+            );
+            let border =
+                graphics::Mesh::new_rectangle(ctx, graphics::DrawMode::stroke(2.0), rect, color)?;
+            canvas.draw(&border, graphics::DrawParam::new());
+
+            let mut label = graphics::Text::new(element.name());
+            label.set_scale(14.0);
+            canvas.draw(
+                &label,
+                graphics::DrawParam::new()
+                    .dest(Vec2::new(rect.x, rect.y - 16.0))
+                    .color(color),
+            );
+        }
+
+        self.draw_banner(canvas, ctx, "HUD EDITOR", 0.45, Color::YELLOW)?;
+        self.draw_banner(canvas, ctx, "Drag to move, F2 to save", 0.52, Color::WHITE)
+    }
+
+    // Dim the whole window, letterbox and all, under the pause message
+    fn draw_pause(
+        &self,
+        canvas: &mut graphics::Canvas,
+        ctx: &mut Context,
+        state: &MainState,
+    ) -> GameResult {
+        let cover = graphics::Rect::new(
+            -state.viewport.x,
+            -state.viewport.y,
+            state.window_width,
+            state.window_height,
+        );
+        canvas.draw(
+            &graphics::Quad,
+            graphics::DrawParam::new()
+                .dest(cover.point())
+                .scale(cover.size())
+                .color(PAUSE_DIM),
+        );
+
+        self.draw_banner(
+            canvas,
+            ctx,
+            "PAUSED \u{2014} press P to resume",
+            0.45,
+            Color::WHITE,
+        )?;
+        self.draw_banner(canvas, ctx, "Escape to quit", 0.55, Color::WHITE)
+    }
+
+    // The strip of ground with the skyline standing on it. Buildings over a
+    // destroyed stretch of ground are gray stumps with their lights out.
+    pub fn draw_ground(
+        &self,
+        canvas: &mut graphics::Canvas,
+        ctx: &mut Context,
+        state: &MainState,
+    ) -> GameResult {
+        let segments = &state.sim.ground_segments;
+        let width = self.screen_width / GROUND_SEGMENTS as f32;
+        for (i, &height) in segments.iter().enumerate() {
+            let strip = height - SKYLINE_HEIGHT;
+            let rect =
+                graphics::Rect::new(i as f32 * width, self.screen_height - strip, width, strip);
+            let color = if height >= SEGMENT_DESTROYED_HEIGHT {
+                RUBBLE_COLOR
+            } else {
+                self.palette.ground
+            };
+            canvas.draw(
+                &graphics::Quad,
+                graphics::DrawParam::new()
+                    .dest(rect.point())
+                    .scale(rect.size())
+                    .color(color),
+            );
+        }
+
+        let mut windows = graphics::MeshBuilder::new();
+        let mut lit = 0;
+        for (i, building) in state.city_buildings.iter().enumerate() {
+            let center = building.x + building.w / 2.0;
+            let segment = ((center * GROUND_SEGMENTS as f32) as usize).min(GROUND_SEGMENTS - 1);
+            let ground_top = self.screen_height - (segments[segment] - SKYLINE_HEIGHT);
+            let ruined = segments[segment] >= SEGMENT_DESTROYED_HEIGHT;
+            let (height, color) = if ruined {
+                (building.h * RUINED_BUILDING_SCALE, RUBBLE_COLOR)
+            } else {
+                (building.h, self.palette.skyline)
+            };
+
+            // down to the bottom of the screen, so it never floats over a
+            // lower stretch of ground next to it
+            let (x, w) = (
+                building.x * self.screen_width,
+                building.w * self.screen_width,
+            );
+            let top = ground_top - height;
+            let rect = graphics::Rect::new(x, top, w, self.screen_height - top);
+            canvas.draw(
+                &graphics::Quad,
+                graphics::DrawParam::new()
+                    .dest(rect.point())
+                    .scale(rect.size())
+                    .color(color),
+            );
+            if ruined {
+                continue;
+            }
+
+            // a fixed scatter of lit windows, so they don't flicker
+            let rows = ((height - WINDOW_SPACING) / WINDOW_SPACING).max(0.0) as usize;
+            let columns = ((w - WINDOW_SPACING / 2.0) / WINDOW_SPACING).max(0.0) as usize;
+            for row in 0..rows {
+                for column in 0..columns {
+                    if (i * 31 + row * 7 + column * 13) % 3 == 0 {
+                        continue;
+                    }
+                    let window = graphics::Rect::new(
+                        x + WINDOW_SPACING / 2.0 + column as f32 * WINDOW_SPACING,
+                        top + WINDOW_SPACING / 2.0 + row as f32 * WINDOW_SPACING,
+                        WINDOW_SIZE,
+                        WINDOW_SIZE,
+                    );
+                    windows.rectangle(graphics::DrawMode::fill(), window, self.palette.window)?;
+                    lit += 1;
+                }
+            }
+        }
+        if lit > 0 {
+            let mesh = graphics::Mesh::from_data(ctx, windows.build());
+            canvas.draw(&mesh, Vec2::ZERO);
+        }
+        Ok(())
+    }
+
+    // A block sitting on the ground, flattened to rubble once destroyed
+    fn draw_city(&self, canvas: &mut graphics::Canvas, city: &Actor) {
+        let (height, color) = if city.life > 0.0 {
+            (CITY_HEIGHT, self.palette.city)
+        } else {
+            (CITY_HEIGHT / 4.0, RUBBLE_COLOR)
+        };
+        let pos = self.to_screen(city.pos);
+        let rect =
+            graphics::Rect::new(pos.x - CITY_WIDTH / 2.0, pos.y - height, CITY_WIDTH, height);
+        canvas.draw(
+            &graphics::Quad,
+            graphics::DrawParam::new()
+                .dest(rect.point())
+                .scale(rect.size())
+                .color(color),
+        );
+    }
+
+    // A squat mound on the ground, flattened to rubble once destroyed
+    fn draw_base(&self, canvas: &mut graphics::Canvas, base: &Actor) {
+        let (height, color) = if base.life > 0.0 {
+            (BASE_HEIGHT, self.palette.cursor)
+        } else {
+            (BASE_HEIGHT / 4.0, RUBBLE_COLOR)
+        };
+        let pos = self.to_screen(base.pos);
+        let rect =
+            graphics::Rect::new(pos.x - BASE_WIDTH / 2.0, pos.y - height, BASE_WIDTH, height);
+        canvas.draw(
+            &graphics::Quad,
+            graphics::DrawParam::new()
+                .dest(rect.point())
+                .scale(rect.size())
+                .color(color),
+        );
+    }
+
+    // A small block with a barrel pointing at its target, or straight up
+    // when it has none
+    fn draw_turret(
+        &self,
+        canvas: &mut graphics::Canvas,
+        ctx: &mut Context,
+        turret: &Turret,
+        target: Option<Point2>,
+    ) {
+        let pos = self.to_screen(turret.pos);
+        let rect = graphics::Rect::new(
+            pos.x - TURRET_WIDTH / 2.0,
+            pos.y - TURRET_HEIGHT,
+            TURRET_WIDTH,
+            TURRET_HEIGHT,
+        );
+        canvas.draw(
+            &graphics::Quad,
+            graphics::DrawParam::new()
+                .dest(rect.point())
+                .scale(rect.size())
+                .color(self.palette.cursor),
+        );
+
+        let top = turret.pos + Vec2::new(0.0, TURRET_HEIGHT);
+        let aim = target
+            .map(|target| (target - top).normalize_or_zero())
+            .filter(|aim| *aim != Vec2::ZERO)
+            .unwrap_or(Vec2::Y);
+        let points = &[
+            self.to_screen(top),
+            self.to_screen(top + aim * TURRET_BARREL),
+        ];
+        let barrel = graphics::Mesh::new_line(ctx, points, 3.0, self.palette.cursor).unwrap();
+        canvas.draw(&barrel, graphics::DrawParam::new());
+    }
+
+    // Two crossed bars, turned to line up with the shot from the base that
+    // would fire at it
+    pub fn draw_cursor(
+        &self,
+        canvas: &mut graphics::Canvas,
+        actor: &Actor,
+        base: Option<Point2>,
+        color: Color,
+    ) {
+        let aim = base.map_or(0.0, |base| {
+            let to_cursor = actor.pos - base;
+            // measured from straight up like rocket angles, see vec_from_angle
+            to_cursor.x.atan2(to_cursor.y)
+        });
+
+        let pos = self.to_screen(actor.pos);
+        let center = Vec2::new(pos.x + CURSOR_WIDTH / 2.0, pos.y + CURSOR_HEIGHT / 2.0);
+        for size in [
+            Vec2::new(CURSOR_WIDTH, CURSOR_HEIGHT),
+            Vec2::new(CURSOR_HEIGHT, CURSOR_WIDTH),
+        ] {
+            canvas.draw(
+                &graphics::Quad,
+                graphics::DrawParam::new()
+                    .dest(center)
+                    .offset(Vec2::new(0.5, 0.5))
+                    .rotation(aim)
+                    .scale(size)
+                    .color(color),
+            );
+        }
+    }
+
+    // Faint dashed line from the launch point to the cursor, with a ring the
+    // size of a fresh explosion where the shot would go off
+    fn draw_shot_preview(
+        &self,
+        canvas: &mut graphics::Canvas,
+        ctx: &mut Context,
+        base: Point2,
+        target: Point2,
+        radius: f32,
+    ) -> GameResult {
+        let mut color = self.palette.explosion;
+        color.a = PREVIEW_ALPHA;
+
+        let start = self.to_screen(base);
+        let end = self.to_screen(target);
+        if start.distance(end) < 1.0 {
+            return Ok(());
+        }
+
+        let mut builder = graphics::MeshBuilder::new();
+        add_dashed_line(&mut builder, start, end, PREVIEW_DASH, 1.5, color)?;
+        builder.circle(graphics::DrawMode::stroke(1.5), end, radius, 1.0, color)?;
+
+        let mesh = graphics::Mesh::from_data(ctx, builder.build());
+        canvas.draw(&mesh, Vec2::ZERO);
+        Ok(())
+    }
+
+    // One dot under the cursor for each queued shot
+    fn draw_shot_queue(
+        &self,
+        canvas: &mut graphics::Canvas,
+        ctx: &mut Context,
+        actor: &Actor,
+        queued: usize,
+    ) {
+        let pos = self.to_screen(actor.pos);
+
+        for i in 0..queued {
+            let dot_pos = Vec2::new(pos.x + i as f32 * CURSOR_WIDTH / 2.0, pos.y + CURSOR_WIDTH);
+            let dot = graphics::Mesh::new_circle(
+                ctx,
+                graphics::DrawMode::fill(),
+                dot_pos,
+                3.0,
+                0.5,
+                Color::WHITE,
+            )
+            .unwrap();
+            canvas.draw(&dot, Vec2::new(0.0, 0.0));
+        }
+    }
+
+    // A ring around the cursor that grows while fire is held, it turns the
+    // explosion color once the shot will go off large
+    fn draw_charge_ring(
+        &self,
+        canvas: &mut graphics::Canvas,
+        ctx: &mut Context,
+        actor: &Actor,
+        charge: f32,
+    ) {
+        if charge <= 0.0 {
+            return;
+        }
+        let color = if charge > CHARGE_LARGE {
+            self.palette.explosion
+        } else {
+            self.palette.cursor
+        };
+
+        let pos = self.to_screen(actor.pos);
+        let center = Vec2::new(pos.x + CURSOR_WIDTH / 2.0, pos.y + CURSOR_HEIGHT / 2.0);
+        let radius = CHARGE_RING_RADIUS * (charge / CHARGE_LARGE).min(1.0);
+        if let Ok(ring) = graphics::Mesh::new_circle(
+            ctx,
+            graphics::DrawMode::stroke(2.0),
+            center,
+            radius.max(1.0),
+            0.5,
+            color,
+        ) {
+            canvas.draw(&ring, Vec2::ZERO);
+        }
+    }
+
+    // A red arrow on the top edge pointing down at where a rocket is about
+    // to come in, it fades once the rocket is on screen
+    fn draw_warning(&self, canvas: &mut graphics::Canvas, ctx: &mut Context, x: f32, time: f32) {
+        if time > WARNING_LEAD + WARNING_FADE {
+            return;
+        }
+        let mut color = WARNING_COLOR;
+        color.a = (time / WARNING_FADE).min(1.0);
+
+        let top = self.to_screen(Point2::new(x, self.screen_height / 2.0));
+        let points = [
+            Vec2::new(top.x - WARNING_WIDTH / 2.0, top.y),
+            Vec2::new(top.x + WARNING_WIDTH / 2.0, top.y),
+            Vec2::new(top.x, top.y + WARNING_HEIGHT),
+        ];
+        if let Ok(arrow) =
+            graphics::Mesh::new_polygon(ctx, graphics::DrawMode::fill(), &points, color)
+        {
+            canvas.draw(&arrow, Vec2::ZERO);
+        }
+    }
+
+    pub fn draw_rocket(
+        &self,
+        canvas: &mut graphics::Canvas,
+        ctx: &mut Context,
+        actor: &Actor,
+        level: u32,
+        leader: bool,
+        target: Option<Point2>,
+    ) -> GameResult {
+        let (width, height) = actor.kind.size();
+        // the tracer ends at the back of the body, which is turned to face
+        // the way the rocket flies
+        let endpoint = actor.pos - vec_from_angle(actor.angle) * actor.depth * height / 2.0;
+        let trail = endpoint - actor.initial_pos;
+        let start = endpoint - trail.normalize_or_zero() * trail.length().min(TRAIL_MAX_LENGTH);
+
+        let points = &[self.to_screen(start), self.to_screen(endpoint)];
+
+        // tracer line
+        let mut modifier = level as f32 / 10.0;
+
+        if modifier > 1.0 {
+            modifier = 1.0;
+        }
+
+        let (tracer_color, tracer_width) = match actor.kind {
+            RocketKind::Splitter => (Color::MAGENTA, 5.0),
+            RocketKind::Homing => (Color::RED, 5.0),
+            RocketKind::Weaver => (WEAVER_TRACER, 5.0),
+            _ if self.config.runtime.colorblind_mode => (
+                self.palette.tracer.unwrap_or(COLORBLIND_ROCKET_TRACER),
+                COLORBLIND_TRACER_MIN_WIDTH
+                    + (COLORBLIND_TRACER_MAX_WIDTH - COLORBLIND_TRACER_MIN_WIDTH) * modifier,
+            ),
+            _ => (
+                self.palette
+                    .tracer
+                    .unwrap_or(Color::new(modifier, 1.0 - modifier, 0.0, 1.0)),
+                5.0,
+            ),
+        };
+
+        // chaos and tiny rockets leave no tracer, which sets them apart
+        let has_tracer = !matches!(actor.kind, RocketKind::Chaos | RocketKind::Tiny);
+        if has_tracer {
+            let line =
+                graphics::Mesh::new_line(ctx, points, tracer_width * actor.depth, tracer_color)
+                    .unwrap();
+
+            canvas.draw(&line, Vec2::new(0.0, 0.0));
+        }
+
+        let body_color = match actor.kind {
+            _ if leader => LEADER_COLOR,
+            RocketKind::Standard => self.palette.rocket,
+            RocketKind::Chaos => Color::MAGENTA,
+            RocketKind::Tiny => self.palette.rocket,
+            RocketKind::Splitter | RocketKind::Homing | RocketKind::Weaver => self.palette.rocket,
+        };
+
+        // rocket body, sized by how close its layer is and turned about its
+        // center. Screen rotation is clockwise with y down, which matches
+        // angles measured clockwise from straight up in the world.
+        canvas.draw(
+            &graphics::Quad,
+            graphics::DrawParam::new()
+                .dest(self.to_screen(actor.pos))
+                .offset(Vec2::new(0.5, 0.5))
+                .rotation(actor.angle)
+                .scale(Vec2::new(width, height) * actor.depth)
+                .color(body_color),
+        );
+
+        // a faint dashed line down to the city it is locked on to
+        if let (true, Some(target)) = (has_tracer, target) {
+            let start = self.to_screen(actor.pos);
+            let end = self.to_screen(target);
+            if start.distance(end) >= 1.0 {
+                let mut color = tracer_color;
+                color.a = LOCK_ON_ALPHA;
+                let mut builder = graphics::MeshBuilder::new();
+                add_dashed_line(&mut builder, start, end, LOCK_ON_DASH, 1.0, color)?;
+                let mesh = graphics::Mesh::from_data(ctx, builder.build());
+                canvas.draw(&mesh, Vec2::ZERO);
+            }
+        }
+        Ok(())
+    }
+
+    pub fn draw_interceptor(
+        &self,
+        canvas: &mut graphics::Canvas,
+        ctx: &mut Context,
+        actor: &Actor,
+        peak_radius: f32,
+    ) {
+        if self.config.runtime.colorblind_mode
+            && actor.explosion_kind() == ExplosionKind::Interceptor
+        {
+            self.draw_dashed_circle(canvas, ctx, self.to_screen(actor.pos), actor.radius);
+        }
+        if self.config.reduced_motion {
+            self.draw_interceptor_flash(canvas, ctx, actor, peak_radius);
+            return;
+        }
+        // ground hits weren't launched from anywhere, so they have no tracer
+        if !actor.fired {
+            return;
+        }
+
+        // the explosion itself is drawn by its particles
+        self.draw_interceptor_tracer(canvas, ctx, actor, actor.elapsed / INTERCEPTOR_PERIOD);
+    }
+
+    // Line from the launch base to the interceptor, it grows while the shot
+    // is in flight and fades along with the explosion
+    fn draw_interceptor_tracer(
+        &self,
+        canvas: &mut graphics::Canvas,
+        ctx: &mut Context,
+        actor: &Actor,
+        alpha: f32,
+    ) {
+        let points = &[self.to_screen(actor.initial_pos), self.to_screen(actor.pos)];
+
+        let mut tracer_color = if self.config.runtime.colorblind_mode {
+            COLORBLIND_INTERCEPTOR_TRACER
+        } else {
+            self.palette.explosion
+        };
+        tracer_color.a = alpha;
+        // nothing to draw the moment a shot leaves the base
+        if let Ok(line) = graphics::Mesh::new_line(ctx, points, 5.0, tracer_color) {
+            canvas.draw(&line, Vec2::ZERO);
+        }
+    }
+
+    // Outline of the blast as it stands right now, in dashes so it can't be
+    // mistaken for anything drawn in a solid line
+    fn draw_dashed_circle(
+        &self,
+        canvas: &mut graphics::Canvas,
+        ctx: &mut Context,
+        center: Point2,
+        radius: f32,
+    ) {
+        use std::f32::consts::TAU;
+
+        if radius < 1.0 {
+            return;
+        }
+        let point = |angle: f32| center + Vec2::new(angle.cos(), angle.sin()) * radius;
+        let dash = TAU / OUTLINE_DASHES as f32;
+
+        let mut builder = graphics::MeshBuilder::new();
+        for i in 0..OUTLINE_DASHES {
+            let start = i as f32 * dash;
+            let points = [point(start), point(start + dash / 2.0)];
+            if builder.line(&points, 2.0, self.palette.explosion).is_err() {
+                return;
+            }
+        }
+        let mesh = graphics::Mesh::from_data(ctx, builder.build());
+        canvas.draw(&mesh, Vec2::ZERO);
+    }
+
+    // Every particle in one mesh, fading out as it dies. Interceptor sparks
+    // are round, ground impact ones square and EMP ones triangles, like the
+    // explosions they came from.
+    fn draw_particles(
+        &self,
+        canvas: &mut graphics::Canvas,
+        ctx: &mut Context,
+        particles: &[Particle],
+    ) -> GameResult {
+        if particles.is_empty() {
+            return Ok(());
+        }
+
+        let mut builder = graphics::MeshBuilder::new();
+        for particle in particles {
+            let pos = self.to_screen(particle.pos);
+            let mut color = particle.color;
+            color.a = (particle.life / PARTICLE_LIFE).min(1.0);
+
+            match particle.kind {
+                ExplosionKind::Interceptor => {
+                    builder.circle(graphics::DrawMode::fill(), pos, PARTICLE_RADIUS, 0.5, color)?
+                }
+                ExplosionKind::GroundImpact => builder.rectangle(
+                    graphics::DrawMode::fill(),
+                    graphics::Rect::new(
+                        pos.x - PARTICLE_RADIUS,
+                        pos.y - PARTICLE_RADIUS,
+                        PARTICLE_RADIUS * 2.0,
+                        PARTICLE_RADIUS * 2.0,
+                    ),
+                    color,
+                )?,
+                ExplosionKind::Emp => builder.polygon(
+                    graphics::DrawMode::fill(),
+                    &triangle_points(pos, PARTICLE_RADIUS),
+                    color,
+                )?,
+            };
+        }
+
+        let mesh = graphics::Mesh::from_data(ctx, builder.build());
+        canvas.draw(&mesh, Vec2::ZERO);
+        Ok(())
+    }
+
+    // Reduced motion stand-in for the interceptor animation, a steady circle at
+    // the peak explosion radius without the fading tracer
+    fn draw_interceptor_flash(
+        &self,
+        canvas: &mut graphics::Canvas,
+        ctx: &mut Context,
+        actor: &Actor,
+        peak_radius: f32,
+    ) {
+        let pos = self.to_screen(actor.pos);
+
+        self.draw_explosion_shape(
+            canvas,
+            ctx,
+            actor.explosion_kind(),
+            pos,
+            peak_radius,
+            graphics::DrawMode::stroke(2.0),
+        );
+    }
+
+    // Interceptors explode as circles, ground impacts as squares and EMPs as
+    // triangles, so they stay distinct without color. pos is in screen
+    // coordinates.
+    pub fn draw_explosion_shape(
+        &self,
+        canvas: &mut graphics::Canvas,
+        ctx: &mut Context,
+        kind: ExplosionKind,
+        pos: Point2,
+        radius: f32,
+        mode: graphics::DrawMode,
+    ) {
+        let mesh = match kind {
+            ExplosionKind::Interceptor => graphics::Mesh::new_circle(
+                ctx,
+                mode,
+                pos,
+                radius,
+                10.0, // for weird pixellated polygon action
+                self.palette.explosion,
+            ),
+            ExplosionKind::GroundImpact => graphics::Mesh::new_rectangle(
+                ctx,
+                mode,
+                graphics::Rect::new(pos.x - radius, pos.y - radius, radius * 2.0, radius * 2.0),
+                self.palette.explosion,
+            ),
+            ExplosionKind::Emp => {
+                graphics::Mesh::new_polygon(ctx, mode, &triangle_points(pos, radius), EMP_COLOR)
+            }
+        }
+        .unwrap();
+
+        canvas.draw(&mesh, Vec2::new(0.0, 0.0));
+    }
+
+    // A ring closing in on the spot as the fuse burns down
+    fn draw_emp(&self, canvas: &mut graphics::Canvas, ctx: &mut Context, actor: &Actor) {
+        let radius = EMP_RING_RADIUS * (actor.elapsed / EMP_FUSE).max(0.1);
+        if let Ok(ring) = graphics::Mesh::new_circle(
+            ctx,
+            graphics::DrawMode::stroke(2.0),
+            self.to_screen(actor.pos),
+            radius,
+            1.0,
+            EMP_COLOR,
+        ) {
+            canvas.draw(&ring, Vec2::ZERO);
+        }
+    }
+
+    // The pulse spreads out from the EMP as a triangle while the sky flashes,
+    // or sits at its full size with reduced motion
+    fn draw_emp_pulse(
+        &self,
+        canvas: &mut graphics::Canvas,
+        ctx: &mut Context,
+        pos: Point2,
+        flash: f32,
+    ) {
+        let spread = if self.config.reduced_motion {
+            1.0
+        } else {
+            1.0 - flash / EMP_FLASH_TIME
+        };
+        self.draw_explosion_shape(
+            canvas,
+            ctx,
+            ExplosionKind::Emp,
+            self.to_screen(pos),
+            EMP_PULSE_RADIUS * spread.max(0.1),
+            graphics::DrawMode::stroke(2.0),
+        );
+    }
+
+    // Draw the warp gate as a green ring of spinning arcs
+    fn draw_warp_gate(
+        &self,
+        canvas: &mut graphics::Canvas,
+        ctx: &mut Context,
+        actor: &Actor,
+    ) -> GameResult {
+        let pos = self.to_screen(actor.pos);
+
+        const ARCS: u32 = 3;
+        const ARC_SEGMENTS: u32 = 8;
+        let arc_len = std::f32::consts::PI / ARCS as f32;
+
+        // hold the ring still and solid when motion is reduced
+        let (spin, alpha) = if self.config.reduced_motion {
+            (0.0, 1.0)
+        } else {
+            (actor.angle, (actor.elapsed / WARP_GATE_TIME).min(1.0))
+        };
+        let color = Color::new(0.0, 1.0, 0.0, alpha);
+
+        let mut builder = graphics::MeshBuilder::new();
+        for arc in 0..ARCS {
+            let start = spin + arc as f32 * 2.0 * arc_len;
+            let points: Vec<Point2> = (0..=ARC_SEGMENTS)
+                .map(|i| {
+                    let theta = start + arc_len * i as f32 / ARC_SEGMENTS as f32;
+                    pos + Vec2::new(theta.cos(), theta.sin()) * actor.radius
+                })
+                .collect();
+            builder.polyline(graphics::DrawMode::stroke(4.0), &points, color)?;
+        }
+
+        let ring = graphics::Mesh::from_data(ctx, builder.build());
+        canvas.draw(&ring, Vec2::new(0.0, 0.0));
+        Ok(())
+    }
+
+    // Traveling interceptors are a small dot with a marker on their target
+    fn draw_traveling(
+        &self,
+        canvas: &mut graphics::Canvas,
+        ctx: &mut Context,
+        interceptor: &TravelingInterceptor,
+    ) {
+        let pos = self.to_screen(interceptor.pos);
+        let target = self.to_screen(interceptor.target);
+
+        let body = graphics::Mesh::new_circle(
+            ctx,
+            graphics::DrawMode::fill(),
+            pos,
+            4.0,
+            0.5,
+            self.palette.explosion,
+        )
+        .unwrap();
+        canvas.draw(&body, Vec2::new(0.0, 0.0));
+
+        let marker = graphics::Mesh::new_circle(
+            ctx,
+            graphics::DrawMode::stroke(1.0),
+            target,
+            6.0,
+            0.5,
+            self.palette.explosion,
+        )
+        .unwrap();
+        canvas.draw(&marker, Vec2::new(0.0, 0.0));
+    }
+
+    // Meteors are short gray streaks pointing back along their path
+    fn draw_meteor(&self, canvas: &mut graphics::Canvas, ctx: &mut Context, actor: &Actor) {
+        let tail = actor.pos - vec_from_angle(actor.angle) * METEOR_VEL * METEOR_STREAK;
+
+        let points = &[self.to_screen(tail), self.to_screen(actor.pos)];
+        let line =
+            graphics::Mesh::new_line(ctx, points, 2.0, Color::new(0.6, 0.6, 0.6, 1.0)).unwrap();
+
+        canvas.draw(&line, Vec2::new(0.0, 0.0));
+    }
+
+    fn draw_raindrop(&self, canvas: &mut graphics::Canvas, actor: &Actor) {
+        let pos = self.to_screen(actor.pos);
+
+        canvas.draw(
+            &graphics::Quad,
+            graphics::DrawParam::new()
+                .dest(pos)
+                .scale(Vec2::new(RAIN_SIZE, RAIN_SIZE))
+                .color(Color::new(0.3, 0.5, 1.0, 0.8)),
+        );
+    }
+
+    // Cover everything outside the viewport with black bars. Expects the canvas
+    // to already be offset so that the viewport starts at the origin.
+    fn draw_letterbox(
+        &self,
+        canvas: &mut graphics::Canvas,
+        viewport: graphics::Rect,
+        window: (f32, f32),
+    ) {
+        let (window_w, window_h) = window;
+
+        let bars = [
+            // top and bottom
+            graphics::Rect::new(-viewport.x, -viewport.y, window_w, viewport.y),
+            graphics::Rect::new(
+                -viewport.x,
+                viewport.h,
+                window_w,
+                window_h - viewport.h - viewport.y,
+            ),
+            // left and right
+            graphics::Rect::new(-viewport.x, -viewport.y, viewport.x, window_h),
+            graphics::Rect::new(
+                viewport.w,
+                -viewport.y,
+                window_w - viewport.w - viewport.x,
+                window_h,
+            ),
+        ];
+
+        for bar in bars {
+            if bar.w <= 0.0 || bar.h <= 0.0 {
+                continue;
+            }
+            canvas.draw(
+                &graphics::Quad,
+                graphics::DrawParam::new()
+                    .dest(bar.point())
+                    .scale(bar.size())
+                    .color(Color::BLACK),
+            );
+        }
+    }
+
+    // Interceptor mode and all the announcement banners, the rest of the HUD
+    // is made of widgets in the ui module
+    pub fn draw_hud(
+        &self,
+        canvas: &mut graphics::Canvas,
+        ctx: &mut Context,
+        state: &MainState,
+    ) -> GameResult {
+        if state.sim.interceptor_mode != InterceptorMode::Standard {
+            canvas.draw(
+                &graphics::Text::new(state.sim.interceptor_mode.name()),
+                graphics::DrawParam::new()
+                    .dest(Vec2::new(20.0, 30.0))
+                    .color(Color::GREEN),
+            );
+        }
+
+        if let Some(modifier) = state.sim.level.modifier {
+            if state.sim.modifier_banner > 0.0 {
+                self.draw_banner(canvas, ctx, modifier.name(), 1.0 / 3.0, Color::YELLOW)?;
+            }
+        }
+
+        if state.sim.bullet_hell_banner > 0.0 {
+            self.draw_banner(canvas, ctx, "BULLET HELL!", 0.5, Color::RED)?;
+        }
+
+        if state.sim.waves.wall_warning.is_some() {
+            self.draw_banner(canvas, ctx, "WALL INCOMING!", 0.5, Color::RED)?;
+        }
+
+        for text in &state.sim.floating_texts {
+            self.draw_floating_text(canvas, text);
+        }
+
+        if state.sim.no_miss_banner > 0.0 {
+            self.draw_banner(canvas, ctx, "NO MISS!", 0.25, Color::CYAN)?;
+        }
+
+        if state.sim.leader_banner > 0.0 {
+            self.draw_banner(canvas, ctx, "LEADER DOWN", 0.4, LEADER_COLOR)?;
+        }
+
+        if let Some(step) = state.tutorial {
+            let text = step.instruction(&state.bindings);
+            self.draw_banner(canvas, ctx, &text, 0.3, Color::YELLOW)?;
+        }
+
+        self.draw_countdown(canvas, ctx, state)?;
+
+        if let Some((message, timer)) = &state.flash_message {
+            let mut color = Color::GREEN;
+            if !self.config.reduced_motion {
+                color.a = timer / FLASH_MESSAGE_TIME;
+            }
+            self.draw_banner(canvas, ctx, message, 0.6, color)?;
+        }
+
+        if let Some(offers) = state.sim.shop {
+            let sim = &state.sim;
+            let repair_cost = sim
+                .bases
+                .iter()
+                .any(|b| b.life <= 0.0)
+                .then(|| sim.repair_cost());
+            self.draw_shop(
+                canvas,
+                ctx,
+                &offers,
+                repair_cost,
+                sim.level.level,
+                sim.scoring.score,
+            )?;
+        } else if state.sim.transitioning {
+            // fade in at the start of the break and out at the end of it
+            let timer = state.sim.transition_timer;
+            let mut color = Color::WHITE;
+            if !self.config.reduced_motion {
+                let fade = ((LEVEL_TRANSITION_TIME - timer) / TRANSITION_FADE_TIME)
+                    .min(timer / TRANSITION_FADE_TIME);
+                color.a = fade.clamp(0.0, 1.0);
+            }
+            let text = format!("Level {}", state.sim.level.level);
+            self.draw_banner(canvas, ctx, &text, 0.5, color)?;
+        }
+
+        Ok(())
+    }
+
+    // The upgrades on offer between levels, the ones the score can't cover
+    // are grayed out. The repair is only offered with a base to repair.
+    fn draw_shop(
+        &self,
+        canvas: &mut graphics::Canvas,
+        ctx: &mut Context,
+        offers: &[UpgradeKind],
+        repair_cost: Option<i32>,
+        level: u32,
+        score: i32,
+    ) -> GameResult {
+        let price_color = |cost: i32| {
+            if cost <= score {
+                Color::WHITE
+            } else {
+                UNAFFORDABLE_COLOR
+            }
+        };
+
+        self.draw_banner(canvas, ctx, "UPGRADES", 0.25, Color::YELLOW)?;
+        for (i, upgrade) in offers.iter().enumerate() {
+            let cost = upgrade.cost(level);
+            let text = format!("{}. {} ({})", i + 1, upgrade.name(), cost);
+            let color = price_color(cost);
+            self.draw_banner(canvas, ctx, &text, 0.37 + i as f32 * 0.08, color)?;
+        }
+        let prompt = if let Some(cost) = repair_cost {
+            let text = format!("4. Repair a launch base ({})", cost);
+            let color = price_color(cost);
+            self.draw_banner(canvas, ctx, &text, 0.61, color)?;
+            "Press 1-4 to buy, Space to skip"
+        } else {
+            "Press 1-3 to buy, Space to skip"
+        };
+        self.draw_banner(canvas, ctx, prompt, 0.7, Color::WHITE)
+    }
+
+    // Text floats upward and fades out over its lifetime
+    fn draw_floating_text(&self, canvas: &mut graphics::Canvas, text: &FloatingText) {
+        let progress = 1.0 - text.timer / FLOATING_TEXT_TIME;
+
+        let mut color = Color::from(text.color);
+        let mut pos = text.pos;
+        if !self.config.reduced_motion {
+            pos.y += progress * FLOATING_TEXT_RISE;
+            color.a = 1.0 - progress;
+        }
+
+        canvas.draw(
+            &graphics::Text::new(text.text.as_str()),
+            graphics::DrawParam::new()
+                .dest(self.to_screen(pos))
+                .color(color),
+        );
+    }
+
+    // The seconds left in the level, or the game in a timed challenge, as
+    // one big digit in the middle of the screen. It swells on each new
+    // second and turns red near the end.
+    fn draw_countdown(
+        &self,
+        canvas: &mut graphics::Canvas,
+        ctx: &mut Context,
+        state: &MainState,
+    ) -> GameResult {
+        let timer = state.sim.countdown_timer();
+        if state.sim.transitioning || state.sim.rockets_held || timer > COUNTDOWN_TIME {
+            return Ok(());
+        }
+
+        let pulse = if self.config.reduced_motion {
+            0.0
+        } else {
+            state.countdown_pulse
+        };
+        let mut text = graphics::Text::new((timer.ceil() as u32).to_string());
+        text.set_scale(COUNTDOWN_SIZE * (1.0 + pulse * COUNTDOWN_PULSE));
+        let size = text.measure(ctx)?;
+        let color = if timer <= COUNTDOWN_RED {
+            Color::RED
+        } else {
+            Color::WHITE
+        };
+
+        canvas.draw(
+            &text,
+            graphics::DrawParam::new()
+                .dest(Vec2::new(
+                    (self.screen_width - size.x) / 2.0,
+                    (self.screen_height - size.y) / 2.0,
+                ))
+                .color(color),
+        );
+        Ok(())
+    }
+
+    // Big centered announcement text, height is given as a fraction of the screen
+    fn draw_banner(
+        &self,
+        canvas: &mut graphics::Canvas,
+        ctx: &mut Context,
+        message: &str,
+        height: f32,
+        color: Color,
+    ) -> GameResult {
+        let mut text = graphics::Text::new(message);
+        text.set_scale(40.0);
+        let size = text.measure(ctx)?;
+
+        canvas.draw(
+            &text,
+            graphics::DrawParam::new()
+                .dest(Vec2::new(
+                    (self.screen_width - size.x) / 2.0,
+                    self.screen_height * height - size.y / 2.0,
+                ))
+                .color(color),
+        );
+        Ok(())
+    }
+}
+
+// The corners of an upright equilateral triangle radius from pos to each
+// corner, in screen coordinates
+fn triangle_points(pos: Point2, radius: f32) -> [Point2; 3] {
+    let half_width = radius * 3f32.sqrt() / 2.0;
+    [
+        pos + Vec2::new(0.0, -radius),
+        pos + Vec2::new(half_width, radius / 2.0),
+        pos + Vec2::new(-half_width, radius / 2.0),
+    ]
+}
+
+// Dashes of the given length from start to end in screen coordinates, with
+// gaps as long as the dashes
+fn add_dashed_line(
+    builder: &mut graphics::MeshBuilder,
+    start: Vec2,
+    end: Vec2,
+    dash: f32,
+    width: f32,
+    color: Color,
+) -> GameResult {
+    let length = start.distance(end);
+    let dir = (end - start) / length;
+    let mut dash_start = 0.0;
+    while dash_start < length {
+        let dash_end = (dash_start + dash).min(length);
+        builder.line(
+            &[start + dir * dash_start, start + dir * dash_end],
+            width,
+            color,
+        )?;
+        dash_start += dash * 2.0;
+    }
+    Ok(())
+}
+
+// Columns of random widths and heights across the ground, generated once so
+// the skyline stays the same all session. x and w are fractions of the
+// screen width so it fits any window, h is in pixels.
+pub fn generate_skyline(rng: &mut Rand32) -> Vec<graphics::Rect> {
+    let count = rng.rand_range(SKYLINE_BUILDINGS);
+    let widths: Vec<f32> = (0..count).map(|_| 0.5 + rng.rand_float()).collect();
+    let total: f32 = widths.iter().sum();
+
+    let mut x = 0.0;
+    widths
+        .iter()
+        .map(|width| {
+            let w = width / total;
+            let h = BUILDING_MIN_HEIGHT + rng.rand_float() * (SKYLINE_HEIGHT - BUILDING_MIN_HEIGHT);
+            let building = graphics::Rect::new(x, 0.0, w, h);
+            x += w;
+            building
+        })
+        .collect()
+}
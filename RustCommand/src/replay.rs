@@ -0,0 +1,249 @@
+use std::fs;
+use std::io;
+
+use serde::{Deserialize, Serialize};
+
+use crate::input::InputState;
+use crate::level::DifficultyPreset;
+use crate::save::{Checkpoint, SaveState};
+use crate::simulation::{Command, GameMode, GameSimulation};
+
+const REPLAY_FILE: &str = "last_replay.bin";
+
+// Bumped whenever the layout changes, a replay from another version can't
+// be played back
+const REPLAY_VERSION: u32 = 3;
+
+// Where the recorded game picked up from
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum ReplayStart {
+    NewGame,
+    Resume(SaveState),
+    Checkpoint(Checkpoint),
+}
+
+// One tick of a recorded game
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+pub struct Frame {
+    pub time: f32, // seconds since the game started
+    pub input: InputState,
+    pub commands: Vec<Command>, // carried out just before the tick
+}
+
+// The simulation is deterministic from its seed, so a game can be played back
+// from the seed, how it was set up, and the input and commands of every tick
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+pub struct Replay {
+    version: u32, // first, so it can be checked before the rest is read
+    pub seed: u64,
+    pub mode: GameMode,
+    pub difficulty: DifficultyPreset,
+    pub adaptive: bool, // whether the wave sizes followed the player
+    pub shop_enabled: bool,
+    pub miss_penalty: i32,
+    pub screen_size: (f32, f32),
+    pub start: ReplayStart,
+    pub log: Vec<Frame>,
+    // commands since the last tick, they go in with the next frame
+    #[serde(skip)]
+    pending: Vec<Command>,
+}
+
+impl Replay {
+    // Start recording a game that is about to be played from sim as it is now
+    pub fn new(
+        seed: u64,
+        sim: &GameSimulation,
+        difficulty: DifficultyPreset,
+        start: ReplayStart,
+    ) -> Replay {
+        Replay {
+            version: REPLAY_VERSION,
+            seed,
+            mode: sim.mode,
+            difficulty,
+            adaptive: sim.adaptive,
+            shop_enabled: sim.shop_enabled,
+            miss_penalty: sim.scoring.miss_penalty,
+            screen_size: (sim.screen_width, sim.screen_height),
+            start,
+            log: Vec::new(),
+            pending: Vec::new(),
+        }
+    }
+
+    pub fn command(&mut self, command: Command) {
+        self.pending.push(command);
+    }
+
+    pub fn record(&mut self, dt: f32, input: &InputState) {
+        let time = self.log.last().map_or(0.0, |frame| frame.time + dt);
+        self.log.push(Frame {
+            time,
+            input: input.clone(),
+            commands: std::mem::take(&mut self.pending),
+        });
+    }
+
+    // The recorded game as it was just before its first tick, set up in the
+    // same order a live game is
+    pub fn game(&self) -> GameSimulation {
+        let (width, height) = self.screen_size;
+        let mut sim = GameSimulation::new(width, height, self.mode, self.seed, false);
+        sim.set_difficulty(DifficultyPreset::Normal);
+        sim.shop_enabled = self.shop_enabled;
+        sim.adaptive = self.adaptive;
+        sim.scoring.miss_penalty = self.miss_penalty;
+        match self.start {
+            ReplayStart::NewGame => (),
+            ReplayStart::Resume(save) => save.restore(&mut sim),
+            ReplayStart::Checkpoint(checkpoint) => checkpoint.restore(&mut sim),
+        }
+        sim.set_difficulty(self.difficulty);
+        sim
+    }
+
+    fn to_bytes(&self) -> bincode::Result<Vec<u8>> {
+        bincode::serialize(self)
+    }
+
+    fn from_bytes(bytes: &[u8]) -> io::Result<Replay> {
+        let invalid = |reason: String| io::Error::new(io::ErrorKind::InvalidData, reason);
+        let version: u32 = bincode::deserialize(bytes)
+            .map_err(|_| invalid(format!("{} is not a replay", REPLAY_FILE)))?;
+        if version != REPLAY_VERSION {
+            return Err(invalid(format!(
+                "{} is from replay version {}, this game plays version {}",
+                REPLAY_FILE, version, REPLAY_VERSION
+            )));
+        }
+        bincode::deserialize(bytes).map_err(|e| invalid(format!("{}: {}", REPLAY_FILE, e)))
+    }
+}
+
+pub fn save_replay(replay: &Replay) -> io::Result<()> {
+    let bytes = replay.to_bytes().map_err(io::Error::other)?;
+    fs::write(REPLAY_FILE, bytes)
+}
+
+pub fn load_replay() -> io::Result<Replay> {
+    Replay::from_bytes(&fs::read(REPLAY_FILE)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::simulation::{Outcome, Point2, DESIRED_FPS};
+    use crate::upgrades::Upgrades;
+
+    fn recorded() -> Replay {
+        let sim = GameSimulation::new(800.0, 600.0, GameMode::TimedChallenge, 1337, false);
+        let save = SaveState::capture(&sim);
+        let start = ReplayStart::Checkpoint(Checkpoint {
+            save,
+            upgrades: Upgrades::default(),
+        });
+        let mut replay = Replay::new(1337, &sim, DifficultyPreset::Hard, start);
+        let mut input = InputState::default();
+        replay.record(1.0 / 60.0, &input);
+        input.xaxis = -1.0;
+        input.fire = true;
+        input.mouse = Some(Point2::new(12.5, -40.0));
+        replay.command(Command::CycleInterceptor);
+        replay.command(Command::Resize(1024.0, 768.0));
+        replay.record(1.0 / 60.0, &input);
+        replay
+    }
+
+    #[test]
+    fn commands_go_in_the_next_frame() {
+        let replay = recorded();
+        assert!(replay.log[0].commands.is_empty());
+        assert_eq!(
+            replay.log[1].commands,
+            vec![Command::CycleInterceptor, Command::Resize(1024.0, 768.0)]
+        );
+        assert!(replay.pending.is_empty());
+    }
+
+    // A resumed game with the shop open between levels, played with a bit of
+    // everything, comes out the same when played back
+    #[test]
+    fn playback_matches_the_recorded_game() {
+        let dt = 1.0 / (DESIRED_FPS as f32);
+        let mut sim = GameSimulation::new(800.0, 600.0, GameMode::Normal, 99, false);
+        sim.shop_enabled = true;
+        sim.scoring.miss_penalty = 7;
+        let mut save = SaveState::capture(&sim);
+        save.level = 3;
+        save.score = 1200;
+        save.restore(&mut sim);
+        sim.set_difficulty(DifficultyPreset::Hard);
+        let mut replay = Replay::new(99, &sim, DifficultyPreset::Hard, ReplayStart::Resume(save));
+
+        let mut input = InputState::default();
+        for tick in 0..DESIRED_FPS * 240 {
+            input.xaxis = if tick % 240 < 120 { 1.0 } else { -1.0 };
+            input.yaxis = if tick % 90 < 45 { -1.0 } else { 1.0 };
+            let mut commands = Vec::new();
+            if tick % 20 == 0 {
+                commands.push(Command::Fire);
+            }
+            if sim.shop.is_some() {
+                commands.push(Command::BuyUpgrade(0));
+            }
+            match tick % 600 {
+                100 => commands.push(Command::CycleInterceptor),
+                200 => commands.push(Command::FireEmp),
+                300 => commands.push(Command::Burst),
+                400 => commands.push(Command::RepairBase),
+                _ => (),
+            }
+            input.fire = false;
+            for command in commands {
+                replay.command(command);
+                if sim.apply(command) {
+                    input.fire = true;
+                }
+            }
+            replay.record(dt, &input);
+            if sim.tick(dt, &input) != Outcome::Running {
+                break;
+            }
+        }
+        assert!(sim.level.level > 4, "the game should get past the shop");
+
+        let mut played = replay.game();
+        for frame in &replay.log {
+            for &command in &frame.commands {
+                played.apply(command);
+            }
+            played.tick(dt, &frame.input);
+        }
+        assert_eq!(played.level.level, sim.level.level);
+        assert_eq!(played.scoring.score, sim.scoring.score);
+        assert_eq!(played.shots_fired, sim.shots_fired);
+        assert_eq!(played.upgrades, sim.upgrades);
+        assert_eq!(played.player.pos, sim.player.pos);
+    }
+
+    #[test]
+    fn replay_round_trip() {
+        let replay = recorded();
+        let bytes = replay.to_bytes().unwrap();
+        assert_eq!(Replay::from_bytes(&bytes).unwrap(), replay);
+    }
+
+    #[test]
+    fn other_versions_are_rejected() {
+        let mut replay = recorded();
+        replay.version = REPLAY_VERSION + 1;
+        let bytes = replay.to_bytes().unwrap();
+        let error = Replay::from_bytes(&bytes).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+
+        assert!(Replay::from_bytes(&[1, 2]).is_err());
+        let bytes = recorded().to_bytes().unwrap();
+        assert!(Replay::from_bytes(&bytes[..bytes.len() - 1]).is_err());
+    }
+}
@@ -0,0 +1,503 @@
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use directories::ProjectDirs;
+use serde::{Deserialize, Serialize};
+
+use crate::simulation::{GameMode, GameSimulation, CITY_COUNT};
+use crate::upgrades::Upgrades;
+
+const AUTOSAVE_FILE: &str = "autosave.json";
+const AUTOSAVE_TEMP_FILE: &str = "autosave.json.tmp";
+pub const AUTOSAVE_PERIOD: f32 = 30.0;
+
+const CHECKPOINT_FILE: &str = "checkpoint.bin";
+pub const CHECKPOINT_LEVELS: u32 = 5; // one is kept at the start of every fifth level
+
+const HIGH_SCORE_FILE: &str = "high_score.json";
+const STATS_FILE: &str = "stats.json";
+
+// Where a file the game keeps between runs lives, in the platform's data
+// directory, or the working directory on a system that doesn't have one
+fn data_file(name: &str) -> PathBuf {
+    let Some(dirs) = ProjectDirs::from("", "", "RustCommand") else {
+        return PathBuf::from(name);
+    };
+    // it won't be there the first time anything is written
+    let _ = fs::create_dir_all(dirs.data_dir());
+    dirs.data_dir().join(name)
+}
+
+// The progress worth keeping from a single player game. Rockets in flight
+// aren't saved, a resumed game picks up the level with a clear sky.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct SaveState {
+    pub level: u32,
+    pub level_timer: f32,
+    pub score: i32,
+    pub city_life: [f32; CITY_COUNT],
+}
+
+impl SaveState {
+    pub fn capture(sim: &GameSimulation) -> SaveState {
+        SaveState {
+            level: sim.level.level,
+            level_timer: sim.level.timer,
+            score: sim.scoring.score,
+            city_life: std::array::from_fn(|i| sim.cities[i].life),
+        }
+    }
+
+    pub fn restore(&self, sim: &mut GameSimulation) {
+        sim.level.level = self.level;
+        sim.level.timer = self.level_timer;
+        sim.scoring.score = self.score;
+        for (city, &life) in sim.cities.iter_mut().zip(&self.city_life) {
+            city.life = life;
+        }
+        sim.waves
+            .start_level(self.level, sim.level.rockets_per_wave_range());
+    }
+}
+
+// A single player game kept at the start of a level, to go back to from the
+// main menu. Unlike the autosave it keeps the upgrades bought so far.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Checkpoint {
+    pub save: SaveState,
+    pub upgrades: Upgrades,
+}
+
+impl Checkpoint {
+    pub fn capture(sim: &GameSimulation) -> Checkpoint {
+        Checkpoint {
+            save: SaveState::capture(sim),
+            upgrades: sim.upgrades,
+        }
+    }
+
+    // Start the level over with a full magazine
+    pub fn restore(&self, sim: &mut GameSimulation) {
+        self.save.restore(sim);
+        sim.upgrades = self.upgrades;
+        sim.ammo = sim.max_ammo();
+    }
+}
+
+pub const HIGH_SCORE_COUNT: usize = 10;
+
+// One of the best normal mode or timed challenge games played on this machine
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct HighScoreEntry {
+    pub score: i32,
+    pub level: u32,
+    pub accuracy: f32, // percent of shots that destroyed a rocket
+    pub date: String,  // ISO 8601, in UTC
+}
+
+// Days since the unix epoch to a year, month and day, Howard Hinnant's
+// civil_from_days
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z.rem_euclid(146097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let year = yoe + era * 400 + (month <= 2) as i64;
+    (year, month, day)
+}
+
+// Seconds since the unix epoch as an ISO 8601 date and time
+fn iso_8601(timestamp: u64) -> String {
+    let secs = timestamp as i64;
+    let (year, month, day) = civil_from_days(secs.div_euclid(86400));
+    let time = secs.rem_euclid(86400);
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z",
+        year,
+        month,
+        day,
+        time / 3600,
+        time / 60 % 60,
+        time % 60
+    )
+}
+
+fn accuracy_percent(shots_fired: u32, rockets_destroyed: u32) -> f32 {
+    if shots_fired == 0 {
+        return 0.0;
+    }
+    rockets_destroyed as f32 * 100.0 / shots_fired as f32
+}
+
+impl HighScoreEntry {
+    pub fn capture(sim: &GameSimulation) -> HighScoreEntry {
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or(0);
+        HighScoreEntry {
+            score: sim.scoring.score,
+            level: sim.level.level,
+            accuracy: accuracy_percent(sim.shots_fired, sim.rockets_destroyed),
+            date: iso_8601(timestamp),
+        }
+    }
+}
+
+// The single record the file held before the table, which had a unix
+// timestamp and the raw shot counts. Records older still have no counts.
+#[derive(Debug, Deserialize)]
+struct OldHighScore {
+    score: i32,
+    level: u32,
+    timestamp: u64,
+    #[serde(default)]
+    shots_fired: u32,
+    #[serde(default)]
+    rockets_destroyed: u32,
+}
+
+impl From<OldHighScore> for HighScoreEntry {
+    fn from(old: OldHighScore) -> HighScoreEntry {
+        HighScoreEntry {
+            score: old.score,
+            level: old.level,
+            accuracy: accuracy_percent(old.shots_fired, old.rockets_destroyed),
+            date: iso_8601(old.timestamp),
+        }
+    }
+}
+
+// Every shape the high score file has had, newest first
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum HighScoreFile {
+    Tables(HighScoreTables),
+    List(Vec<HighScoreEntry>), // from before timed challenge, normal mode only
+    Record(OldHighScore),      // from before the table
+}
+
+// The best games of each mode that keeps a table, best first. Chaos mode
+// has its own leaderboard. Games played in night vision go in a table of
+// their own whatever the mode, the dark makes them a different game.
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+pub struct HighScoreTables {
+    #[serde(rename = "high_scores")]
+    pub normal: Vec<HighScoreEntry>,
+    #[serde(rename = "timed_high_scores", default)]
+    pub timed: Vec<HighScoreEntry>,
+    #[serde(rename = "night_vision_high_scores", default)]
+    pub night_vision: Vec<HighScoreEntry>,
+}
+
+impl HighScoreTables {
+    pub fn table(&self, mode: GameMode, night_vision: bool) -> &[HighScoreEntry] {
+        if night_vision {
+            return &self.night_vision;
+        }
+        match mode {
+            GameMode::TimedChallenge => &self.timed,
+            _ => &self.normal,
+        }
+    }
+
+    pub fn table_mut(&mut self, mode: GameMode, night_vision: bool) -> &mut Vec<HighScoreEntry> {
+        if night_vision {
+            return &mut self.night_vision;
+        }
+        match mode {
+            GameMode::TimedChallenge => &mut self.timed,
+            _ => &mut self.normal,
+        }
+    }
+}
+
+fn best_first(mut scores: Vec<HighScoreEntry>) -> Vec<HighScoreEntry> {
+    scores.sort_by_key(|entry| std::cmp::Reverse(entry.score));
+    scores.truncate(HIGH_SCORE_COUNT);
+    scores
+}
+
+// Put a finished game in the table if it's good enough, true if it made it.
+// The table stays sorted best first.
+pub fn insert_high_score(scores: &mut Vec<HighScoreEntry>, entry: HighScoreEntry) -> bool {
+    let rank = scores.partition_point(|s| s.score >= entry.score);
+    if rank >= HIGH_SCORE_COUNT {
+        return false;
+    }
+    scores.insert(rank, entry);
+    scores.truncate(HIGH_SCORE_COUNT);
+    true
+}
+
+// Running totals over every game played on this machine
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+pub struct Stats {
+    pub games_played: u32,
+    pub total_rockets_destroyed: u64,
+    pub total_shots_fired: u64,
+    pub best_score: i32, // normal mode only, chaos scores count down
+    pub best_level: u32,
+    pub total_play_time_seconds: f64,
+}
+
+impl Stats {
+    // Add a finished game to the totals
+    pub fn record_game(&mut self, sim: &GameSimulation, play_time: f64) {
+        self.games_played += 1;
+        self.total_rockets_destroyed += sim.rockets_destroyed as u64;
+        self.total_shots_fired += sim.shots_fired as u64;
+        self.total_play_time_seconds += play_time;
+        if sim.mode == GameMode::Normal {
+            self.best_score = self.best_score.max(sim.scoring.score);
+            self.best_level = self.best_level.max(sim.level.level);
+        }
+    }
+}
+
+// No games have been finished on this machine yet
+pub fn first_launch() -> bool {
+    !data_file(STATS_FILE).exists() && !Path::new(STATS_FILE).exists()
+}
+
+// A missing or unreadable file starts the totals again from zero
+pub fn load_stats() -> Stats {
+    let Ok(text) = read_data_file(STATS_FILE) else {
+        return Stats::default();
+    };
+    serde_json::from_str(&text).unwrap_or_else(|e| {
+        println!("Ignoring unreadable {}: {}", STATS_FILE, e);
+        Stats::default()
+    })
+}
+
+// One field per line so the file is easy to read
+pub fn save_stats(stats: Stats) -> io::Result<()> {
+    let json = serde_json::to_string_pretty(&stats)?;
+    fs::write(data_file(STATS_FILE), json)
+}
+
+// A file from before timed challenge is a bare list of normal mode games,
+// and one from before the table holds a single record, which becomes the
+// first entry
+fn parse_high_scores(text: &str) -> serde_json::Result<HighScoreTables> {
+    let tables = match serde_json::from_str(text)? {
+        HighScoreFile::Tables(tables) => tables,
+        HighScoreFile::List(normal) => HighScoreTables {
+            normal,
+            ..HighScoreTables::default()
+        },
+        HighScoreFile::Record(old) => HighScoreTables {
+            normal: vec![old.into()],
+            ..HighScoreTables::default()
+        },
+    };
+    Ok(HighScoreTables {
+        normal: best_first(tables.normal),
+        timed: best_first(tables.timed),
+        night_vision: best_first(tables.night_vision),
+    })
+}
+
+// A file in the data directory, or where older versions kept it in the
+// working directory if it hasn't been written there yet
+fn read_data_file(name: &str) -> io::Result<String> {
+    fs::read_to_string(data_file(name)).or_else(|_| fs::read_to_string(name))
+}
+
+// No file yet means empty tables. A corrupt one is reported and ignored so
+// the next game just writes over it.
+pub fn load_high_scores() -> HighScoreTables {
+    let Ok(text) = read_data_file(HIGH_SCORE_FILE) else {
+        return HighScoreTables::default();
+    };
+    parse_high_scores(&text).unwrap_or_else(|e| {
+        println!("Ignoring unreadable {}: {}", HIGH_SCORE_FILE, e);
+        HighScoreTables::default()
+    })
+}
+
+// Written straight away rather than on a thread, the game quits right after
+pub fn save_high_scores(tables: &HighScoreTables) -> io::Result<()> {
+    let json = serde_json::to_string_pretty(tables)?;
+    fs::write(data_file(HIGH_SCORE_FILE), json)
+}
+
+// Write the save on its own thread so the game doesn't hitch. It goes to a
+// temporary file first so a crash mid write can't leave half a save behind.
+pub fn autosave(state: SaveState) {
+    thread::spawn(move || {
+        let (temp, file) = (data_file(AUTOSAVE_TEMP_FILE), data_file(AUTOSAVE_FILE));
+        let result = serde_json::to_string(&state)
+            .map_err(io::Error::from)
+            .and_then(|json| fs::write(&temp, json))
+            .and_then(|_| fs::rename(&temp, &file));
+        if let Err(e) = result {
+            println!("Autosave failed: {}", e);
+        }
+    });
+}
+
+pub fn delete_autosave() {
+    // nothing to do if it was never written
+    let _ = fs::remove_file(data_file(AUTOSAVE_FILE));
+}
+
+// Offer to pick up a game that didn't end cleanly. A declined or unreadable
+// save is thrown away.
+pub fn prompt_resume() -> Option<SaveState> {
+    let text = fs::read_to_string(data_file(AUTOSAVE_FILE)).ok()?;
+
+    let state = match serde_json::from_str(&text) {
+        Ok(state) => state,
+        Err(e) => {
+            println!("Ignoring unreadable {}: {}", AUTOSAVE_FILE, e);
+            delete_autosave();
+            return None;
+        }
+    };
+
+    print!("Resume interrupted game? (Y/N) ");
+    io::stdout().flush().ok()?;
+    let mut answer = String::new();
+    io::stdin().read_line(&mut answer).ok()?;
+
+    if answer.trim().eq_ignore_ascii_case("y") {
+        Some(state)
+    } else {
+        delete_autosave();
+        None
+    }
+}
+
+pub fn save_checkpoint(checkpoint: Checkpoint) -> io::Result<()> {
+    let bytes = bincode::serialize(&checkpoint).map_err(io::Error::other)?;
+    fs::write(data_file(CHECKPOINT_FILE), bytes)
+}
+
+// An unreadable checkpoint is reported and thrown away
+pub fn load_checkpoint() -> Option<Checkpoint> {
+    let bytes = fs::read(data_file(CHECKPOINT_FILE)).ok()?;
+    match bincode::deserialize(&bytes) {
+        Ok(checkpoint) => Some(checkpoint),
+        Err(e) => {
+            println!("Ignoring unreadable {}: {}", CHECKPOINT_FILE, e);
+            delete_checkpoint();
+            None
+        }
+    }
+}
+
+// Gone once the game it was kept for is over, so it can't be used to play
+// the same levels again and again
+pub fn delete_checkpoint() {
+    // nothing to do if it was never written
+    let _ = fs::remove_file(data_file(CHECKPOINT_FILE));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(score: i32) -> HighScoreEntry {
+        HighScoreEntry {
+            score,
+            level: 3,
+            accuracy: 50.0,
+            date: "2024-01-02T03:04:05Z".to_string(),
+        }
+    }
+
+    #[test]
+    fn high_scores_round_trip() {
+        let tables = HighScoreTables {
+            normal: vec![entry(900), entry(400)],
+            timed: vec![entry(700)],
+            night_vision: vec![entry(300)],
+        };
+        let json = serde_json::to_string_pretty(&tables).unwrap();
+        assert_eq!(parse_high_scores(&json).unwrap(), tables);
+    }
+
+    #[test]
+    fn night_vision_games_have_their_own_table() {
+        let mut tables = HighScoreTables::default();
+        insert_high_score(tables.table_mut(GameMode::Normal, true), entry(500));
+        insert_high_score(tables.table_mut(GameMode::TimedChallenge, true), entry(600));
+        insert_high_score(tables.table_mut(GameMode::Normal, false), entry(100));
+        assert_eq!(tables.night_vision, vec![entry(600), entry(500)]);
+        assert_eq!(tables.table(GameMode::Normal, false), [entry(100)]);
+        assert!(tables.timed.is_empty());
+
+        // files from before night vision had a table
+        let json = r#"{"high_scores": [], "timed_high_scores": []}"#;
+        assert!(parse_high_scores(json).unwrap().night_vision.is_empty());
+    }
+
+    #[test]
+    fn bare_list_becomes_the_normal_table() {
+        let json = r#"[
+            {"score": 400, "level": 3, "accuracy": 50.0, "date": "2024-01-02T03:04:05Z"},
+            {"score": 900, "level": 3, "accuracy": 50.0, "date": "2024-01-02T03:04:05Z"}
+        ]"#;
+        let tables = parse_high_scores(json).unwrap();
+        assert_eq!(tables.normal, vec![entry(900), entry(400)]);
+        assert!(tables.timed.is_empty());
+    }
+
+    #[test]
+    fn single_record_becomes_the_first_entry() {
+        let json = r#"{"score": 1200, "level": 4, "timestamp": 86400, "shots_fired": 20, "rockets_destroyed": 15}"#;
+        let tables = parse_high_scores(json).unwrap();
+        assert_eq!(
+            tables.normal,
+            vec![HighScoreEntry {
+                score: 1200,
+                level: 4,
+                accuracy: 75.0,
+                date: "1970-01-02T00:00:00Z".to_string(),
+            }]
+        );
+
+        // the oldest records have no shot counts
+        let json = r#"{"score": 1200, "level": 4, "timestamp": 0}"#;
+        assert_eq!(parse_high_scores(json).unwrap().normal[0].accuracy, 0.0);
+    }
+
+    #[test]
+    fn checkpoint_round_trip() {
+        let checkpoint = Checkpoint {
+            save: SaveState {
+                level: 10,
+                level_timer: 12.5,
+                score: 48000,
+                city_life: std::array::from_fn(|i| i as f32 * 0.5),
+            },
+            upgrades: Upgrades {
+                interceptor_radius_bonus: 0.2,
+                cursor_speed_bonus: 0.1,
+                ammo_bonus: 3,
+                reload_speed_bonus: 0.0,
+            },
+        };
+        let bytes = bincode::serialize(&checkpoint).unwrap();
+        assert_eq!(
+            bincode::deserialize::<Checkpoint>(&bytes).unwrap(),
+            checkpoint
+        );
+        assert!(bincode::deserialize::<Checkpoint>(&bytes[..bytes.len() - 1]).is_err());
+    }
+
+    #[test]
+    fn corrupt_file_is_an_error() {
+        assert!(parse_high_scores("{\"high_scores\": [").is_err());
+        assert!(parse_high_scores("{\"score\": \"lots\"}").is_err());
+    }
+}
@@ -0,0 +1,221 @@
+use crate::simulation::GameMode;
+
+pub const LEADER_SCORE: i32 = 300;
+pub const FORMATION_LEADER_SCORE: i32 = 500;
+pub const CLEAN_WAVE_BONUS: i32 = 500; // no ground hits between one wave and the next
+pub const WALL_SCORE: i32 = 5000;
+pub const WARP_SCORE: i32 = 100;
+pub const LEFTOVER_ROCKET_SCORE: i32 = 25; // each rocket still up when a level ends
+const CHAOS_GROUND_SCORE: i32 = 75;
+pub const MISS_PENALTY: i32 = 10; // unless the settings file picks another
+const WAVE_CLEAR_BONUS: i32 = 50; // per level cleared
+const SPEED_CLEAR_BONUS: f32 = 10.0; // per second left on the level timer, per level
+
+const CHAIN_BONUS: i32 = 50; // for every extra rocket caught in the same blast
+const COMBO_MULTIPLIER_MAX: u32 = 8; // a kill is worth its place in the combo, up to this
+pub const COMBO_WINDOW: f32 = 1.5; // seconds between kills to keep a combo going
+
+const SHIELD_TIME: f32 = 10.0;
+const SHIELD_MILESTONE: i32 = 5000; // a shield is earned every time the score passes a multiple
+
+// Band of the sky a rocket was destroyed in, stopping it early pays more
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Zone {
+    High,
+    Middle,
+    Low,
+}
+
+impl Zone {
+    // height is 0.0 at the ground and 1.0 at the top of the screen
+    pub fn from_height(height: f32) -> Zone {
+        if height > 2.0 / 3.0 {
+            Zone::High
+        } else if height > 1.0 / 3.0 {
+            Zone::Middle
+        } else {
+            Zone::Low
+        }
+    }
+
+    fn scale(&self) -> f32 {
+        match self {
+            Zone::High => 1.5,
+            Zone::Middle => 1.25,
+            Zone::Low => 1.0,
+        }
+    }
+}
+
+// Every rule that changes the score. Each add method applies its points and
+// returns how much the score changed by.
+#[derive(Debug)]
+pub struct ScoreEngine {
+    pub score: i32,
+    pub combo: u32,
+    pub combo_timer: f32,
+    pub multiplier: f32,
+    pub shield_timer: f32, // the score can't go down while this is running
+    pub high_score: i32,
+    pub miss_penalty: i32, // lost for each shot that burns out without a kill
+    next_shield: i32,
+    mode: GameMode,
+}
+
+impl ScoreEngine {
+    pub fn new(mode: GameMode) -> ScoreEngine {
+        ScoreEngine {
+            score: 0,
+            combo: 0,
+            combo_timer: 0.0,
+            multiplier: 1.0,
+            shield_timer: 0.0,
+            high_score: 0,
+            miss_penalty: MISS_PENALTY,
+            next_shield: SHIELD_MILESTONE,
+            mode,
+        }
+    }
+
+    // Scale points by the active multiplier, inverted in chaos mode
+    fn points(&self, base: i32) -> i32 {
+        let points = (base as f32 * self.multiplier).round() as i32;
+        match self.mode {
+            GameMode::Normal | GameMode::TimedChallenge => points,
+            GameMode::Chaos => -points,
+        }
+    }
+
+    fn add(&mut self, points: i32) -> i32 {
+        if points < 0 && self.shield_active() {
+            return 0;
+        }
+        self.score += points;
+        self.check_shield_milestone();
+        points
+    }
+
+    pub fn shield_active(&self) -> bool {
+        self.shield_timer > 0.0
+    }
+
+    // Chaos mode wants the score to go down, so it never earns shields
+    fn check_shield_milestone(&mut self) {
+        if self.mode == GameMode::Chaos {
+            return;
+        }
+        while self.score >= self.next_shield {
+            self.next_shield += SHIELD_MILESTONE;
+            self.shield_timer = SHIELD_TIME;
+        }
+    }
+
+    // chain is how many rockets the interceptor has destroyed, this one included
+    pub fn add_intercept_kill(&mut self, base: i32, zone: Zone, chain: u32) -> i32 {
+        self.combo += 1;
+        self.combo_timer = COMBO_WINDOW;
+
+        let bonus = CHAIN_BONUS * chain.saturating_sub(1) as i32;
+        let points = ((base + bonus) as f32 * zone.scale()).round() as i32;
+        self.add(self.points(points * self.combo_multiplier() as i32))
+    }
+
+    pub fn combo_multiplier(&self) -> u32 {
+        self.combo.clamp(1, COMBO_MULTIPLIER_MAX)
+    }
+
+    // Pay for something out of the score, false if there isn't enough. The
+    // shield only guards against losing points, not spending them.
+    pub fn spend(&mut self, cost: i32) -> bool {
+        if self.score < cost {
+            return false;
+        }
+        self.score -= cost;
+        true
+    }
+
+    // Flat bonuses like the leader, the wall and warps
+    pub fn add_bonus(&mut self, base: i32) -> i32 {
+        self.add(self.points(base))
+    }
+
+    // Ground hits break the combo, they only score in chaos mode where the
+    // health bar is replaced by the timer
+    pub fn add_ground_hit_penalty(&mut self) -> i32 {
+        self.combo = 0;
+        match self.mode {
+            GameMode::Normal | GameMode::TimedChallenge => 0,
+            GameMode::Chaos => self.add(self.points(-CHAOS_GROUND_SCORE)),
+        }
+    }
+
+    pub fn add_wave_clear_bonus(&mut self, level: u32) -> i32 {
+        match self.mode {
+            GameMode::Normal => self.add(self.points(WAVE_CLEAR_BONUS * level as i32)),
+            // a timed challenge never clears a level, it runs straight on
+            GameMode::Chaos | GameMode::TimedChallenge => 0,
+        }
+    }
+
+    // Clearing the sky of a level's last wave ends the level early, and the
+    // time left over is paid out
+    pub fn add_speed_clear_bonus(&mut self, remaining: f32, level: u32) -> i32 {
+        match self.mode {
+            GameMode::Normal => {
+                let bonus = (remaining * SPEED_CLEAR_BONUS * level as f32).round() as i32;
+                self.add(self.points(bonus))
+            }
+            GameMode::Chaos | GameMode::TimedChallenge => 0,
+        }
+    }
+
+    // An interceptor that burned out without a kill, chaos mode wants low
+    // scores anyway so it is the only one not punished
+    pub fn add_miss_penalty(&mut self) -> i32 {
+        self.combo = 0;
+        match self.mode {
+            GameMode::Normal | GameMode::TimedChallenge if self.shield_active() => 0,
+            GameMode::Normal | GameMode::TimedChallenge => {
+                let before = self.score;
+                self.score = (self.score - self.miss_penalty).max(0);
+                self.score - before
+            }
+            GameMode::Chaos => 0,
+        }
+    }
+
+    pub fn tick(&mut self, dt: f32) {
+        self.shield_timer = (self.shield_timer - dt).max(0.0);
+        self.combo_timer -= dt;
+        if self.combo_timer <= 0.0 {
+            self.combo = 0;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::actor::RocketKind;
+
+    #[test]
+    fn level_10_kill_scores_1500() {
+        let mut scoring = ScoreEngine::new(GameMode::Normal);
+        let base = RocketKind::Standard.score() * 10;
+        // low in the sky, alone in its blast and the first of a combo
+        assert_eq!(scoring.add_intercept_kill(base, Zone::Low, 1), 1500);
+        assert_eq!(scoring.combo_multiplier(), 1);
+        assert_eq!(scoring.score, 1500);
+    }
+
+    #[test]
+    fn speed_clear_pays_for_time_left() {
+        let mut scoring = ScoreEngine::new(GameMode::Normal);
+        assert_eq!(scoring.add_speed_clear_bonus(4.5, 3), 135);
+        assert_eq!(scoring.add_speed_clear_bonus(0.0, 3), 0);
+        assert_eq!(scoring.score, 135);
+
+        let mut timed = ScoreEngine::new(GameMode::TimedChallenge);
+        assert_eq!(timed.add_speed_clear_bonus(4.5, 3), 0);
+    }
+}
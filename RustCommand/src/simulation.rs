@@ -0,0 +1,2235 @@
+use glam::*;
+use oorandom::Rand32;
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+
+use crate::actor::{
+    Actor, ActorId, ActorPool, RocketKind, BASE_LIFE, CURSOR_VEL, GROUND_LIFE, NUKE_RADIUS,
+};
+use crate::input::InputState;
+use crate::level::{DifficultyPreset, LevelState, LevelUp};
+use crate::physics::{predict_impact, vec_from_angle};
+use crate::scoring::{
+    ScoreEngine, Zone, CLEAN_WAVE_BONUS, FORMATION_LEADER_SCORE, LEADER_SCORE,
+    LEFTOVER_ROCKET_SCORE, WALL_SCORE, WARP_SCORE,
+};
+use crate::upgrades::{roll_offers, UpgradeKind, Upgrades, SHOP_OFFERS};
+use crate::wave_manager::{RocketSpec, WaveAction, WaveManager};
+
+pub type Point2 = Vec2;
+
+const LEADER_FLOCK_RADIUS: f32 = 80.0;
+const LEADER_FLOCK_LERP: f32 = 0.1;
+const LEADER_BANNER_TIME: f32 = 1.5;
+
+const WALL_BONUS_TIME: f32 = 5.0;
+
+const BULLET_HELL_RADIUS_SCALE: f32 = 2.0;
+const BULLET_HELL_BANNER_TIME: f32 = 2.0;
+
+// a splitter passing within this multiple of an explosion's radius splits
+const SPLIT_RANGE: f32 = 1.5;
+const SPLIT_ANGLE: f32 = 20.0 * std::f32::consts::PI / 180.0;
+
+// overlapping explosions merge, the bigger one grows by this much
+const CHAIN_RADIUS_BONUS: f32 = 15.0;
+
+pub const GROUND_HEIGHT: f32 = 150.0;
+// how far past the edge of the screen a rocket can fly before it is gone
+// for good, far enough that none of it is still showing
+const OFFSCREEN_MARGIN: f32 = 100.0;
+
+// The ground is split into equal strips across the screen, each piling up
+// with rubble from the rockets that land on it until it is destroyed
+pub const GROUND_SEGMENTS: usize = 10;
+const CRATER_DEPTH: f32 = 15.0;
+pub const SEGMENT_DESTROYED_HEIGHT: f32 = GROUND_HEIGHT + 60.0;
+
+pub const CITY_COUNT: usize = 6;
+
+pub const BASE_COUNT: usize = 3;
+const BASE_HIT_RANGE: f32 = 40.0; // a rocket landing this close wrecks a base
+const GROUND_HIT_DAMAGE_PER_LEVEL: f32 = 0.1; // on top of the 1 a ground hit always does
+
+// a timed challenge city is battered but never falls, the clock ends the game
+const TIMED_CITY_MIN_LIFE: f32 = 0.1;
+const BASE_REPAIR_COST: i32 = 2000; // times the level, like the upgrades
+
+// adaptive difficulty moves the wave size once the performance score gets
+// this far from 0, and keeps this much of it for the next wave
+const PERFORMANCE_THRESHOLD: f32 = 3.0;
+const PERFORMANCE_DECAY: f32 = 0.5;
+
+// Which ground segment is under x
+fn segment_at(screen_width: f32, x: f32) -> usize {
+    let fraction = (x + screen_width / 2.0) / screen_width;
+    // anything off the sides belongs to the end segments
+    ((fraction * GROUND_SEGMENTS as f32) as usize).min(GROUND_SEGMENTS - 1)
+}
+
+// World height of the top of the ground at x
+fn ground_top(
+    segments: &[f32; GROUND_SEGMENTS],
+    screen_width: f32,
+    screen_height: f32,
+    x: f32,
+) -> f32 {
+    -screen_height / 2.0 + segments[segment_at(screen_width, x)]
+}
+
+// A rocket landed at x, raising the ground there until it is destroyed
+fn raise_ground(segments: &mut [f32; GROUND_SEGMENTS], screen_width: f32, x: f32) {
+    let segment = &mut segments[segment_at(screen_width, x)];
+    *segment = (*segment + CRATER_DEPTH).min(SEGMENT_DESTROYED_HEIGHT);
+}
+
+// Cities are spread evenly along the ground, each in the middle of its own
+// equal share of the screen width and sitting on top of its segment
+fn place_cities(
+    cities: &mut [Actor],
+    segments: &[f32; GROUND_SEGMENTS],
+    screen_width: f32,
+    screen_height: f32,
+) {
+    let spacing = screen_width / cities.len() as f32;
+    for (i, city) in cities.iter_mut().enumerate() {
+        let x = -screen_width / 2.0 + spacing * (i as f32 + 0.5);
+        city.pos = Point2::new(x, ground_top(segments, screen_width, screen_height, x));
+    }
+}
+
+// The launch bases stand at even spacing along the ground, a quarter of the
+// way in from each side and in the middle
+fn place_bases(
+    bases: &mut [Actor],
+    segments: &[f32; GROUND_SEGMENTS],
+    screen_width: f32,
+    screen_height: f32,
+) {
+    let spacing = screen_width / (bases.len() + 1) as f32;
+    for (i, base) in bases.iter_mut().enumerate() {
+        let x = -screen_width / 2.0 + spacing * (i as f32 + 1.0);
+        base.pos = Point2::new(x, ground_top(segments, screen_width, screen_height, x));
+    }
+}
+
+// Whichever city is closest to x. Ruined cities still count, so a hit on
+// the rubble doesn't move on to a neighbour.
+fn nearest_city(cities: &[Actor], x: f32) -> Option<usize> {
+    (0..cities.len()).min_by(|&a, &b| {
+        (cities[a].pos.x - x)
+            .abs()
+            .total_cmp(&(cities[b].pos.x - x).abs())
+    })
+}
+
+// Something landed and the city at index takes the hit, down to min_life
+fn damage_city(cities: &mut [Actor], index: Option<usize>, damage: f32, min_life: f32) {
+    if let Some(city) = index.and_then(|i| cities.get_mut(i)) {
+        city.life = (city.life - damage).max(min_life);
+    }
+}
+
+// Which kind of interceptor the player fires
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum InterceptorMode {
+    Standard,
+    WarpGate,
+    Traveling,
+}
+
+impl InterceptorMode {
+    pub fn next(&self) -> InterceptorMode {
+        match self {
+            InterceptorMode::Standard => InterceptorMode::WarpGate,
+            InterceptorMode::WarpGate => InterceptorMode::Traveling,
+            InterceptorMode::Traveling => InterceptorMode::Standard,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            InterceptorMode::Standard => "Standard",
+            InterceptorMode::WarpGate => "Warp Gate",
+            InterceptorMode::Traveling => "Traveling",
+        }
+    }
+}
+
+const TURRET_COUNT: usize = 3; // most that can be built at once
+const TURRET_COST: i32 = 500;
+const TURRET_RANGE: f32 = 300.0;
+const TURRET_COOLDOWN: f32 = 1.5;
+const TURRET_LEAD: f32 = 0.3; // seconds ahead of the rocket a turret aims
+const TURRET_HIT_RANGE: f32 = 20.0;
+
+// A gun on the ground that shoots at whichever rocket in range is lowest
+#[derive(Debug)]
+pub struct Turret {
+    pub pos: Point2,
+    pub fire_cooldown: f32,
+    pub target: Option<ActorId>,
+}
+
+const TRAVELING_VEL: f32 = 500.0;
+const TRAVELING_STEER: f32 = 0.05; // how quickly it turns back toward its target
+const TRAVELING_ARRIVE_DIST: f32 = 10.0;
+const GRAVITY_ASSIST_IMPULSE: f32 = 50.0;
+
+// An interceptor that flies up from the base and detonates on reaching the
+// spot the cursor was at when it was fired
+#[derive(Debug)]
+pub struct TravelingInterceptor {
+    pub pos: Point2,
+    pub target: Point2,
+    pub vel: Vec2,
+    pub speed: f32,        // slower for a large shot
+    pub radius_scale: f32, // passed on to the explosion
+}
+
+// Move toward the target, turning gradually so any push bends the path.
+// Returns true once the interceptor has arrived.
+fn traveling_move(interceptor: &mut TravelingInterceptor, dt: f32) -> bool {
+    let to_target = interceptor.target - interceptor.pos;
+    if to_target.length() < TRAVELING_ARRIVE_DIST.max(interceptor.speed * dt) {
+        return true;
+    }
+
+    let desired = to_target.normalize() * interceptor.speed;
+    interceptor.vel = interceptor.vel.lerp(desired, TRAVELING_STEER);
+    interceptor.pos += interceptor.vel * dt;
+    false
+}
+
+// Nudge an interceptor in flight away from the side the cursor is on, so
+// the player can curve shots around things
+pub fn apply_gravity_assist(interceptor: &mut TravelingInterceptor, cursor_pos: Point2) {
+    if cursor_pos.x < interceptor.pos.x {
+        interceptor.vel.x += GRAVITY_ASSIST_IMPULSE;
+    } else {
+        interceptor.vel.x -= GRAVITY_ASSIST_IMPULSE;
+    }
+}
+
+pub const SHOT_TIMEOUT: f32 = 0.5;
+const TRIGGER_DEADZONE: f32 = 0.1;
+pub const SHOT_QUEUE_MAX: usize = 3;
+
+pub const MAX_AMMO: u32 = 10;
+const RELOAD_TIME: f32 = 3.0;
+const LEVEL_AMMO_REFILL: std::ops::Range<u32> = 1..3; // rounds back when a level starts
+
+const EMP_COOLDOWN: f32 = 10.0;
+pub const EMP_FREEZE_TIME: f32 = 3.0;
+pub const EMP_FLASH_TIME: f32 = 0.3;
+
+const NUKE_COOLDOWN: f32 = 30.0;
+const NUKE_SCORE_SCALE: i32 = 2; // points per rocket compared to a normal interceptor
+
+// Cooldown for a shot fired with the analog trigger, half a pull fires at the
+// normal rate and a full pull twice as fast
+fn trigger_to_fire_rate(trigger: f32, base_timeout: f32) -> f32 {
+    base_timeout * (1.5 - trigger.clamp(0.0, 1.0))
+}
+
+// seconds fire has to be held for each bigger interceptor
+const CHARGE_NORMAL: f32 = 0.3;
+pub const CHARGE_LARGE: f32 = 0.8;
+const LARGE_SHOT_SPEED: f32 = 0.8; // of the normal traveling speed
+
+// How big an interceptor goes off, picked by how long fire was held
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ShotTier {
+    Small,
+    Normal,
+    Large,
+}
+
+impl ShotTier {
+    pub fn from_charge(charge: f32) -> ShotTier {
+        if charge < CHARGE_NORMAL {
+            ShotTier::Small
+        } else if charge <= CHARGE_LARGE {
+            ShotTier::Normal
+        } else {
+            ShotTier::Large
+        }
+    }
+
+    // multiple of the normal explosion radius
+    pub fn radius_scale(&self) -> f32 {
+        match self {
+            ShotTier::Small => 0.7,
+            ShotTier::Normal => 1.0,
+            ShotTier::Large => 1.5,
+        }
+    }
+
+    // multiple of the normal cooldown before the next shot
+    fn timeout_scale(&self) -> f32 {
+        match self {
+            ShotTier::Small => 0.6,
+            ShotTier::Normal => 1.0,
+            ShotTier::Large => 1.5,
+        }
+    }
+
+    fn ammo_cost(&self) -> u32 {
+        match self {
+            ShotTier::Large => 2,
+            _ => 1,
+        }
+    }
+
+    // multiple of the normal traveling interceptor speed
+    fn speed_scale(&self) -> f32 {
+        match self {
+            ShotTier::Large => LARGE_SHOT_SPEED,
+            _ => 1.0,
+        }
+    }
+}
+
+// Build up charge while fire is held, letting go gives the size to fire at
+fn charge_shot(charge: &mut f32, held: bool, dt: f32) -> Option<ShotTier> {
+    if held {
+        *charge += dt;
+        None
+    } else if *charge > 0.0 {
+        let tier = ShotTier::from_charge(*charge);
+        *charge = 0.0;
+        Some(tier)
+    } else {
+        None
+    }
+}
+
+// Random buff or debuff rolled at the start of every level
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum WaveModifier {
+    FastRockets,
+    TinyExplosions,
+    DoubleScore,
+    FogOfWar,
+    WindStorm,
+    NoBoundaryKills,
+    ExtraAmmo,
+}
+
+const WAVE_MODIFIERS: [WaveModifier; 7] = [
+    WaveModifier::FastRockets,
+    WaveModifier::TinyExplosions,
+    WaveModifier::DoubleScore,
+    WaveModifier::FogOfWar,
+    WaveModifier::WindStorm,
+    WaveModifier::NoBoundaryKills,
+    WaveModifier::ExtraAmmo,
+];
+
+impl WaveModifier {
+    pub fn name(&self) -> &'static str {
+        match self {
+            WaveModifier::FastRockets => "Fast Rockets",
+            WaveModifier::TinyExplosions => "Tiny Explosions",
+            WaveModifier::DoubleScore => "Double Score",
+            WaveModifier::FogOfWar => "Fog of War",
+            WaveModifier::WindStorm => "Wind Storm",
+            WaveModifier::NoBoundaryKills => "No Boundary Kills",
+            WaveModifier::ExtraAmmo => "Extra Ammo",
+        }
+    }
+}
+
+const MODIFIER_BANNER_TIME: f32 = 3.0;
+pub const LEVEL_TRANSITION_TIME: f32 = 3.0;
+const WIND_STORM_VEL: f32 = 60.0;
+const WIND_MAX: f32 = 30.0; // strongest gust at level 10, it keeps growing after
+const EXTRA_AMMO_SHOTS: u32 = 5;
+// rockets above this world height are hidden while fog of war is active
+pub const FOG_CEILING: f32 = 0.0;
+
+// Chaos mode inverts the scoring and runs on a timer instead of health.
+// A timed challenge scores as usual against the same timer, with the
+// waves coming twice as often and getting harder without any breaks.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum GameMode {
+    Normal,
+    Chaos,
+    TimedChallenge,
+}
+
+const SESSION_TIME: f32 = 60.0; // how long a chaos or timed challenge game lasts
+
+// Passive background hazards, rolled every level
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum WeatherKind {
+    None,
+    Meteor,
+    Rain,
+}
+
+const WEATHER_KINDS: [WeatherKind; 3] = [WeatherKind::None, WeatherKind::Meteor, WeatherKind::Rain];
+
+const METEOR_RATE: f32 = 1.0; // spawns per second
+pub const METEOR_VEL: f32 = 400.0;
+const METEOR_HIT_CHANCE: f32 = 0.01; // per frame spent over the city
+const RAIN_RATE: f32 = 40.0;
+const RAIN_VEL: f32 = 300.0;
+
+const NO_MISS_BANNER_TIME: f32 = 2.0;
+
+pub const FLOATING_TEXT_TIME: f32 = 1.0;
+const FLOATING_TEXT_SPACING: f32 = 20.0; // between texts from the same spot
+
+// Short-lived text that drifts up from a point in the world
+pub struct FloatingText {
+    pub pos: Point2,
+    pub text: String,
+    pub color: [f32; 4],
+    pub timer: f32,
+}
+
+// An interceptor blast overlapping a rocket, found before any of them are
+// applied so removing one can't invalidate another
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Interception {
+    rocket: ActorId,
+    interceptor: ActorId,
+}
+
+const REMOTE_CURSOR_LERP: f32 = 0.2; // how hard to pull toward the reported position
+
+// How a tick left the game
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Outcome {
+    Running,
+    GameOver, // every city was destroyed
+    TimeUp,   // the chaos mode timer ran out
+}
+
+// Anything from outside that changes the game between ticks rather than
+// through the input held down during them. Replays keep these next to the
+// input, so a game plays back exactly as it went.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum Command {
+    Fire,  // a fresh press of fire, see press_fire
+    Burst, // the gamepad trigger pulled all the way, queues as many shots as fit
+    CycleInterceptor,
+    BuyUpgrade(usize), // which of the offers in the shop
+    RepairBase,
+    PlaceTurret,
+    FireEmp,
+    FireNuke,
+    SetMissPenalty(i32), // the config was reloaded
+    Resize(f32, f32),    // the window changed size
+}
+
+pub const DESIRED_FPS: u32 = 60; // how often the game steps the simulation
+pub const SINGLE_PLAYER_SEED: u64 = 1337; // unless --seed picks another
+
+// A game that can be run without a window, for benchmarks and bots that
+// play it in a loop
+pub trait Simulation {
+    fn step(&mut self, dt: f32);
+    fn is_over(&self) -> bool;
+    fn score(&self) -> i32;
+    fn level(&self) -> u32;
+}
+
+// Everything that changes while playing. None of this knows about windows,
+// drawing or the network, so it can be run and stepped on its own.
+pub struct GameSimulation {
+    pub player: Actor,
+    pub cities: Vec<Actor>,
+    pub ground_segments: [f32; GROUND_SEGMENTS], // height of each strip of ground
+    pub bases: Vec<Actor>, // interceptors launch from these, a dead one until repaired
+    pub turrets: Vec<Turret>,
+    pub screen_width: f32,
+    pub screen_height: f32,
+    pub rockets: ActorPool,
+    pub pending_rockets: VecDeque<(Actor, f32)>, // waiting on their delay to appear
+    pub interceptors: ActorPool,
+    pub warp_gates: Vec<Actor>,
+    pub emps: Vec<Actor>,
+    pub emp_timeout: f32,
+    pub frozen_timer: f32, // rockets hold still while this runs
+    pub emp_flash: f32,
+    pub emp_pos: Point2, // where the last EMP went off
+    pub nuke_ready: bool,
+    pub nuke_cooldown: f32,
+    pub weather: WeatherKind,
+    pub meteors: Vec<Actor>,
+    pub raindrops: Vec<Actor>,
+    pub interceptor_mode: InterceptorMode,
+    pub traveling: Vec<TravelingInterceptor>,
+    pub in_flight: Vec<Actor>, // fired interceptors on their way up to the cursor
+    pub shot_timeout: f32,
+    pub shot_queue: VecDeque<()>,
+    pub fire_charge: f32, // how long fire has been held with a shot ready
+    pub rng: Rand32,
+    pub waves: WaveManager,
+    pub level: LevelState,
+    pub scoring: ScoreEngine,
+    pub modifier_banner: f32,
+    pub leader: Option<ActorId>,
+    pub leader_banner: f32,
+    pub floating_texts: Vec<FloatingText>,
+    pub level_missed: bool,
+    // shots_fired when the current level began
+    pub level_start_shots: u32,
+    pub clean_wave: bool, // nothing has hit the ground since the last wave came in
+    pub perfect_waves: u32, // waves that ended without a ground hit
+    // the break between levels, no rockets come while it lasts
+    pub transitioning: bool,
+    pub transition_timer: f32,
+    pub upgrades: Upgrades,
+    // the upgrades on offer, the break holds while the shop is open
+    pub shop: Option<[UpgradeKind; SHOP_OFFERS]>,
+    pub shop_enabled: bool,
+    // waves grow for players who are doing well and shrink for ones who
+    // aren't, unless --fixed-difficulty is given
+    pub adaptive: bool,
+    pub rockets_held: bool, // no rockets and a stopped clock during the tutorial
+    pub performance_score: f32, // up for each kill and down for each ground hit
+    pub no_miss_banner: f32,
+    pub wall_bonus_timer: f32,
+    pub wall_remaining: u32,
+    pub bullet_hell_banner: f32,
+    pub fog_of_war: bool,
+    pub wind: f32,       // sideways pixels per second, positive blows right
+    pub storm_wind: f32, // the part of the wind set by a wind storm
+    pub boundary_kills: bool,
+    pub bonus_shots: u32,
+    // shared by both players in a networked game, so both machines agree
+    pub ammo: u32,
+    pub reloading: bool,
+    pub reload_timer: f32,
+    pub mode: GameMode,
+    pub session_timer: f32, // counts down in the modes that run against the clock
+    pub two_player: bool,
+    pub player2: Actor,
+    pub remote_input: InputState,
+    pub remote_pos: Option<Point2>, // last reported over the network, None for local co-op
+    pub player2_shot_timeout: f32,
+    pub player2_fire_charge: f32,
+    // running totals, so the layers above can tell when something happened
+    pub shots_fired: u32,
+    pub rockets_destroyed: u32, // by the players, turrets don't count toward accuracy
+    pub turret_kills: u32,
+    pub player_kills: [u32; 2], // who destroyed what in co-op, the score is pooled
+    pub ground_hits: u32,
+}
+
+// Tweak the per-level values in state according to the modifier
+fn apply_modifier(state: &mut GameSimulation, modifier: WaveModifier) {
+    // start from the defaults so modifiers don't stack between levels
+    state.scoring.multiplier = 1.0;
+    state.fog_of_war = false;
+    state.storm_wind = 0.0;
+    state.boundary_kills = true;
+    state.bonus_shots = 0;
+
+    match modifier {
+        // these only change the difficulty, see LevelState
+        WaveModifier::FastRockets | WaveModifier::TinyExplosions => {}
+        WaveModifier::DoubleScore => state.scoring.multiplier = 2.0,
+        WaveModifier::FogOfWar => state.fog_of_war = true,
+        WaveModifier::WindStorm => {
+            // blow either left or right
+            let dir = if state.rng.rand_float() < 0.5 {
+                -1.0
+            } else {
+                1.0
+            };
+            state.storm_wind = dir * WIND_STORM_VEL;
+        }
+        WaveModifier::NoBoundaryKills => state.boundary_kills = false,
+        WaveModifier::ExtraAmmo => state.bonus_shots = EXTRA_AMMO_SHOTS,
+    }
+
+    state.wind = state.storm_wind;
+    state.level.set_modifier(modifier);
+    state.modifier_banner = MODIFIER_BANNER_TIME;
+}
+
+impl GameSimulation {
+    pub fn new(
+        screen_width: f32,
+        screen_height: f32,
+        mode: GameMode,
+        seed: u64,
+        two_player: bool,
+    ) -> GameSimulation {
+        let mut s = GameSimulation {
+            player: Actor::player_cursor(),
+            cities: (0..CITY_COUNT).map(|_| Actor::city()).collect(),
+            ground_segments: [GROUND_HEIGHT; GROUND_SEGMENTS],
+            bases: (0..BASE_COUNT).map(|_| Actor::launch_base()).collect(),
+            turrets: Vec::new(),
+            screen_width,
+            screen_height,
+            rockets: ActorPool::new(),
+            pending_rockets: VecDeque::new(),
+            interceptors: ActorPool::new(),
+            warp_gates: Vec::new(),
+            emps: Vec::new(),
+            emp_timeout: 0.0,
+            frozen_timer: 0.0,
+            emp_flash: 0.0,
+            emp_pos: Point2::ZERO,
+            nuke_ready: false,
+            nuke_cooldown: 0.0,
+            weather: WeatherKind::None,
+            meteors: Vec::new(),
+            raindrops: Vec::new(),
+            interceptor_mode: InterceptorMode::Standard,
+            traveling: Vec::new(),
+            in_flight: Vec::new(),
+            shot_timeout: 0.0,
+            shot_queue: VecDeque::new(),
+            fire_charge: 0.0,
+            rng: Rand32::new(seed),
+            // offset so the waves don't draw the same numbers as everything else
+            waves: WaveManager::new(seed.wrapping_add(1)),
+            level: LevelState::new(),
+            scoring: ScoreEngine::new(mode),
+            modifier_banner: 0.0,
+            leader: None,
+            leader_banner: 0.0,
+            floating_texts: Vec::new(),
+            level_missed: false,
+            level_start_shots: 0,
+            // no wave yet, so nothing to reward on the first one
+            clean_wave: false,
+            perfect_waves: 0,
+            transitioning: false,
+            transition_timer: 0.0,
+            upgrades: Upgrades::default(),
+            shop: None,
+            shop_enabled: false,
+            adaptive: false,
+            rockets_held: false,
+            performance_score: 0.0,
+            no_miss_banner: 0.0,
+            wall_bonus_timer: 0.0,
+            wall_remaining: 0,
+            bullet_hell_banner: 0.0,
+            fog_of_war: false,
+            wind: 0.0,
+            storm_wind: 0.0,
+            boundary_kills: true,
+            bonus_shots: 0,
+            ammo: MAX_AMMO,
+            reloading: false,
+            reload_timer: 0.0,
+            mode,
+            session_timer: SESSION_TIME,
+            two_player,
+            player2: Actor::player_cursor(),
+            remote_input: InputState::default(),
+            remote_pos: None,
+            player2_shot_timeout: 0.0,
+            player2_fire_charge: 0.0,
+            shots_fired: 0,
+            rockets_destroyed: 0,
+            turret_kills: 0,
+            player_kills: [0; 2],
+            ground_hits: 0,
+        };
+
+        place_cities(
+            &mut s.cities,
+            &s.ground_segments,
+            screen_width,
+            screen_height,
+        );
+        place_bases(
+            &mut s.bases,
+            &s.ground_segments,
+            screen_width,
+            screen_height,
+        );
+        s.roll_modifier();
+        if mode == GameMode::TimedChallenge {
+            s.set_difficulty(DifficultyPreset::Normal);
+        }
+        s.waves.start_level(1, s.level.rockets_per_wave_range());
+
+        s
+    }
+
+    // Change the size of the world, the cities move to stay on the ground
+    pub fn resize(&mut self, screen_width: f32, screen_height: f32) {
+        self.screen_width = screen_width;
+        self.screen_height = screen_height;
+        place_cities(
+            &mut self.cities,
+            &self.ground_segments,
+            screen_width,
+            screen_height,
+        );
+        place_bases(
+            &mut self.bases,
+            &self.ground_segments,
+            screen_width,
+            screen_height,
+        );
+    }
+
+    // Advance the game by dt seconds with the local player's input
+    pub fn tick(&mut self, dt: f32, input: &InputState) -> Outcome {
+        if self.transitioning {
+            if self.shop.is_none() {
+                self.transition_timer -= dt;
+                if self.transition_timer <= 0.0 {
+                    if self.shop_enabled {
+                        self.shop = Some(roll_offers(&mut self.rng));
+                    } else {
+                        self.end_transition();
+                    }
+                }
+            }
+        } else if self.rockets_held {
+            // the level doesn't start until the rockets are let go
+        } else if let Some(level_up) = self.level.tick(dt).or_else(|| self.speed_clear()) {
+            if self.mode == GameMode::TimedChallenge {
+                // no break against the clock, the next waves just get harder
+                self.waves
+                    .start_level(self.level.level, self.level.rockets_per_wave_range());
+            } else {
+                // a level with no shots at all isn't one without a miss
+                if !self.level_missed && self.shots_fired > self.level_start_shots {
+                    self.no_miss_banner = NO_MISS_BANNER_TIME;
+                }
+                self.level_missed = false;
+                self.level_start_shots = self.shots_fired;
+                self.scoring.add_wave_clear_bonus(level_up.cleared);
+                self.clear_leftover_rockets();
+
+                self.transitioning = true;
+                self.transition_timer = LEVEL_TRANSITION_TIME;
+            }
+        }
+
+        self.wall_bonus_timer -= dt;
+        self.update_bullet_hell();
+        self.update_weather(dt);
+
+        self.scoring.tick(dt);
+
+        self.modifier_banner -= dt;
+        self.leader_banner -= dt;
+        self.bullet_hell_banner -= dt;
+        self.no_miss_banner -= dt;
+
+        for text in &mut self.floating_texts {
+            text.timer -= dt;
+        }
+        self.floating_texts.retain(|t| t.timer > 0.0);
+
+        self.update_cursor(dt, input);
+        self.update_turrets(dt);
+        self.update_rockets(dt);
+        self.update_interceptors(dt);
+        self.update_collisions();
+        self.retarget_rockets();
+
+        if self.mode != GameMode::Normal {
+            self.session_timer -= dt;
+        }
+        self.outcome()
+    }
+
+    // A normal level ends as soon as its last wave is shot down, with a bonus
+    // for the time left on the clock. A wave that reached the ground doesn't
+    // count, or a player who did nothing would be paid for it.
+    fn speed_clear(&mut self) -> Option<LevelUp> {
+        let last_wave = self.waves.next_arrival() > self.level.timer;
+        let sky_clear =
+            self.pending_rockets.is_empty() && self.rockets.active_iter().all(|r| r.life <= 0.0);
+        if self.mode != GameMode::Normal || !last_wave || !sky_clear || !self.clean_wave {
+            return None;
+        }
+        let bonus = self
+            .scoring
+            .add_speed_clear_bonus(self.level.timer, self.level.level);
+        self.floating_texts.push(FloatingText {
+            pos: Point2::ZERO,
+            text: format!("SPEED CLEAR +{}", bonus),
+            color: [1.0, 0.84, 0.0, 1.0], // gold
+            timer: FLOATING_TEXT_TIME,
+        });
+        Some(self.level.finish())
+    }
+
+    // Whether the game is still going, and if not how it ended
+    pub fn outcome(&self) -> Outcome {
+        match self.mode {
+            GameMode::Normal if self.cities.iter().all(|c| c.life <= 0.0) => Outcome::GameOver,
+            GameMode::Chaos | GameMode::TimedChallenge if self.session_timer <= 0.0 => {
+                Outcome::TimeUp
+            }
+            _ => Outcome::Running,
+        }
+    }
+
+    // Steer the rockets that steer themselves and fly them all along
+    fn move_rockets(&mut self, dt: f32) {
+        for rocket in self.rockets.active_iter_mut() {
+            match rocket.kind {
+                RocketKind::Chaos => rocket.chaos_rocket_steer(&mut self.rng, dt),
+                RocketKind::Homing => {
+                    // chase whichever cursor is closer, both machines agree
+                    // on that in a networked game
+                    let mut target = self.player.pos;
+                    if self.two_player
+                        && self.player2.pos.distance(rocket.pos) < target.distance(rocket.pos)
+                    {
+                        target = self.player2.pos;
+                    }
+                    rocket.homing_rocket_steer(target, dt);
+                }
+                _ => (),
+            }
+            let vel = self.level.current_rocket_vel() * rocket.kind.speed_scale() * rocket.depth;
+            rocket.rocket_move(vel, self.wind, dt);
+        }
+    }
+
+    // Switch to the base values of a preset. A game resumed from a save keeps
+    // the same share of its level left.
+    pub fn set_difficulty(&mut self, preset: DifficultyPreset) {
+        let mut settings = preset.settings();
+        if self.mode == GameMode::TimedChallenge {
+            settings = settings.timed_challenge();
+        }
+        let left = self.level.timer / self.level.settings.level_time;
+        self.level.timer = left * settings.level_time;
+        self.level.settings = settings;
+        self.waves.set_difficulty(&settings);
+    }
+
+    // Move the cursors and fire whatever the players asked for
+    fn update_cursor(&mut self, dt: f32, input: &InputState) {
+        let cursor_vel = self.upgrades.apply_cursor_speed(CURSOR_VEL);
+        self.player
+            .cursor_move(self.screen_width, self.screen_height, input, cursor_vel, dt);
+
+        self.update_player2(dt);
+
+        self.shot_timeout -= dt;
+        self.update_reload(dt);
+        self.update_emps(dt);
+        self.update_nuke(dt);
+
+        if !self.can_fire() {
+            self.fire_charge = 0.0;
+        } else if self.shot_timeout <= 0.0 {
+            let timeout = self.level.settings.shot_timeout;
+            if input.trigger > TRIGGER_DEADZONE {
+                let rate = trigger_to_fire_rate(input.trigger, timeout);
+                self.fire_interceptor(rate, ShotTier::Normal);
+            } else if let Some(tier) = charge_shot(&mut self.fire_charge, input.fire, dt) {
+                // a press made during the cooldown and held until it was
+                // over has queued itself already
+                self.shot_queue.pop_back();
+                self.fire_interceptor(timeout * tier.timeout_scale(), tier);
+            } else if !input.fire && self.shot_queue.pop_front().is_some() {
+                // taps made during the cooldown go off at the normal size
+                self.fire_interceptor(timeout, ShotTier::Normal);
+            }
+        }
+    }
+
+    // Launch the next wave when it is due and fly the rockets, nothing moves
+    // during the break between levels or while an EMP has them frozen
+    fn update_rockets(&mut self, dt: f32) {
+        if self.transitioning || self.rockets_held {
+            return;
+        }
+
+        if let WaveAction::SpawnWave(specs) = self.waves.tick(dt) {
+            // the wave before this one is over
+            if self.clean_wave && self.mode == GameMode::Normal {
+                self.scoring.add_bonus(CLEAN_WAVE_BONUS);
+                self.perfect_waves += 1;
+            }
+            self.clean_wave = true;
+            if self.adaptive && self.mode == GameMode::Normal {
+                self.adapt_difficulty();
+            }
+            self.roll_wind();
+            self.spawn_wave(specs);
+        }
+        self.release_pending_rockets(dt);
+
+        if self.frozen_timer <= 0.0 {
+            self.flock_to_leader();
+            self.move_rockets(dt);
+        }
+    }
+
+    // Grow and shrink the explosions and run down the warp gates
+    fn update_interceptors(&mut self, dt: f32) {
+        for interceptor in self.interceptors.active_iter_mut() {
+            if interceptor.nuke {
+                // burns twice as long
+                interceptor.interceptor_elapse(NUKE_RADIUS, dt / 2.0);
+                continue;
+            }
+            let scale = interceptor.radius_scale;
+            let mut radius = self
+                .upgrades
+                .apply_radius(self.level.current_interceptor_radius());
+            if self.waves.frenzy {
+                radius *= BULLET_HELL_RADIUS_SCALE;
+            }
+            interceptor.interceptor_elapse(radius * scale, dt);
+        }
+
+        for gate in &mut self.warp_gates {
+            gate.warp_gate_elapse(dt);
+        }
+
+        self.update_traveling(dt);
+        self.update_in_flight(dt);
+    }
+
+    // Fly launched interceptors up from their bases and set them off once
+    // they reach the spot they were aimed at
+    fn update_in_flight(&mut self, dt: f32) {
+        let speed = self.level.settings.interceptor_speed;
+        let mut arrived = Vec::new();
+        self.in_flight.retain_mut(|shot| {
+            if shot.interceptor_move(speed, dt) {
+                arrived.push(shot.detonation());
+                false
+            } else {
+                true
+            }
+        });
+
+        for explosion in arrived {
+            self.interceptors.alloc(explosion);
+        }
+    }
+
+    // Resolve everything that touched this tick, then clear out whatever
+    // died or ran out
+    fn update_collisions(&mut self) {
+        self.handle_warps();
+        self.handle_border_collisions();
+        self.handle_interceptions();
+
+        // kill dead missiles and elapsed interceptors
+        self.rockets.retain(|r| r.life > 0.0);
+        self.leader = self.leader.filter(|&id| self.rockets.contains(id));
+        self.penalize_misses();
+        self.interceptors.retain(|i| i.elapsed > 0.0);
+        self.warp_gates.retain(|g| g.elapsed > 0.0);
+    }
+
+    // Buy one of the upgrades on offer and get on with the next level, or
+    // stay in the shop if the score doesn't cover it
+    pub fn buy_upgrade(&mut self, choice: usize) {
+        let Some(upgrade) = self.shop.and_then(|offers| offers.get(choice).copied()) else {
+            return;
+        };
+        if self.scoring.spend(upgrade.cost(self.level.level)) {
+            upgrade.apply(&mut self.upgrades);
+            self.end_transition();
+        }
+    }
+
+    // Leave the shop, or the break if there was no shop, and start the level
+    pub fn end_transition(&mut self) {
+        self.shop = None;
+        self.transitioning = false;
+        self.begin_level();
+    }
+
+    // Carry out a command. Only a press of fire has anything to say back,
+    // see press_fire.
+    pub fn apply(&mut self, command: Command) -> bool {
+        match command {
+            Command::Fire => return self.press_fire(),
+            Command::Burst => {
+                // the queued shots go out one per cooldown
+                while self.shot_queue.len() < SHOT_QUEUE_MAX {
+                    self.shot_queue.push_back(());
+                }
+            }
+            Command::CycleInterceptor => self.interceptor_mode = self.interceptor_mode.next(),
+            Command::BuyUpgrade(choice) => self.buy_upgrade(choice),
+            Command::RepairBase => self.repair_base(),
+            Command::PlaceTurret => self.place_turret(),
+            Command::FireEmp => self.fire_emp(),
+            Command::FireNuke => self.fire_nuke(),
+            Command::SetMissPenalty(penalty) => self.scoring.miss_penalty = penalty,
+            Command::Resize(width, height) => self.resize(width, height),
+        }
+        true
+    }
+
+    // A fresh press of fire leaves the upgrade shop without buying anything,
+    // bends the path of a traveling interceptor in the air, or queues a shot
+    // if the last one is still cooling down. False if the press was used up
+    // and shouldn't fire.
+    fn press_fire(&mut self) -> bool {
+        if self.shop.is_some() {
+            self.end_transition();
+            return false;
+        }
+        if self.interceptor_mode == InterceptorMode::Traveling {
+            if let Some(interceptor) = self.traveling.last_mut() {
+                apply_gravity_assist(interceptor, self.player.pos);
+                return false;
+            }
+        }
+        if self.shot_timeout > 0.0 && self.shot_queue.len() < SHOT_QUEUE_MAX {
+            self.shot_queue.push_back(());
+        }
+        true
+    }
+
+    // The base explosion radius for this level with any upgrades bought
+    pub fn interceptor_radius(&self) -> f32 {
+        self.upgrades
+            .apply_radius(self.level.current_interceptor_radius())
+    }
+
+    // The base radius of a particular blast, nukes are their own size
+    pub fn blast_radius(&self, interceptor: &Actor) -> f32 {
+        if interceptor.nuke {
+            NUKE_RADIUS
+        } else {
+            self.interceptor_radius() * interceptor.radius_scale
+        }
+    }
+
+    pub fn max_ammo(&self) -> u32 {
+        self.upgrades.apply_max_ammo(MAX_AMMO)
+    }
+
+    // Called when the break between levels is over
+    fn begin_level(&mut self) {
+        // a nuke comes with every level once the last one has cooled down
+        if self.nuke_cooldown <= 0.0 {
+            self.nuke_ready = true;
+        }
+
+        let refill = self.rng.rand_range(LEVEL_AMMO_REFILL);
+        self.ammo = (self.ammo + refill).min(self.max_ammo());
+
+        self.roll_modifier();
+
+        self.waves
+            .start_level(self.level.level, self.level.rockets_per_wave_range());
+
+        let index = self.rng.rand_range(0..WEATHER_KINDS.len() as u32) as usize;
+        self.weather = WEATHER_KINDS[index];
+    }
+
+    // Rockets still up when a level ends are taken away, each one is worth a
+    // small bonus for having survived them
+    fn clear_leftover_rockets(&mut self) {
+        let leftover = self.rockets.len() as i32;
+        if leftover > 0 {
+            self.scoring.add_bonus(LEFTOVER_ROCKET_SCORE * leftover);
+        }
+        self.rockets.clear();
+        // the ones that never showed up don't count
+        self.pending_rockets.clear();
+        self.leader = None;
+        self.wall_remaining = 0;
+    }
+
+    // What is left of the cities together, 1.0 with none of them damaged
+    pub fn city_health(&self) -> f32 {
+        let life: f32 = self.cities.iter().map(|city| city.life).sum();
+        life / (CITY_COUNT as f32 * GROUND_LIFE)
+    }
+
+    // What the countdown in the middle of the screen counts down. A timed
+    // challenge levels up without stopping, so it counts down the game.
+    pub fn countdown_timer(&self) -> f32 {
+        match self.mode {
+            GameMode::TimedChallenge => self.session_timer,
+            _ => self.level.timer,
+        }
+    }
+
+    // The least a city can be left with after a hit
+    fn city_min_life(&self) -> f32 {
+        if self.mode == GameMode::TimedChallenge {
+            TIMED_CITY_MIN_LIFE
+        } else {
+            0.0
+        }
+    }
+
+    // How close the lowest live rocket is to the ground, 0.0 is the ground
+    // and 1.0 is the top of the screen
+    pub fn lowest_rocket_distance(&self) -> f32 {
+        let ground = -self.screen_height / 2.0 + GROUND_HEIGHT;
+        let sky_height = self.screen_height - GROUND_HEIGHT;
+
+        let screen_x = self.screen_width / 2.0;
+
+        // rockets on course to land off the side of the screen aren't a threat
+        let threatening = |r: &&Actor| {
+            let vel =
+                self.level.current_rocket_vel() * r.kind.speed_scale() * r.depth * r.speed_ramp();
+            predict_impact(r.pos, r.angle, vel, self.wind, ground)
+                .is_some_and(|impact| impact.x.abs() <= screen_x)
+        };
+
+        self.rockets
+            .active_iter()
+            .filter(|r| r.life > 0.0)
+            .filter(threatening)
+            .map(|r| (r.pos.y - ground) / sky_height)
+            .fold(f32::MAX, f32::min)
+    }
+
+    // Every wave brings a new gust, stronger the higher the level, on top of
+    // any wind storm
+    fn roll_wind(&mut self) {
+        let max = WIND_MAX * (self.level.level as f32 / 10.0);
+        let gust = (self.rng.rand_float() * 2.0 - 1.0) * max;
+        self.wind = self.storm_wind + gust;
+    }
+
+    // Pick a random modifier for the level that is starting
+    fn roll_modifier(&mut self) {
+        let index = self.rng.rand_range(0..WAVE_MODIFIERS.len() as u32) as usize;
+        apply_modifier(self, WAVE_MODIFIERS[index]);
+    }
+
+    // Handle the case where a missile hits the side of the screen or the ground
+    fn handle_border_collisions(&mut self) {
+        let screen_x = self.screen_width / 2.0;
+        let screen_y = self.screen_height / 2.0;
+        let min_life = self.city_min_life();
+        let mut cratered = false;
+
+        for rocket in self.rockets.active_iter_mut() {
+            let ground = ground_top(
+                &self.ground_segments,
+                self.screen_width,
+                self.screen_height,
+                rocket.pos.x,
+            );
+            if rocket.pos.y < ground {
+                // hit ground
+                rocket.life = 0.0; // kill missile
+                self.clean_wave = false;
+                if self.mode != GameMode::Chaos {
+                    let damage = 1.0 + self.level.level as f32 * GROUND_HIT_DAMAGE_PER_LEVEL;
+                    let city = nearest_city(&self.cities, rocket.pos.x);
+                    damage_city(&mut self.cities, city, damage, min_life);
+                    self.floating_texts.push(FloatingText {
+                        pos: rocket.pos,
+                        text: format!("-{:.1} HP", damage),
+                        color: [1.0, 0.3, 0.3, 1.0], // light red
+                        timer: FLOATING_TEXT_TIME,
+                    });
+                }
+                // there is no shop in the other modes to repair a base in
+                if self.mode == GameMode::Normal {
+                    for base in &mut self.bases {
+                        if (base.pos.x - rocket.pos.x).abs() < BASE_HIT_RANGE {
+                            base.life = 0.0;
+                        }
+                    }
+                    self.turrets
+                        .retain(|t| (t.pos.x - rocket.pos.x).abs() >= TURRET_HIT_RANGE);
+                }
+                raise_ground(&mut self.ground_segments, self.screen_width, rocket.pos.x);
+                cratered = true;
+                self.ground_hits += 1;
+                self.performance_score -= 1.0;
+                self.scoring.add_ground_hit_penalty();
+
+                // make explosion by recycling the interceptor code
+                let mut explosion = Actor::interceptor();
+                explosion.pos = rocket.pos;
+                self.interceptors.alloc(explosion);
+            }
+            // a rocket that came in from a side isn't killed by the side it
+            // entered through, it starts right on it
+            let off_left = rocket.pos.x < -screen_x && rocket.initial_pos.x > -screen_x;
+            let off_right = rocket.pos.x > screen_x && rocket.initial_pos.x < screen_x;
+            if self.boundary_kills && (off_left || off_right) {
+                // hit side
+                rocket.life = 0.0; // kill missile
+            }
+            let heading_out = vec_from_angle(rocket.angle).x * rocket.pos.x > 0.0;
+            if rocket.pos.x.abs() > screen_x + OFFSCREEN_MARGIN && heading_out {
+                // flew out a side with boundary kills off, it isn't coming
+                // back so stop simulating it, it's no kill and no impact
+                rocket.life = 0.0;
+            }
+            if rocket.pos.y > screen_y + OFFSCREEN_MARGIN {
+                // chaos rocket wandered off the top
+                rocket.life = 0.0; // kill missile
+            }
+        }
+
+        // the cities and bases ride up on the rubble
+        if cratered {
+            place_cities(
+                &mut self.cities,
+                &self.ground_segments,
+                self.screen_width,
+                self.screen_height,
+            );
+            place_bases(
+                &mut self.bases,
+                &self.ground_segments,
+                self.screen_width,
+                self.screen_height,
+            );
+            for turret in &mut self.turrets {
+                turret.pos.y = ground_top(
+                    &self.ground_segments,
+                    self.screen_width,
+                    self.screen_height,
+                    turret.pos.x,
+                );
+            }
+        }
+    }
+
+    // Handle collisions between interceptors and missiles. Every overlap is
+    // found first, then applied in order, so a rocket caught by two blasts in
+    // the same frame is only destroyed once.
+    fn handle_interceptions(&mut self) {
+        let ground = -self.screen_height / 2.0 + GROUND_HEIGHT;
+        let sky_height = self.screen_height - GROUND_HEIGHT;
+        let mut leader_down = false;
+
+        let mut hits = Vec::new();
+        let mut near_misses = Vec::new();
+        for rocket in self.rockets.active_iter() {
+            for interceptor in self.interceptors.active_iter() {
+                // interceptors only reach rockets in their layer or closer
+                if rocket.depth < interceptor.depth {
+                    continue;
+                }
+                let dist = (rocket.pos - interceptor.pos).length();
+                if dist < interceptor.radius {
+                    hits.push(Interception {
+                        rocket: rocket.id,
+                        interceptor: interceptor.id,
+                    });
+                } else if rocket.kind == RocketKind::Splitter
+                    && dist <= interceptor.radius * SPLIT_RANGE
+                {
+                    near_misses.push(rocket.id);
+                }
+            }
+        }
+
+        for hit in hits {
+            let (Some(rocket), Some(interceptor)) = (
+                self.rockets.get_mut(hit.rocket),
+                self.interceptors.get_mut(hit.interceptor),
+            ) else {
+                continue;
+            };
+            if rocket.life <= 0.0 {
+                continue;
+            }
+
+            interceptor.kills += 1;
+            if interceptor.turret {
+                self.turret_kills += 1;
+            } else {
+                self.rockets_destroyed += 1;
+                self.player_kills[interceptor.player2 as usize] += 1;
+            }
+            self.performance_score += 1.0;
+            if self.leader == Some(hit.rocket) {
+                self.scoring.add_bonus(LEADER_SCORE);
+                leader_down = true;
+            }
+            if rocket.formation_leader {
+                let points = self.scoring.add_bonus(FORMATION_LEADER_SCORE);
+                self.floating_texts.push(FloatingText {
+                    // above the points for the kill itself
+                    pos: rocket.pos + Vec2::new(0.0, FLOATING_TEXT_SPACING),
+                    text: format!("Formation Leader +{}", points),
+                    color: [1.0, 0.84, 0.0, 1.0], // gold
+                    timer: FLOATING_TEXT_TIME,
+                });
+            }
+            if rocket.wall && self.wall_remaining > 0 {
+                self.wall_remaining -= 1;
+                if self.wall_remaining == 0 && self.wall_bonus_timer > 0.0 {
+                    self.scoring.add_bonus(WALL_SCORE);
+                }
+            }
+            // collision
+            rocket.life = 0.0; // kill rocket, interceptor will be killed by elapse system
+            let zone = Zone::from_height((rocket.pos.y - ground) / sky_height);
+            // later levels pay more for every kill
+            let mut base = rocket.kind.score() * self.level.level as i32;
+            if interceptor.nuke {
+                base *= NUKE_SCORE_SCALE;
+            }
+            let points = self
+                .scoring
+                .add_intercept_kill(base, zone, interceptor.kills);
+            if points > 0 {
+                self.floating_texts.push(FloatingText {
+                    pos: rocket.pos,
+                    text: format!("+{}", points),
+                    color: [0.3, 1.0, 0.3, 1.0], // light green
+                    timer: FLOATING_TEXT_TIME,
+                });
+            }
+        }
+
+        // splitters that were only grazed break into two rockets, unless
+        // another explosion got them outright
+        let mut children = Vec::new();
+        for id in near_misses {
+            let Some(rocket) = self.rockets.get_mut(id) else {
+                continue;
+            };
+            if rocket.life <= 0.0 {
+                continue;
+            }
+            rocket.life = 0.0;
+
+            for turn in [-SPLIT_ANGLE, SPLIT_ANGLE] {
+                let mut child = Actor::rocket();
+                child.pos = rocket.pos;
+                child.initial_pos = rocket.pos;
+                child.angle = rocket.angle + turn;
+                child.depth = rocket.depth;
+                children.push(child);
+            }
+        }
+        for mut child in children {
+            child.target_city_index = self.lock_on(&child);
+            self.rockets.alloc(child);
+        }
+
+        if leader_down {
+            self.disperse_flock();
+        }
+
+        self.chain_interceptors();
+    }
+
+    // Fired explosions that overlap merge into one, the smaller goes out and
+    // the bigger grows. Each pair is looked at once, by index, and one that
+    // has gone out can't merge again.
+    fn chain_interceptors(&mut self) {
+        let mut blasts: Vec<(ActorId, Point2, f32)> = self
+            .interceptors
+            .active_iter()
+            .filter(|i| i.fired && i.elapsed > 0.0)
+            .map(|i| (i.id, i.pos, i.radius))
+            .collect();
+        let mut gone = vec![false; blasts.len()];
+        let mut merges = Vec::new();
+        for a in 0..blasts.len() {
+            for b in a + 1..blasts.len() {
+                if gone[a] || gone[b] {
+                    continue;
+                }
+                let ((_, pos_a, radius_a), (_, pos_b, radius_b)) = (blasts[a], blasts[b]);
+                if pos_a.distance(pos_b) >= radius_a + radius_b {
+                    continue;
+                }
+                let (bigger, smaller) = if radius_a >= radius_b { (a, b) } else { (b, a) };
+                gone[smaller] = true;
+                blasts[bigger].2 += CHAIN_RADIUS_BONUS;
+                merges.push((blasts[bigger].0, blasts[smaller].0));
+            }
+        }
+
+        for (bigger, smaller) in merges {
+            if let Some(interceptor) = self.interceptors.get_mut(smaller) {
+                interceptor.elapsed = 0.0;
+                interceptor.merged = true;
+            }
+            if let Some(interceptor) = self.interceptors.get_mut(bigger) {
+                interceptor.chain_bonus += CHAIN_RADIUS_BONUS;
+                interceptor.radius += CHAIN_RADIUS_BONUS;
+            }
+        }
+    }
+
+    // Shots that burn out without destroying anything cost points, call this
+    // right before the elapsed interceptors are retained. One that merged
+    // into another lives on in that one, and turrets miss for free.
+    fn penalize_misses(&mut self) {
+        for interceptor in self.interceptors.active_iter() {
+            if interceptor.elapsed > 0.0
+                || !interceptor.fired
+                || interceptor.turret
+                || interceptor.kills > 0
+                || interceptor.merged
+            {
+                continue;
+            }
+
+            self.level_missed = true;
+
+            let lost = self.scoring.add_miss_penalty();
+            if lost < 0 {
+                self.floating_texts.push(FloatingText {
+                    pos: interceptor.pos,
+                    text: lost.to_string(),
+                    color: [1.0, 0.0, 0.0, 1.0], // red
+                    timer: FLOATING_TEXT_TIME,
+                });
+            }
+        }
+    }
+
+    // Rockets near the leader gradually turn to match its heading
+    fn flock_to_leader(&mut self) {
+        let leader = match self.leader.and_then(|id| self.rockets.get(id)) {
+            Some(leader) => leader,
+            None => return,
+        };
+        let (leader_pos, leader_angle) = (leader.pos, leader.angle);
+
+        for rocket in self.rockets.active_iter_mut() {
+            if (rocket.pos - leader_pos).length() < LEADER_FLOCK_RADIUS {
+                rocket.angle += (leader_angle - rocket.angle) * LEADER_FLOCK_LERP;
+            }
+        }
+    }
+
+    // Without a leader the rest of the rockets scatter in random directions
+    fn disperse_flock(&mut self) {
+        self.leader_banner = LEADER_BANNER_TIME;
+
+        for rocket in self.rockets.active_iter_mut() {
+            if rocket.life > 0.0 {
+                rocket.angle = self.rng.rand_float() * 0.5 * std::f32::consts::PI
+                    + 0.75 * std::f32::consts::PI;
+            }
+        }
+    }
+
+    // Teleport any rocket that wanders into a warp gate off the side of the screen
+    fn handle_warps(&mut self) {
+        let screen_x = self.screen_width / 2.0;
+
+        for rocket in self.rockets.active_iter_mut() {
+            for gate in &self.warp_gates {
+                let dist = rocket.pos - gate.pos;
+                if dist.length() < gate.radius {
+                    // rocket isn't killed, it just flies off from outside the play area
+                    let side = if self.rng.rand_float() < 0.5 {
+                        -1.0
+                    } else {
+                        1.0
+                    };
+                    rocket.pos.x = side * (screen_x + 100.0);
+                    rocket.initial_pos = rocket.pos;
+                    self.scoring.add_bonus(WARP_SCORE);
+                    break;
+                }
+            }
+        }
+    }
+
+    // Bonus shots are free, anything else needs a round
+    fn can_fire(&self) -> bool {
+        (self.bonus_shots > 0 || self.ammo > 0) && self.nearest_base(self.player.pos).is_some()
+    }
+
+    // The standing launch base closest to target, None once they are all
+    // destroyed
+    pub fn nearest_base(&self, target: Point2) -> Option<Point2> {
+        self.bases
+            .iter()
+            .filter(|b| b.life > 0.0)
+            .map(|b| b.pos)
+            .min_by(|a, b| a.distance(target).total_cmp(&b.distance(target)))
+    }
+
+    pub fn repair_cost(&self) -> i32 {
+        BASE_REPAIR_COST * self.level.level as i32
+    }
+
+    // Rebuild one destroyed base from the shop, it stays open afterwards
+    pub fn repair_base(&mut self) {
+        if self.shop.is_none() {
+            return;
+        }
+        let cost = self.repair_cost();
+        let Some(base) = self.bases.iter_mut().find(|b| b.life <= 0.0) else {
+            return;
+        };
+        if self.scoring.spend(cost) {
+            base.life = BASE_LIFE;
+        }
+    }
+
+    // Build a turret on the ground under the cursor, paid for out of the
+    // score. Chaos mode is scored backwards, so there is nothing to pay with.
+    pub fn place_turret(&mut self) {
+        if self.mode != GameMode::Normal || self.turrets.len() >= TURRET_COUNT {
+            return;
+        }
+        if !self.scoring.spend(TURRET_COST) {
+            return;
+        }
+        let x = self.player.pos.x;
+        let y = ground_top(
+            &self.ground_segments,
+            self.screen_width,
+            self.screen_height,
+            x,
+        );
+        self.turrets.push(Turret {
+            pos: Point2::new(x, y),
+            fire_cooldown: 0.0,
+            target: None,
+        });
+    }
+
+    // Each turret follows the rocket in range closest to the ground and
+    // fires where it will be shortly. Turret shots cost no ammo.
+    fn update_turrets(&mut self, dt: f32) {
+        // a frozen rocket stays put, so there's nothing to lead
+        let lead = if self.frozen_timer > 0.0 {
+            0.0
+        } else {
+            TURRET_LEAD
+        };
+        for turret in &mut self.turrets {
+            turret.fire_cooldown -= dt;
+            turret.target = self
+                .rockets
+                .active_iter()
+                .filter(|r| r.life > 0.0 && r.pos.distance(turret.pos) < TURRET_RANGE)
+                .min_by(|a, b| a.pos.y.total_cmp(&b.pos.y))
+                .map(|r| r.id);
+
+            let Some(rocket) = turret.target.and_then(|id| self.rockets.get(id)) else {
+                continue;
+            };
+            if turret.fire_cooldown > 0.0 {
+                continue;
+            }
+            turret.fire_cooldown = TURRET_COOLDOWN;
+
+            let vel = self.level.current_rocket_vel()
+                * rocket.kind.speed_scale()
+                * rocket.depth
+                * rocket.speed_ramp();
+            let velocity = vec_from_angle(rocket.angle) * vel + Vec2::new(self.wind, 0.0);
+            let mut shot = Actor::interceptor();
+            shot.pos = rocket.pos + velocity * lead;
+            shot.initial_pos = turret.pos;
+            shot.fired = true;
+            shot.turret = true;
+            shot.depth = rocket.depth;
+            self.interceptors.alloc(shot);
+        }
+    }
+
+    // Spend rounds, the last one starts the reload
+    fn use_ammo(&mut self, rounds: u32) {
+        self.ammo = self.ammo.saturating_sub(rounds);
+        if self.ammo == 0 && !self.reloading {
+            self.reloading = true;
+            self.reload_timer = self.upgrades.apply_reload_time(RELOAD_TIME);
+        }
+    }
+
+    // Everything flying around that gets moved each tick
+    pub fn entity_count(&self) -> usize {
+        self.rockets.len()
+            + self.interceptors.len()
+            + self.warp_gates.len()
+            + self.emps.len()
+            + self.meteors.len()
+            + self.raindrops.len()
+            + self.traveling.len()
+            + self.in_flight.len()
+    }
+
+    // Rockets destroyed per hundred shots. A blast that takes out several
+    // rockets can push it past 100.
+    pub fn accuracy(&self) -> u32 {
+        if self.shots_fired == 0 {
+            return 0;
+        }
+        self.rockets_destroyed * 100 / self.shots_fired
+    }
+
+    // Drop an EMP on the cursor, it goes off after a short fuse and stops
+    // every rocket in the sky for a while. Does nothing on cooldown.
+    pub fn fire_emp(&mut self) {
+        if self.emp_timeout > 0.0 {
+            return;
+        }
+        self.emp_timeout = EMP_COOLDOWN;
+
+        let mut emp = Actor::emp();
+        emp.pos = self.player.pos;
+        self.emps.push(emp);
+    }
+
+    // Drop a nuke on the cursor if one is ready, then wait out the cooldown
+    pub fn fire_nuke(&mut self) {
+        if !self.nuke_ready {
+            return;
+        }
+        self.nuke_ready = false;
+        self.nuke_cooldown = NUKE_COOLDOWN;
+        self.shots_fired += 1;
+
+        let mut nuke = Actor::nuke();
+        nuke.pos = self.player.pos;
+        // goes up even with every base destroyed, it's the last resort
+        nuke.initial_pos = self
+            .nearest_base(self.player.pos)
+            .unwrap_or(Point2::new(0.0, -self.screen_height / 2.0 + GROUND_HEIGHT));
+        self.interceptors.alloc(nuke);
+    }
+
+    fn update_nuke(&mut self, dt: f32) {
+        if self.nuke_cooldown <= 0.0 {
+            return;
+        }
+        self.nuke_cooldown -= dt;
+        if self.nuke_cooldown <= 0.0 {
+            self.nuke_ready = true;
+        }
+    }
+
+    fn update_emps(&mut self, dt: f32) {
+        self.emp_timeout = (self.emp_timeout - dt).max(0.0);
+        self.frozen_timer -= dt;
+        self.emp_flash -= dt;
+
+        for emp in &mut self.emps {
+            emp.elapsed -= dt;
+            if emp.elapsed <= 0.0 {
+                self.frozen_timer = EMP_FREEZE_TIME;
+                self.emp_flash = EMP_FLASH_TIME;
+                self.emp_pos = emp.pos;
+            }
+        }
+        self.emps.retain(|e| e.elapsed > 0.0);
+    }
+
+    fn update_reload(&mut self, dt: f32) {
+        if !self.reloading {
+            return;
+        }
+        self.reload_timer -= dt;
+        if self.reload_timer <= 0.0 {
+            self.ammo = self.max_ammo();
+            self.reloading = false;
+        }
+    }
+
+    // Fire a new interceptor by adding it to state
+    fn fire_interceptor(&mut self, timeout: f32, tier: ShotTier) {
+        let Some(base) = self.nearest_base(self.player.pos) else {
+            return;
+        };
+        // bonus shots skip the cooldown and cost no ammo
+        if self.bonus_shots > 0 {
+            self.bonus_shots -= 1;
+        } else {
+            self.shot_timeout = timeout;
+            self.use_ammo(tier.ammo_cost());
+        }
+        self.shots_fired += 1;
+        match self.interceptor_mode {
+            InterceptorMode::Standard => {
+                let mut shot = Actor::interceptor();
+                shot.pos = base;
+                shot.initial_pos = base;
+                shot.target = self.player.pos;
+                shot.depth = self.player.cursor_depth(self.screen_height);
+                shot.radius_scale = tier.radius_scale();
+                self.in_flight.push(shot);
+            }
+            InterceptorMode::WarpGate => {
+                let mut gate = Actor::warp_gate();
+                gate.pos = self.player.pos;
+                self.warp_gates.push(gate);
+            }
+            InterceptorMode::Traveling => {
+                let dir = (self.player.pos - base).normalize_or_zero();
+                let speed = TRAVELING_VEL * tier.speed_scale();
+                self.traveling.push(TravelingInterceptor {
+                    pos: base,
+                    target: self.player.pos,
+                    vel: dir * speed,
+                    speed,
+                    radius_scale: tier.radius_scale(),
+                });
+            }
+        }
+    }
+
+    // Move the second cursor from its input, either the last the other
+    // machine sent or the second player's keys in local co-op. A networked
+    // cursor is eased toward the position it reports to hide jitter.
+    fn update_player2(&mut self, dt: f32) {
+        if !self.two_player {
+            return;
+        }
+
+        self.player2.cursor_move(
+            self.screen_width,
+            self.screen_height,
+            &self.remote_input,
+            self.upgrades.apply_cursor_speed(CURSOR_VEL),
+            dt,
+        );
+        if let Some(remote_pos) = self.remote_pos {
+            self.player2.pos = self.player2.pos.lerp(remote_pos, REMOTE_CURSOR_LERP);
+        }
+
+        self.player2_shot_timeout -= dt;
+        let base = self.nearest_base(self.player2.pos);
+        if base.is_none() || self.ammo == 0 {
+            self.player2_fire_charge = 0.0;
+        }
+        if self.player2_shot_timeout > 0.0 {
+            return;
+        }
+        let Some(base) = base else {
+            return;
+        };
+        let held = self.remote_input.fire && self.ammo > 0;
+        if let Some(tier) = charge_shot(&mut self.player2_fire_charge, held, dt) {
+            self.player2_shot_timeout = self.level.settings.shot_timeout * tier.timeout_scale();
+            self.use_ammo(tier.ammo_cost());
+            self.shots_fired += 1;
+            let mut shot = Actor::interceptor();
+            shot.pos = base;
+            shot.initial_pos = base;
+            shot.target = self.player2.pos;
+            shot.depth = self.player2.cursor_depth(self.screen_height);
+            shot.radius_scale = tier.radius_scale();
+            shot.player2 = true;
+            self.in_flight.push(shot);
+        }
+    }
+
+    // Fly traveling interceptors and detonate the ones that have arrived
+    fn update_traveling(&mut self, dt: f32) {
+        let mut arrived = Vec::new();
+        self.traveling.retain_mut(|t| {
+            if traveling_move(t, dt) {
+                arrived.push((t.pos, t.radius_scale));
+                false
+            } else {
+                true
+            }
+        });
+
+        for (pos, radius_scale) in arrived {
+            let mut shot = Actor::interceptor();
+            shot.pos = pos;
+            shot.radius_scale = radius_scale;
+            shot.fired = true;
+            shot.depth = shot.cursor_depth(self.screen_height);
+            self.interceptors.alloc(shot);
+        }
+    }
+
+    // Spawn, move and retire meteors and rain. Meteors that pass over the
+    // city have a small chance each frame of hitting it.
+    fn update_weather(&mut self, dt: f32) {
+        let screen_x = self.screen_width / 2.0;
+        let screen_y = self.screen_height / 2.0;
+
+        // rates are per second, so spawn with the matching chance each frame
+        let rate = match self.weather {
+            WeatherKind::None => 0.0,
+            WeatherKind::Meteor => METEOR_RATE,
+            WeatherKind::Rain => RAIN_RATE,
+        };
+        let mut to_spawn = rate * dt;
+        while to_spawn > 0.0 {
+            if self.rng.rand_float() < to_spawn {
+                let mut particle = Actor::rocket();
+                particle.pos = Vec2::new(
+                    self.rng.rand_float() * self.screen_width - screen_x,
+                    screen_y,
+                );
+                if self.weather == WeatherKind::Meteor {
+                    // slight slant so meteors streak across the sky
+                    particle.angle = std::f32::consts::PI + (self.rng.rand_float() - 0.5) * 0.6;
+                    self.meteors.push(particle);
+                } else {
+                    particle.angle = std::f32::consts::PI;
+                    self.raindrops.push(particle);
+                }
+            }
+            to_spawn -= 1.0;
+        }
+
+        let min_life = self.city_min_life();
+        for meteor in &mut self.meteors {
+            meteor.weather_move(METEOR_VEL, dt);
+
+            let ground = ground_top(
+                &self.ground_segments,
+                self.screen_width,
+                self.screen_height,
+                meteor.pos.x,
+            );
+            if meteor.pos.y < ground && self.rng.rand_float() < METEOR_HIT_CHANCE {
+                meteor.life = 0.0;
+                if self.mode != GameMode::Chaos {
+                    let city = nearest_city(&self.cities, meteor.pos.x);
+                    damage_city(&mut self.cities, city, 1.0, min_life);
+                    self.floating_texts.push(FloatingText {
+                        pos: meteor.pos,
+                        text: "-1.0 HP".to_string(),
+                        color: [1.0, 0.3, 0.3, 1.0], // light red
+                        timer: FLOATING_TEXT_TIME,
+                    });
+                }
+                self.ground_hits += 1;
+            }
+            if meteor.pos.y < -screen_y {
+                meteor.life = 0.0;
+            }
+        }
+
+        for drop in &mut self.raindrops {
+            drop.weather_move(RAIN_VEL, dt);
+            let ground = ground_top(
+                &self.ground_segments,
+                self.screen_width,
+                self.screen_height,
+                drop.pos.x,
+            );
+            if drop.pos.y < ground {
+                drop.life = 0.0;
+            }
+        }
+
+        self.meteors.retain(|m| m.life > 0.0);
+        self.raindrops.retain(|r| r.life > 0.0);
+    }
+
+    // The bullet hell frenzy is over once none of its tiny rockets are left
+    fn update_bullet_hell(&mut self) {
+        if self.waves.frenzy {
+            self.waves.frenzy = self
+                .rockets
+                .active_iter()
+                .any(|r| r.kind == RocketKind::Tiny);
+        }
+    }
+
+    // Turn the rockets the wave manager asked for into actors on screen
+    fn spawn_wave(&mut self, specs: Vec<RocketSpec>) {
+        let wall_rockets = specs.iter().filter(|s| s.wall).count() as u32;
+        if wall_rockets > 0 {
+            self.wall_remaining = wall_rockets;
+            self.wall_bonus_timer = WALL_BONUS_TIME;
+        }
+        if specs.iter().any(|s| s.kind == RocketKind::Tiny) {
+            self.bullet_hell_banner = BULLET_HELL_BANNER_TIME;
+        }
+
+        for spec in specs {
+            let mut rocket = Actor::rocket();
+            if spec.leader {
+                self.leader = Some(rocket.id);
+            }
+
+            let start_pos = Vec2::new(spec.x * self.screen_width, spec.y * self.screen_height);
+            rocket.pos = start_pos;
+            rocket.initial_pos = match spec.trail_from {
+                Some((x, y)) => Vec2::new(x * self.screen_width, y * self.screen_height),
+                None => start_pos,
+            };
+            rocket.angle = spec.angle;
+            rocket.kind = spec.kind;
+            rocket.depth = spec.depth;
+            rocket.wall = spec.wall;
+            rocket.formation_leader = spec.formation_leader;
+            if rocket.kind == RocketKind::Chaos {
+                rocket.start_chaos_timer();
+            }
+            rocket.target_city_index = self.lock_on(&rocket);
+            if spec.delay > 0.0 {
+                self.pending_rockets.push_back((rocket, spec.delay));
+            } else {
+                self.rockets.alloc(rocket);
+            }
+        }
+    }
+
+    // The city a new rocket locks on to, the one closest to where it is on
+    // course to come down. The lock is only drawn, a ground hit still
+    // damages whichever city is nearest. Chaos and tiny rockets leave no
+    // tracer to draw the lock in, homing and weaving rockets don't hold a
+    // course long enough for one, and in chaos mode there are no cities.
+    fn lock_on(&self, rocket: &Actor) -> Option<usize> {
+        if self.mode == GameMode::Chaos
+            || matches!(
+                rocket.kind,
+                RocketKind::Chaos | RocketKind::Tiny | RocketKind::Homing | RocketKind::Weaver
+            )
+        {
+            return None;
+        }
+        let ground = -self.screen_height / 2.0 + GROUND_HEIGHT;
+        let vel = self.level.current_rocket_vel()
+            * rocket.kind.speed_scale()
+            * rocket.depth
+            * rocket.speed_ramp();
+        let impact = predict_impact(rocket.pos, rocket.angle, vel, self.wind, ground)?;
+        if impact.x.abs() > self.screen_width / 2.0 {
+            return None;
+        }
+        nearest_city(&self.cities, impact.x)
+    }
+
+    // Rockets locked on to a city that has fallen move on to a random one
+    // still standing, or give up once they have all gone
+    fn retarget_rockets(&mut self) {
+        if self.cities.iter().all(|city| city.life > 0.0) {
+            return;
+        }
+        let standing: Vec<usize> = (0..self.cities.len())
+            .filter(|&i| self.cities[i].life > 0.0)
+            .collect();
+
+        let pending = self.pending_rockets.iter_mut().map(|(rocket, _)| rocket);
+        for rocket in self.rockets.active_iter_mut().chain(pending) {
+            let Some(index) = rocket.target_city_index else {
+                continue;
+            };
+            if self.cities[index].life > 0.0 {
+                continue;
+            }
+            rocket.target_city_index = if standing.is_empty() {
+                None
+            } else {
+                Some(standing[self.rng.rand_range(0..standing.len() as u32) as usize])
+            };
+        }
+    }
+
+    // Size the waves to come by how the player has been doing. The score
+    // fades between waves so it mostly reflects the last few.
+    fn adapt_difficulty(&mut self) {
+        if self.performance_score > PERFORMANCE_THRESHOLD {
+            self.waves.adjust_rockets(1);
+        } else if self.performance_score < -PERFORMANCE_THRESHOLD {
+            self.waves.adjust_rockets(-1);
+        }
+        self.performance_score *= PERFORMANCE_DECAY;
+    }
+
+    // Rockets held back from their wave appear once their delay runs out
+    fn release_pending_rockets(&mut self, dt: f32) {
+        for (_, delay) in &mut self.pending_rockets {
+            *delay -= dt;
+        }
+        while let Some(index) = self.pending_rockets.iter().position(|(_, d)| *d <= 0.0) {
+            if let Some((rocket, _)) = self.pending_rockets.remove(index) {
+                self.rockets.alloc(rocket);
+            }
+        }
+    }
+}
+
+impl Simulation for GameSimulation {
+    // nobody is at the controls, so the cursor sits still and never fires
+    fn step(&mut self, dt: f32) {
+        self.tick(dt, &InputState::default());
+    }
+
+    fn is_over(&self) -> bool {
+        self.outcome() != Outcome::Running
+    }
+
+    fn score(&self) -> i32 {
+        self.scoring.score
+    }
+
+    fn level(&self) -> u32 {
+        self.level.level
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::level::{Difficulty, ROCKET_VEL};
+
+    // A game with no modifier in play and nothing on screen yet
+    fn quiet_sim() -> GameSimulation {
+        let mut sim = GameSimulation::new(1280.0, 760.0, GameMode::Normal, 1337, false);
+        sim.level.difficulty = Difficulty::default();
+        sim.wind = 0.0;
+        sim
+    }
+
+    fn add_rocket(sim: &mut GameSimulation, pos: Vec2, angle: f32) -> ActorId {
+        let mut rocket = Actor::rocket();
+        rocket.pos = pos;
+        rocket.initial_pos = pos;
+        rocket.angle = angle;
+        let id = rocket.id;
+        sim.rockets.alloc(rocket);
+        id
+    }
+
+    #[test]
+    fn rocket_moves_rocket_vel_per_second() {
+        let mut sim = quiet_sim();
+        let start = Vec2::new(0.0, 300.0);
+        let id = add_rocket(&mut sim, start, std::f32::consts::PI);
+
+        let dt = 1.0 / 60.0;
+        let mut expected = 0.0;
+        for _ in 0..60 {
+            expected += ROCKET_VEL * sim.rockets.get(id).unwrap().speed_ramp() * dt;
+            sim.move_rockets(dt);
+        }
+
+        let rocket = sim.rockets.get(id).unwrap();
+        let travelled = start - rocket.pos;
+        assert!(travelled.x.abs() < 1e-3);
+        assert!((travelled.y - expected).abs() < 1e-3);
+    }
+
+    #[test]
+    fn rocket_ramps_up_to_rocket_vel() {
+        let mut sim = quiet_sim();
+        let id = add_rocket(&mut sim, Vec2::new(0.0, 300.0), std::f32::consts::PI);
+
+        // slower than ROCKET_VEL at first, then faster once it has flown a while
+        let dt = 1.0 / 60.0;
+        let before = sim.rockets.get(id).unwrap().pos;
+        sim.move_rockets(dt);
+        let first = before.y - sim.rockets.get(id).unwrap().pos.y;
+        assert!(first < ROCKET_VEL * dt);
+
+        for _ in 0..300 {
+            sim.move_rockets(dt);
+        }
+        let before = sim.rockets.get(id).unwrap().pos;
+        sim.move_rockets(dt);
+        let later = before.y - sim.rockets.get(id).unwrap().pos.y;
+        assert!(later > ROCKET_VEL * dt);
+    }
+
+    // A splitter heading straight down with a blast of radius 50 off to
+    // the side of it by offset
+    fn splitter_near_blast(offset: f32) -> GameSimulation {
+        let mut sim = quiet_sim();
+        let id = add_rocket(&mut sim, Vec2::new(offset, 100.0), std::f32::consts::PI);
+        sim.rockets.get_mut(id).unwrap().kind = RocketKind::Splitter;
+
+        let mut blast = Actor::interceptor();
+        blast.pos = Vec2::new(0.0, 100.0);
+        blast.radius = 50.0;
+        sim.interceptors.alloc(blast);
+        sim
+    }
+
+    fn live_rockets(sim: &GameSimulation) -> Vec<&Actor> {
+        sim.rockets.active_iter().filter(|r| r.life > 0.0).collect()
+    }
+
+    #[test]
+    fn clean_hit_kills_a_splitter() {
+        let mut sim = splitter_near_blast(10.0);
+        sim.handle_interceptions();
+        assert!(live_rockets(&sim).is_empty());
+        assert_eq!(sim.rockets_destroyed, 1);
+    }
+
+    #[test]
+    fn near_miss_splits_a_splitter_in_two() {
+        // outside the blast but within SPLIT_RANGE of it
+        let mut sim = splitter_near_blast(60.0);
+        sim.handle_interceptions();
+
+        let children = live_rockets(&sim);
+        assert_eq!(children.len(), 2);
+        for child in &children {
+            assert_eq!(child.kind, RocketKind::Standard);
+            assert_eq!(child.pos, Vec2::new(60.0, 100.0));
+            let turn = (child.angle - std::f32::consts::PI).abs();
+            assert!((turn - SPLIT_ANGLE).abs() < 1e-5);
+        }
+        assert_eq!(sim.rockets_destroyed, 0);
+    }
+
+    #[test]
+    fn splitter_out_of_range_is_left_alone() {
+        let mut sim = splitter_near_blast(100.0);
+        sim.handle_interceptions();
+        let rockets = live_rockets(&sim);
+        assert_eq!(rockets.len(), 1);
+        assert_eq!(rockets[0].kind, RocketKind::Splitter);
+    }
+
+    #[test]
+    fn game_over_once_every_city_is_destroyed() {
+        let mut sim = quiet_sim();
+        assert_eq!(sim.outcome(), Outcome::Running);
+        for city in &mut sim.cities {
+            city.life = 0.0;
+        }
+        assert_eq!(sim.outcome(), Outcome::GameOver);
+    }
+
+    #[test]
+    fn clearing_the_last_wave_ends_the_level_early() {
+        let mut sim = quiet_sim();
+        let level = sim.level.level;
+        sim.level.timer = 3.0;
+        sim.waves.rocket_delay = 5.0;
+        let id = add_rocket(&mut sim, Vec2::new(0.0, 300.0), std::f32::consts::PI);
+        assert_eq!(sim.speed_clear(), None);
+
+        // landing on the cities isn't clearing the sky
+        sim.rockets.get_mut(id).unwrap().life = 0.0;
+        sim.clean_wave = false;
+        assert_eq!(sim.speed_clear(), None);
+
+        sim.clean_wave = true;
+        let score = sim.scoring.score;
+        assert_eq!(sim.speed_clear(), Some(LevelUp { cleared: level }));
+        assert_eq!(sim.scoring.score - score, 30 * level as i32);
+        assert_eq!(sim.level.level, level + 1);
+    }
+
+    #[test]
+    fn no_miss_needs_a_shot_fired() {
+        let mut sim = quiet_sim();
+        sim.rockets_held = false;
+        sim.level.timer = 0.001;
+        sim.tick(1.0 / 60.0, &InputState::default());
+        assert!(sim.transitioning);
+        assert!(sim.no_miss_banner <= 0.0);
+
+        let mut sim = quiet_sim();
+        sim.rockets_held = false;
+        sim.level.timer = 0.001;
+        sim.shots_fired = 1;
+        sim.tick(1.0 / 60.0, &InputState::default());
+        assert!(sim.no_miss_banner > 0.0);
+        assert_eq!(sim.level_start_shots, 1);
+    }
+
+    #[test]
+    fn rockets_off_the_side_are_culled_without_boundary_kills() {
+        let mut sim = quiet_sim();
+        sim.boundary_kills = false;
+        let edge = sim.screen_width / 2.0;
+        let right = std::f32::consts::FRAC_PI_2;
+        let gone = add_rocket(&mut sim, Vec2::new(edge + 150.0, 100.0), right);
+        let left = add_rocket(&mut sim, Vec2::new(-edge - 150.0, 100.0), -right);
+        let showing = add_rocket(&mut sim, Vec2::new(edge + 10.0, 100.0), right);
+        // warped out past the side, on its way in
+        let coming_in = add_rocket(&mut sim, Vec2::new(edge + 150.0, 100.0), -right);
+        for id in [gone, left, showing, coming_in] {
+            sim.rockets.get_mut(id).unwrap().initial_pos = Vec2::new(0.0, 300.0);
+        }
+        let score = sim.scoring.score;
+
+        sim.handle_border_collisions();
+        assert_eq!(sim.rockets.get_mut(gone).unwrap().life, 0.0);
+        assert_eq!(sim.rockets.get_mut(left).unwrap().life, 0.0);
+        assert!(sim.rockets.get_mut(showing).unwrap().life > 0.0);
+        assert!(sim.rockets.get_mut(coming_in).unwrap().life > 0.0);
+        assert_eq!(sim.rockets_destroyed, 0);
+        assert_eq!(sim.ground_hits, 0);
+        assert_eq!(sim.scoring.score, score);
+    }
+
+    #[test]
+    fn turret_misses_are_not_the_players() {
+        let mut sim = quiet_sim();
+        sim.turrets.push(Turret {
+            pos: Point2::new(0.0, -300.0),
+            fire_cooldown: 0.0,
+            target: None,
+        });
+        add_rocket(&mut sim, Vec2::new(0.0, -100.0), std::f32::consts::PI);
+        sim.scoring.score = 1000;
+
+        sim.update_turrets(1.0 / 60.0);
+        assert_eq!(sim.shots_fired, 0);
+        let shot = sim.interceptors.active_iter_mut().next().unwrap();
+        assert!(shot.turret);
+
+        // burned out without a kill
+        shot.elapsed = 0.0;
+        sim.penalize_misses();
+        assert_eq!(sim.scoring.score, 1000);
+        assert!(!sim.level_missed);
+    }
+
+    #[test]
+    fn coop_kills_go_to_whoever_fired() {
+        let mut sim = quiet_sim();
+        sim.two_player = true;
+        for (x, player2) in [(-200.0, false), (0.0, true), (200.0, true)] {
+            add_rocket(&mut sim, Vec2::new(x, 100.0), std::f32::consts::PI);
+            let mut shot = Actor::interceptor();
+            shot.target = Vec2::new(x, 100.0);
+            shot.player2 = player2;
+            let mut blast = shot.detonation();
+            blast.radius = 50.0;
+            sim.interceptors.alloc(blast);
+        }
+
+        let score = sim.scoring.score;
+        sim.handle_interceptions();
+        assert_eq!(sim.player_kills, [1, 2]);
+        assert_eq!(sim.rockets_destroyed, 3);
+        assert!(sim.scoring.score > score);
+    }
+
+    #[test]
+    fn ground_hit_damages_nearest_city_not_lock() {
+        let mut sim = quiet_sim();
+        let nearest = 0;
+        let locked = sim.cities.len() - 1;
+        let ground = -sim.screen_height / 2.0 + GROUND_HEIGHT;
+        let x = sim.cities[nearest].pos.x;
+        let id = add_rocket(&mut sim, Vec2::new(x, ground - 1.0), std::f32::consts::PI);
+        sim.rockets.get_mut(id).unwrap().target_city_index = Some(locked);
+
+        let life = sim.cities[locked].life;
+        sim.handle_border_collisions();
+        assert!(sim.cities[nearest].life < life);
+        assert_eq!(sim.cities[locked].life, life);
+    }
+
+    #[test]
+    fn only_rockets_holding_a_course_lock_on() {
+        let sim = quiet_sim();
+        let mut rocket = Actor::rocket();
+        rocket.pos = Vec2::new(sim.cities[0].pos.x, 300.0);
+        rocket.angle = std::f32::consts::PI;
+        assert_eq!(sim.lock_on(&rocket), Some(0));
+        for kind in [RocketKind::Homing, RocketKind::Weaver, RocketKind::Tiny] {
+            rocket.kind = kind;
+            assert_eq!(sim.lock_on(&rocket), None);
+        }
+    }
+}
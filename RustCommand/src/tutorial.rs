@@ -0,0 +1,74 @@
+use glam::Vec2;
+
+use crate::input::{key_name, KeyBindings};
+
+// The steps of the first launch tutorial, in the order they are taught
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TutorialStep {
+    MoveLeft,
+    MoveRight,
+    MoveUp,
+    MoveDown,
+    FireInterceptor,
+    WatchExplosion,
+    Done,
+}
+
+impl TutorialStep {
+    pub fn next(self) -> TutorialStep {
+        match self {
+            TutorialStep::MoveLeft => TutorialStep::MoveRight,
+            TutorialStep::MoveRight => TutorialStep::MoveUp,
+            TutorialStep::MoveUp => TutorialStep::MoveDown,
+            TutorialStep::MoveDown => TutorialStep::FireInterceptor,
+            TutorialStep::FireInterceptor => TutorialStep::WatchExplosion,
+            TutorialStep::WatchExplosion | TutorialStep::Done => TutorialStep::Done,
+        }
+    }
+
+    // What to do next, naming whatever keys the player has bound
+    pub fn instruction(self, bindings: &KeyBindings) -> String {
+        let keys = |key, alt| format!("{} or {}", key_name(key), key_name(alt));
+        match self {
+            TutorialStep::MoveLeft => format!(
+                "Move the crosshair left with {}",
+                keys(bindings.move_left, bindings.alt_move_left)
+            ),
+            TutorialStep::MoveRight => format!(
+                "Move the crosshair right with {}",
+                keys(bindings.move_right, bindings.alt_move_right)
+            ),
+            TutorialStep::MoveUp => format!(
+                "Move the crosshair up with {}",
+                keys(bindings.move_up, bindings.alt_move_up)
+            ),
+            TutorialStep::MoveDown => format!(
+                "Move the crosshair down with {}",
+                keys(bindings.move_down, bindings.alt_move_down)
+            ),
+            TutorialStep::FireInterceptor => format!(
+                "Tap {} to fire an interceptor at the crosshair, hold it for a bigger one",
+                key_name(bindings.fire)
+            ),
+            TutorialStep::WatchExplosion => {
+                "Rockets caught in the explosion are destroyed".to_string()
+            }
+            TutorialStep::Done => "Get ready!".to_string(),
+        }
+    }
+
+    // Whether the last tick finished this step, from how far the cursor
+    // moved, whether an interceptor went up and whether any are still
+    // exploding
+    pub fn completed(self, moved: Vec2, fired: bool, exploding: bool) -> bool {
+        match self {
+            TutorialStep::MoveLeft => moved.x < 0.0,
+            TutorialStep::MoveRight => moved.x > 0.0,
+            TutorialStep::MoveUp => moved.y > 0.0,
+            TutorialStep::MoveDown => moved.y < 0.0,
+            TutorialStep::FireInterceptor => fired,
+            TutorialStep::WatchExplosion => !exploding,
+            TutorialStep::Done => true,
+        }
+    }
+}
@@ -0,0 +1,872 @@
+use ggez::glam::*;
+use ggez::graphics::{self, Canvas, Color};
+use ggez::Context;
+use serde::{Deserialize, Serialize};
+
+use crate::actor::GROUND_LIFE;
+use crate::level::LEVEL_TIME;
+use crate::physics::world_to_screen_coords;
+use crate::scoring::COMBO_WINDOW;
+use crate::simulation::{GameMode, GameSimulation, Point2, MAX_AMMO};
+
+// Where the movable parts of the HUD go. The score, level and ammo are
+// placed as a fraction of the way across and down the screen, so they keep
+// their place in any window. Health bars follow their cities, so theirs is
+// the offset in pixels from the city to the middle of its bar.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct HudLayout {
+    #[serde(rename = "healthbar")]
+    pub healthbar_pos: Point2,
+    #[serde(rename = "score")]
+    pub score_pos: Point2,
+    #[serde(rename = "level")]
+    pub level_pos: Point2,
+    #[serde(rename = "ammo")]
+    pub ammo_pos: Point2,
+}
+
+// where everything has always been on the default 1280 by 760 window
+impl Default for HudLayout {
+    fn default() -> Self {
+        HudLayout {
+            healthbar_pos: Vec2::new(0.0, -(HEALTHBAR_GAP + HEALTHBAR_HEIGHT / 2.0)),
+            score_pos: Vec2::new(0.5, 10.0 / 760.0),
+            level_pos: Vec2::new(20.0 / 1280.0, 10.0 / 760.0),
+            ammo_pos: Vec2::new(1120.0 / 1280.0, 700.0 / 760.0),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum HudElement {
+    HealthBar,
+    Score,
+    Level,
+    Ammo,
+}
+
+pub const HUD_ELEMENTS: [HudElement; 4] = [
+    HudElement::HealthBar,
+    HudElement::Score,
+    HudElement::Level,
+    HudElement::Ammo,
+];
+
+pub const HUD_HANDLE_SIZE: f32 = 24.0; // how close a click has to be to grab an element
+
+impl HudElement {
+    pub fn name(&self) -> &'static str {
+        match self {
+            HudElement::HealthBar => "Health bars",
+            HudElement::Score => "Score",
+            HudElement::Level => "Level",
+            HudElement::Ammo => "Ammo",
+        }
+    }
+}
+
+impl HudLayout {
+    // Where an element is grabbed on a screen of the given size. The health
+    // bars are all grabbed by the one over city, in screen coordinates.
+    pub fn handle(&self, element: HudElement, screen: Vec2, city: Vec2) -> Vec2 {
+        match element {
+            HudElement::HealthBar => city + self.healthbar_pos,
+            HudElement::Score => self.score_pos * screen,
+            HudElement::Level => self.level_pos * screen,
+            HudElement::Ammo => self.ammo_pos * screen,
+        }
+    }
+
+    // The element with its handle under point, if any
+    pub fn element_at(&self, point: Vec2, screen: Vec2, city: Vec2) -> Option<HudElement> {
+        HUD_ELEMENTS.into_iter().find(|&element| {
+            let offset = point - self.handle(element, screen, city);
+            offset.x.abs() < HUD_HANDLE_SIZE && offset.y.abs() < HUD_HANDLE_SIZE
+        })
+    }
+
+    // Move an element by a mouse movement of delta pixels, keeping the
+    // fractions on the screen
+    pub fn drag(&mut self, element: HudElement, delta: Vec2, screen: Vec2) {
+        let fraction = |pos: &mut Point2| {
+            *pos = (*pos + delta / screen).clamp(Vec2::ZERO, Vec2::ONE);
+        };
+        match element {
+            HudElement::HealthBar => self.healthbar_pos += delta,
+            HudElement::Score => fraction(&mut self.score_pos),
+            HudElement::Level => fraction(&mut self.level_pos),
+            HudElement::Ammo => fraction(&mut self.ammo_pos),
+        }
+    }
+}
+
+// A piece of the HUD. Widgets copy what they show out of the simulation in
+// sync, animate in update and never touch the game themselves. The ones the
+// player can move take their place from set_layout.
+pub trait Widget {
+    fn sync(&mut self, sim: &GameSimulation);
+    fn update(&mut self, dt: f32);
+    fn draw(&self, canvas: &mut Canvas, ctx: &mut Context);
+    fn set_layout(&mut self, _layout: &HudLayout) {}
+}
+
+// Owns every HUD widget, they are drawn in the order they were added
+pub struct UiManager {
+    widgets: Vec<Box<dyn Widget>>,
+}
+
+impl UiManager {
+    // the EMP and nuke status are left out when there are no special weapons
+    pub fn new(
+        reduced_motion: bool,
+        colorblind: bool,
+        specials: bool,
+        layout: &HudLayout,
+    ) -> UiManager {
+        let mut ui = UiManager {
+            widgets: vec![
+                Box::new(HealthBar::new(colorblind)),
+                Box::new(ScoreDisplay::new()),
+                Box::new(LevelDisplay::new()),
+                Box::new(WaveTimer::new()),
+                Box::new(AccuracyDisplay::new()),
+                Box::new(WindIndicator::new()),
+                Box::new(ComboMeter::new()),
+                Box::new(AmmoDisplay::new(reduced_motion)),
+            ],
+        };
+        if specials {
+            ui.widgets.push(Box::new(EmpDisplay::new()));
+            ui.widgets.push(Box::new(NukeDisplay::new()));
+        }
+        ui.set_layout(layout);
+        ui
+    }
+
+    pub fn set_layout(&mut self, layout: &HudLayout) {
+        for widget in &mut self.widgets {
+            widget.set_layout(layout);
+        }
+    }
+
+    pub fn sync(&mut self, sim: &GameSimulation) {
+        for widget in &mut self.widgets {
+            widget.sync(sim);
+        }
+    }
+
+    pub fn update(&mut self, dt: f32) {
+        for widget in &mut self.widgets {
+            widget.update(dt);
+        }
+    }
+
+    pub fn draw(&self, canvas: &mut Canvas, ctx: &mut Context) {
+        for widget in &self.widgets {
+            widget.draw(canvas, ctx);
+        }
+    }
+}
+
+fn draw_text(canvas: &mut Canvas, text: &str, pos: Vec2, color: Color) {
+    canvas.draw(
+        &graphics::Text::new(text),
+        graphics::DrawParam::new().dest(pos).color(color),
+    );
+}
+
+fn draw_rect(canvas: &mut Canvas, rect: graphics::Rect, color: Color) {
+    canvas.draw(
+        &graphics::Quad,
+        graphics::DrawParam::new()
+            .dest(rect.point())
+            .scale(rect.size())
+            .color(color),
+    );
+}
+
+const HEALTHBAR_WIDTH: f32 = 60.0;
+const HEALTHBAR_HEIGHT: f32 = 12.0;
+const HEALTHBAR_GAP: f32 = 40.0; // between the ground and the bottom of each bar
+const HEALTHBAR_DRAIN: f32 = 2.0; // life per second the shown bar catches up at
+const HEALTHBAR_TEXT_SCALE: f32 = 11.0;
+
+// One health bar above each city, hidden in chaos mode where there are none
+pub struct HealthBar {
+    cities: Vec<CityHealth>,
+    visible: bool,
+    colorblind: bool, // orange to blue with the percentage written on top
+    offset: Vec2,     // from each city to the middle of its bar, in pixels
+    screen_width: f32,
+    screen_height: f32,
+}
+
+struct CityHealth {
+    pos: Vec2,
+    life: f32,
+    shown: f32,
+}
+
+impl HealthBar {
+    pub fn new(colorblind: bool) -> HealthBar {
+        HealthBar {
+            cities: Vec::new(),
+            visible: true,
+            colorblind,
+            offset: HudLayout::default().healthbar_pos,
+            screen_width: 0.0,
+            screen_height: 0.0,
+        }
+    }
+}
+
+impl Widget for HealthBar {
+    fn sync(&mut self, sim: &GameSimulation) {
+        if self.cities.len() != sim.cities.len() {
+            self.cities = sim
+                .cities
+                .iter()
+                .map(|c| CityHealth {
+                    pos: c.pos,
+                    life: c.life,
+                    shown: c.life,
+                })
+                .collect();
+        }
+        for (bar, city) in self.cities.iter_mut().zip(&sim.cities) {
+            bar.pos = city.pos;
+            bar.life = city.life;
+        }
+        self.visible = sim.mode != GameMode::Chaos;
+        self.screen_width = sim.screen_width;
+        self.screen_height = sim.screen_height;
+    }
+
+    // slide down to the real value instead of jumping on a hit
+    fn update(&mut self, dt: f32) {
+        for bar in &mut self.cities {
+            if bar.shown > bar.life {
+                bar.shown = (bar.shown - HEALTHBAR_DRAIN * dt).max(bar.life);
+            } else {
+                bar.shown = bar.life;
+            }
+        }
+    }
+
+    fn draw(&self, canvas: &mut Canvas, ctx: &mut Context) {
+        if !self.visible {
+            return;
+        }
+
+        for bar in &self.cities {
+            let pos = world_to_screen_coords(self.screen_width, self.screen_height, bar.pos)
+                + self.offset;
+            let x = pos.x - HEALTHBAR_WIDTH / 2.0;
+            let y = pos.y - HEALTHBAR_HEIGHT / 2.0;
+            let container = graphics::Rect::new(x, y, HEALTHBAR_WIDTH, HEALTHBAR_HEIGHT);
+
+            let health = bar.shown.max(0.0) / GROUND_LIFE;
+            let bar_width = health * (HEALTHBAR_WIDTH - 4.0);
+            let bar_color = if self.colorblind {
+                Color::new(1.0 - 0.8 * health, 0.6 - 0.1 * health, health, 1.0)
+            } else {
+                Color::new(1.0 - health, health, 0.0, 1.0)
+            };
+            let health_bar =
+                graphics::Rect::new(x + 2.0, y + 2.0, bar_width, HEALTHBAR_HEIGHT - 4.0);
+
+            draw_rect(canvas, container, Color::BLACK);
+            draw_rect(canvas, health_bar, bar_color);
+
+            if self.colorblind {
+                let mut text = graphics::Text::new(format!("{:.0}%", health * 100.0));
+                text.set_scale(HEALTHBAR_TEXT_SCALE);
+                let Ok(size) = text.measure(ctx) else {
+                    continue;
+                };
+                let pos = Vec2::new(
+                    x + (HEALTHBAR_WIDTH - size.x) / 2.0,
+                    y + (HEALTHBAR_HEIGHT - size.y) / 2.0,
+                );
+                canvas.draw(
+                    &text,
+                    graphics::DrawParam::new().dest(pos).color(Color::WHITE),
+                );
+            }
+        }
+    }
+
+    fn set_layout(&mut self, layout: &HudLayout) {
+        self.offset = layout.healthbar_pos;
+    }
+}
+
+const SHIELD_COLOR: Color = Color::new(1.0, 0.84, 0.0, 1.0); // gold
+
+// Score at the top center of the screen unless moved, boxed in gold with a countdown
+// beside it while the score shield is up. The high score sits to its left,
+// except in chaos mode where a lower score is better.
+pub struct ScoreDisplay {
+    score: i32,
+    high_score: Option<i32>,
+    shield: f32,
+    pos: Vec2, // fraction of the screen
+    screen_width: f32,
+    screen_height: f32,
+}
+
+impl Default for ScoreDisplay {
+    fn default() -> ScoreDisplay {
+        ScoreDisplay::new()
+    }
+}
+
+impl ScoreDisplay {
+    pub fn new() -> ScoreDisplay {
+        ScoreDisplay {
+            score: 0,
+            high_score: None,
+            shield: 0.0,
+            pos: HudLayout::default().score_pos,
+            screen_width: 0.0,
+            screen_height: 0.0,
+        }
+    }
+}
+
+impl Widget for ScoreDisplay {
+    fn sync(&mut self, sim: &GameSimulation) {
+        self.score = sim.scoring.score;
+        self.high_score = match sim.mode {
+            GameMode::Normal | GameMode::TimedChallenge => {
+                Some(sim.scoring.high_score.max(sim.scoring.score))
+            }
+            GameMode::Chaos => None,
+        };
+        self.shield = sim.scoring.shield_timer;
+        self.screen_width = sim.screen_width;
+        self.screen_height = sim.screen_height;
+    }
+
+    fn update(&mut self, _dt: f32) {}
+
+    fn draw(&self, canvas: &mut Canvas, ctx: &mut Context) {
+        let text = format!("Score: {}", self.score);
+        let pos = self.pos * Vec2::new(self.screen_width, self.screen_height);
+        draw_text(canvas, &text, pos, Color::WHITE);
+
+        if let Some(high_score) = self.high_score {
+            draw_text(
+                canvas,
+                &format!("High: {}", high_score),
+                Vec2::new(pos.x - 180.0, pos.y),
+                Color::WHITE,
+            );
+        }
+
+        if self.shield <= 0.0 {
+            return;
+        }
+
+        let size = match graphics::Text::new(text).measure(ctx) {
+            Ok(size) => size,
+            Err(_) => return,
+        };
+        let border = graphics::Rect::new(pos.x - 4.0, pos.y - 4.0, size.x + 8.0, size.y + 8.0);
+        if let Ok(mesh) = graphics::Mesh::new_rectangle(
+            ctx,
+            graphics::DrawMode::stroke(2.0),
+            border,
+            SHIELD_COLOR,
+        ) {
+            canvas.draw(&mesh, Vec2::ZERO);
+        }
+
+        draw_text(
+            canvas,
+            &format!("SHIELD: {:.1}s", self.shield),
+            Vec2::new(border.x + border.w + 10.0, pos.y),
+            SHIELD_COLOR,
+        );
+    }
+
+    fn set_layout(&mut self, layout: &HudLayout) {
+        self.pos = layout.score_pos;
+    }
+}
+
+const WIND_ARROW_SCALE: f32 = 1.5; // pixels of arrow per unit of wind
+const WIND_ARROW_HEAD: f32 = 6.0;
+const WIND_COLOR: Color = Color::new(0.6, 0.8, 1.0, 1.0);
+
+// Arrow under the score pointing the way the wind blows, longer when it is
+// stronger, nothing at all when it is calm
+pub struct WindIndicator {
+    wind: f32,
+    screen_width: f32,
+}
+
+impl Default for WindIndicator {
+    fn default() -> WindIndicator {
+        WindIndicator::new()
+    }
+}
+
+impl WindIndicator {
+    pub fn new() -> WindIndicator {
+        WindIndicator {
+            wind: 0.0,
+            screen_width: 0.0,
+        }
+    }
+}
+
+impl Widget for WindIndicator {
+    fn sync(&mut self, sim: &GameSimulation) {
+        self.wind = sim.wind;
+        self.screen_width = sim.screen_width;
+    }
+
+    fn update(&mut self, _dt: f32) {}
+
+    fn draw(&self, canvas: &mut Canvas, ctx: &mut Context) {
+        let length = self.wind * WIND_ARROW_SCALE;
+        if length.abs() < WIND_ARROW_HEAD {
+            return;
+        }
+
+        let center = Vec2::new(self.screen_width / 2.0 + 40.0, 45.0);
+        let tail = center - Vec2::new(length / 2.0, 0.0);
+        let tip = center + Vec2::new(length / 2.0, 0.0);
+        // the label sits just left of the arrow whichever way it points
+        let label = Vec2::new(tail.x.min(tip.x) - 50.0, center.y - 8.0);
+        draw_text(canvas, "Wind", label, WIND_COLOR);
+
+        let back = -WIND_ARROW_HEAD * length.signum();
+        let points = [
+            tail,
+            tip,
+            tip + Vec2::new(back, -WIND_ARROW_HEAD),
+            tip,
+            tip + Vec2::new(back, WIND_ARROW_HEAD),
+        ];
+        if let Ok(mesh) = graphics::Mesh::new_line(ctx, &points, 2.0, WIND_COLOR) {
+            canvas.draw(&mesh, Vec2::ZERO);
+        }
+    }
+}
+
+// Current level in the top left corner unless moved
+pub struct LevelDisplay {
+    level: u32,
+    pos: Vec2, // fraction of the screen
+    screen_width: f32,
+    screen_height: f32,
+}
+
+impl Default for LevelDisplay {
+    fn default() -> LevelDisplay {
+        LevelDisplay::new()
+    }
+}
+
+impl LevelDisplay {
+    pub fn new() -> LevelDisplay {
+        LevelDisplay {
+            level: 1,
+            pos: HudLayout::default().level_pos,
+            screen_width: 0.0,
+            screen_height: 0.0,
+        }
+    }
+}
+
+impl Widget for LevelDisplay {
+    fn sync(&mut self, sim: &GameSimulation) {
+        self.level = sim.level.level;
+        self.screen_width = sim.screen_width;
+        self.screen_height = sim.screen_height;
+    }
+
+    fn update(&mut self, _dt: f32) {}
+
+    fn draw(&self, canvas: &mut Canvas, _ctx: &mut Context) {
+        draw_text(
+            canvas,
+            &format!("Level: {}", self.level),
+            self.pos * Vec2::new(self.screen_width, self.screen_height),
+            Color::WHITE,
+        );
+    }
+
+    fn set_layout(&mut self, layout: &HudLayout) {
+        self.pos = layout.level_pos;
+    }
+}
+
+// Time left in the level, or in the whole game down in the bottom left for
+// chaos mode
+pub struct WaveTimer {
+    remaining: f32,
+    mode: GameMode,
+    screen_width: f32,
+    screen_height: f32,
+}
+
+impl Default for WaveTimer {
+    fn default() -> WaveTimer {
+        WaveTimer::new()
+    }
+}
+
+impl WaveTimer {
+    pub fn new() -> WaveTimer {
+        WaveTimer {
+            remaining: LEVEL_TIME,
+            mode: GameMode::Normal,
+            screen_width: 0.0,
+            screen_height: 0.0,
+        }
+    }
+}
+
+impl Widget for WaveTimer {
+    fn sync(&mut self, sim: &GameSimulation) {
+        self.mode = sim.mode;
+        self.remaining = match sim.mode {
+            GameMode::Normal => sim.level.timer,
+            GameMode::Chaos | GameMode::TimedChallenge => sim.session_timer,
+        };
+        self.screen_width = sim.screen_width;
+        self.screen_height = sim.screen_height;
+    }
+
+    fn update(&mut self, _dt: f32) {}
+
+    fn draw(&self, canvas: &mut Canvas, _ctx: &mut Context) {
+        match self.mode {
+            GameMode::Normal => draw_text(
+                canvas,
+                &format!("Next wave: {:.0}", self.remaining.max(0.0).ceil()),
+                Vec2::new(self.screen_width - 140.0, 10.0),
+                Color::WHITE,
+            ),
+            GameMode::Chaos => draw_text(
+                canvas,
+                &format!("Time: {:.0}", self.remaining.max(0.0).ceil()),
+                Vec2::new(25.0, self.screen_height - 60.0),
+                Color::MAGENTA,
+            ),
+            // in place of the wave timer, the levels go up without a break
+            GameMode::TimedChallenge => draw_text(
+                canvas,
+                &format!("Time: {:.0}", self.remaining.max(0.0).ceil()),
+                Vec2::new(self.screen_width - 140.0, 10.0),
+                Color::YELLOW,
+            ),
+        }
+    }
+}
+
+// Rockets destroyed per shot, under the wave timer in the top right corner
+pub struct AccuracyDisplay {
+    accuracy: u32,
+    screen_width: f32,
+}
+
+impl Default for AccuracyDisplay {
+    fn default() -> AccuracyDisplay {
+        AccuracyDisplay::new()
+    }
+}
+
+impl AccuracyDisplay {
+    pub fn new() -> AccuracyDisplay {
+        AccuracyDisplay {
+            accuracy: 0,
+            screen_width: 0.0,
+        }
+    }
+}
+
+impl Widget for AccuracyDisplay {
+    fn sync(&mut self, sim: &GameSimulation) {
+        self.accuracy = sim.accuracy();
+        self.screen_width = sim.screen_width;
+    }
+
+    fn update(&mut self, _dt: f32) {}
+
+    fn draw(&self, canvas: &mut Canvas, _ctx: &mut Context) {
+        draw_text(
+            canvas,
+            &format!("Accuracy: {}%", self.accuracy),
+            Vec2::new(self.screen_width - 140.0, 30.0),
+            Color::WHITE,
+        );
+    }
+}
+
+// EMP cooldown under the accuracy, Shift + Space fires it once it's ready
+pub struct EmpDisplay {
+    cooldown: f32,
+    screen_width: f32,
+}
+
+impl Default for EmpDisplay {
+    fn default() -> EmpDisplay {
+        EmpDisplay::new()
+    }
+}
+
+impl EmpDisplay {
+    pub fn new() -> EmpDisplay {
+        EmpDisplay {
+            cooldown: 0.0,
+            screen_width: 0.0,
+        }
+    }
+}
+
+impl Widget for EmpDisplay {
+    fn sync(&mut self, sim: &GameSimulation) {
+        self.cooldown = sim.emp_timeout;
+        self.screen_width = sim.screen_width;
+    }
+
+    fn update(&mut self, _dt: f32) {}
+
+    fn draw(&self, canvas: &mut Canvas, _ctx: &mut Context) {
+        let pos = Vec2::new(self.screen_width - 140.0, 50.0);
+        if self.cooldown > 0.0 {
+            draw_text(
+                canvas,
+                &format!("EMP: {:.0}", self.cooldown.ceil()),
+                pos,
+                Color::WHITE,
+            );
+        } else {
+            draw_text(canvas, "EMP: Ready", pos, Color::GREEN);
+        }
+    }
+}
+
+const NUKE_READY_SCALE: f32 = 32.0;
+const NUKE_COLOR: Color = Color::new(1.0, 0.5, 0.0, 1.0);
+
+// Nuke status in the top left, under the level and the interceptor mode
+// the renderer draws below it, big while one is ready
+pub struct NukeDisplay {
+    ready: bool,
+    cooldown: f32,
+}
+
+impl Default for NukeDisplay {
+    fn default() -> NukeDisplay {
+        NukeDisplay::new()
+    }
+}
+
+impl NukeDisplay {
+    pub fn new() -> NukeDisplay {
+        NukeDisplay {
+            ready: false,
+            cooldown: 0.0,
+        }
+    }
+}
+
+impl Widget for NukeDisplay {
+    fn sync(&mut self, sim: &GameSimulation) {
+        self.ready = sim.nuke_ready;
+        self.cooldown = sim.nuke_cooldown;
+    }
+
+    fn update(&mut self, _dt: f32) {}
+
+    fn draw(&self, canvas: &mut Canvas, _ctx: &mut Context) {
+        let pos = Vec2::new(20.0, 50.0);
+        if self.ready {
+            let mut text = graphics::Text::new("NUKE READY");
+            text.set_scale(NUKE_READY_SCALE);
+            canvas.draw(
+                &text,
+                graphics::DrawParam::new().dest(pos).color(NUKE_COLOR),
+            );
+        } else if self.cooldown > 0.0 {
+            draw_text(
+                canvas,
+                &format!("Nuke: {:.0}", self.cooldown.ceil()),
+                pos,
+                Color::WHITE,
+            );
+        } else {
+            draw_text(canvas, "Nuke: next level", pos, Color::WHITE);
+        }
+    }
+}
+
+const COMBO_TEXT_MAX_SCALE: f32 = 64.0;
+const COMBO_TEXT_MIN_SCALE: f32 = 24.0;
+
+// Big multiplier callout in the middle of the screen while a kill streak
+// lasts, shrinking as the time to keep it going runs out
+pub struct ComboMeter {
+    multiplier: u32,
+    timer: f32,
+    screen_width: f32,
+    screen_height: f32,
+}
+
+impl Default for ComboMeter {
+    fn default() -> ComboMeter {
+        ComboMeter::new()
+    }
+}
+
+impl ComboMeter {
+    pub fn new() -> ComboMeter {
+        ComboMeter {
+            multiplier: 1,
+            timer: 0.0,
+            screen_width: 0.0,
+            screen_height: 0.0,
+        }
+    }
+}
+
+impl Widget for ComboMeter {
+    fn sync(&mut self, sim: &GameSimulation) {
+        self.multiplier = sim.scoring.combo_multiplier();
+        self.timer = sim.scoring.combo_timer;
+        self.screen_width = sim.screen_width;
+        self.screen_height = sim.screen_height;
+    }
+
+    fn update(&mut self, _dt: f32) {}
+
+    fn draw(&self, canvas: &mut Canvas, ctx: &mut Context) {
+        if self.multiplier < 2 {
+            return;
+        }
+
+        let left = (self.timer / COMBO_WINDOW).clamp(0.0, 1.0);
+        let mut text = graphics::Text::new(format!("\u{00d7}{} COMBO!", self.multiplier));
+        text.set_scale(COMBO_TEXT_MIN_SCALE + (COMBO_TEXT_MAX_SCALE - COMBO_TEXT_MIN_SCALE) * left);
+
+        let size = match text.measure(ctx) {
+            Ok(size) => size,
+            Err(_) => return,
+        };
+        let pos = Vec2::new(
+            (self.screen_width - size.x) / 2.0,
+            self.screen_height * 0.15 - size.y / 2.0,
+        );
+        canvas.draw(
+            &text,
+            graphics::DrawParam::new().dest(pos).color(Color::YELLOW),
+        );
+    }
+}
+
+const AMMO_SLOT_WIDTH: f32 = 8.0;
+const AMMO_SLOT_HEIGHT: f32 = 14.0;
+const AMMO_SLOTS_SPAN: f32 = 150.0; // upgraded magazines squeeze into this
+const AMMO_SLOT_GAP: f32 = 4.0;
+const SPENT_AMMO_COLOR: Color = Color::new(0.3, 0.3, 0.3, 1.0);
+const RELOAD_FLASH_RATE: f32 = 2.0; // flashes per second
+
+// Ammo, cooldown, queued shots and bonus shots in the bottom right corner
+// unless moved
+pub struct AmmoDisplay {
+    ammo: u32,
+    max_ammo: u32,
+    reloading: bool,
+    reload_timer: f32,
+    cooldown: f32,
+    queued: usize,
+    bonus: u32,
+    pos: Vec2, // fraction of the screen
+    screen_width: f32,
+    screen_height: f32,
+    reduced_motion: bool, // show RELOADING steadily instead of flashing
+}
+
+impl AmmoDisplay {
+    pub fn new(reduced_motion: bool) -> AmmoDisplay {
+        AmmoDisplay {
+            ammo: MAX_AMMO,
+            max_ammo: MAX_AMMO,
+            reloading: false,
+            reload_timer: 0.0,
+            cooldown: 0.0,
+            queued: 0,
+            bonus: 0,
+            pos: HudLayout::default().ammo_pos,
+            screen_width: 0.0,
+            screen_height: 0.0,
+            reduced_motion,
+        }
+    }
+}
+
+impl Widget for AmmoDisplay {
+    fn sync(&mut self, sim: &GameSimulation) {
+        self.ammo = sim.ammo;
+        self.max_ammo = sim.max_ammo();
+        self.reloading = sim.reloading;
+        self.reload_timer = sim.reload_timer;
+        self.cooldown = sim.shot_timeout.max(0.0);
+        self.queued = sim.shot_queue.len();
+        self.bonus = sim.bonus_shots;
+        self.screen_width = sim.screen_width;
+        self.screen_height = sim.screen_height;
+    }
+
+    fn update(&mut self, _dt: f32) {}
+
+    fn draw(&self, canvas: &mut Canvas, _ctx: &mut Context) {
+        let Vec2 { x, y } = self.pos * Vec2::new(self.screen_width, self.screen_height);
+
+        // one slot per round, spent ones grayed out
+        let pitch = (AMMO_SLOTS_SPAN / self.max_ammo as f32).min(AMMO_SLOT_WIDTH + AMMO_SLOT_GAP);
+        let width = AMMO_SLOT_WIDTH.min(pitch - 1.0);
+        for slot in 0..self.max_ammo {
+            let color = if slot < self.ammo {
+                Color::WHITE
+            } else {
+                SPENT_AMMO_COLOR
+            };
+            let rect = graphics::Rect::new(
+                x + slot as f32 * pitch,
+                y - AMMO_SLOT_HEIGHT - 10.0,
+                width,
+                AMMO_SLOT_HEIGHT,
+            );
+            draw_rect(canvas, rect, color);
+        }
+
+        if self.reloading {
+            let shown =
+                self.reduced_motion || (self.reload_timer * RELOAD_FLASH_RATE).fract() < 0.5;
+            if shown {
+                draw_text(canvas, "RELOADING", Vec2::new(x, y), Color::RED);
+            }
+        } else if self.cooldown > 0.0 {
+            draw_text(canvas, "Cooldown", Vec2::new(x, y), Color::YELLOW);
+        } else {
+            draw_text(canvas, "Ready", Vec2::new(x, y), Color::GREEN);
+        }
+
+        let mut details = format!("Queued: {}", self.queued);
+        if self.bonus > 0 {
+            details.push_str(&format!("  Bonus: {}", self.bonus));
+        }
+        draw_text(canvas, &details, Vec2::new(x, y + 20.0), Color::WHITE);
+    }
+
+    fn set_layout(&mut self, layout: &HudLayout) {
+        self.pos = layout.ammo_pos;
+    }
+}
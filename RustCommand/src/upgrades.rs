@@ -0,0 +1,91 @@
+use oorandom::Rand32;
+use serde::{Deserialize, Serialize};
+
+pub const SHOP_OFFERS: usize = 3;
+
+// Permanent improvements bought between levels, they last until the game
+// ends. Each bonus starts at nothing and grows with every purchase.
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+pub struct Upgrades {
+    pub interceptor_radius_bonus: f32, // fraction of the base radius added
+    pub cursor_speed_bonus: f32,       // fraction of the base speed added
+    pub ammo_bonus: u32,               // extra rounds in a full magazine
+    pub reload_speed_bonus: f32,       // fraction of the base reload speed added
+}
+
+impl Upgrades {
+    pub fn apply_radius(&self, base: f32) -> f32 {
+        base * (1.0 + self.interceptor_radius_bonus)
+    }
+
+    pub fn apply_cursor_speed(&self, base: f32) -> f32 {
+        base * (1.0 + self.cursor_speed_bonus)
+    }
+
+    pub fn apply_max_ammo(&self, base: u32) -> u32 {
+        base + self.ammo_bonus
+    }
+
+    // a faster reload takes less time
+    pub fn apply_reload_time(&self, base: f32) -> f32 {
+        base / (1.0 + self.reload_speed_bonus)
+    }
+}
+
+// One thing the shop can sell
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum UpgradeKind {
+    BlastRadius,
+    CursorSpeed,
+    ExtraAmmo,
+    FastReload,
+}
+
+const UPGRADE_POOL: [UpgradeKind; 4] = [
+    UpgradeKind::BlastRadius,
+    UpgradeKind::CursorSpeed,
+    UpgradeKind::ExtraAmmo,
+    UpgradeKind::FastReload,
+];
+
+impl UpgradeKind {
+    pub fn name(&self) -> &'static str {
+        match self {
+            UpgradeKind::BlastRadius => "Bigger blasts +15%",
+            UpgradeKind::CursorSpeed => "Faster crosshair +20%",
+            UpgradeKind::ExtraAmmo => "Extra interceptors +2",
+            UpgradeKind::FastReload => "Faster reload +25%",
+        }
+    }
+
+    // Kills pay the level times their points, so prices rise the same way
+    pub fn cost(&self, level: u32) -> i32 {
+        let base = match self {
+            UpgradeKind::BlastRadius => 3000,
+            UpgradeKind::CursorSpeed => 1500,
+            UpgradeKind::ExtraAmmo => 2500,
+            UpgradeKind::FastReload => 2000,
+        };
+        base * level as i32
+    }
+
+    pub fn apply(&self, upgrades: &mut Upgrades) {
+        match self {
+            UpgradeKind::BlastRadius => upgrades.interceptor_radius_bonus += 0.15,
+            UpgradeKind::CursorSpeed => upgrades.cursor_speed_bonus += 0.2,
+            UpgradeKind::ExtraAmmo => upgrades.ammo_bonus += 2,
+            UpgradeKind::FastReload => upgrades.reload_speed_bonus += 0.25,
+        }
+    }
+}
+
+// Pick the upgrades on offer this time, no two the same
+pub fn roll_offers(rng: &mut Rand32) -> [UpgradeKind; SHOP_OFFERS] {
+    let mut pool = UPGRADE_POOL;
+    // partial shuffle, the first few end up as a random selection
+    for i in 0..SHOP_OFFERS {
+        let j = rng.rand_range(i as u32..pool.len() as u32) as usize;
+        pool.swap(i, j);
+    }
+    [pool[0], pool[1], pool[2]]
+}
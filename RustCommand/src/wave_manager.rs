@@ -0,0 +1,335 @@
+use oorandom::Rand32;
+use std::ops::Range;
+
+use crate::actor::{RocketKind, DEPTH_FAR, DEPTH_MID, DEPTH_NEAR};
+use crate::level::DifficultySettings;
+
+pub const ROCKET_DELAY: f32 = 4.0;
+
+const DEPTH_LAYERS: [f32; 3] = [DEPTH_FAR, DEPTH_MID, DEPTH_NEAR];
+
+const CHAOS_ROCKET_LEVEL: u32 = 12;
+const CHAOS_ROCKET_CHANCE: f32 = 0.2;
+
+const SPLITTER_LEVEL: u32 = 3;
+
+// from here on rockets can come in from the left and right edges too
+const SIDE_SPAWN_LEVEL: u32 = 5;
+
+// one homing rocket per wave at first, then another every two levels
+const HOMING_LEVEL: u32 = 5;
+const HOMING_LEVEL_INTERVAL: u32 = 2;
+
+fn homing_rockets(level: u32) -> u32 {
+    if level < HOMING_LEVEL {
+        0
+    } else {
+        1 + (level - HOMING_LEVEL) / HOMING_LEVEL_INTERVAL
+    }
+}
+
+const WALL_FIRST_LEVEL: u32 = 25;
+const WALL_LEVEL_INTERVAL: u32 = 10;
+const WALL_ROCKETS: u32 = 15;
+const WALL_WARNING_TIME: f32 = 3.0;
+
+fn is_wall_level(level: u32) -> bool {
+    level >= WALL_FIRST_LEVEL && (level - WALL_FIRST_LEVEL).is_multiple_of(WALL_LEVEL_INTERVAL)
+}
+
+// from here on a wave is sometimes a V of rockets flying together
+const FORMATION_LEVEL: u32 = 8;
+const FORMATION_CHANCE: f32 = 0.25;
+const FORMATION_SIZE: u32 = 5;
+const FORMATION_SPREAD: f32 = std::f32::consts::PI / 12.0; // 15 degrees either side of the middle
+const FORMATION_SPACING: f32 = 0.025; // about 30 pixels apart on the default window
+
+// the rockets of a regular wave trickle in over this many seconds instead
+// of all arriving together
+const STAGGER_MAX: f32 = 2.0;
+
+// how far adaptive difficulty can move the size of a regular wave, and the
+// most rockets one can have
+const MAX_ROCKET_ADJUSTMENT: i32 = 5;
+const MAX_ROCKETS_PER_WAVE: i32 = 30;
+
+// from here on some rockets weave from side to side
+const WEAVER_LEVEL: u32 = 6;
+const WEAVER_CHANCE: f32 = 0.25;
+
+// the frenzy is the bullet hell swarm of tiny rockets
+const FRENZY_LEVEL: u32 = 18;
+const FRENZY_PERIOD: f32 = 60.0;
+const FRENZY_ROCKETS: u32 = 50;
+
+// A rocket waiting to be spawned. The position is a fraction of the screen
+// size from its center, so specs don't depend on the window.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RocketSpec {
+    pub x: f32,
+    pub y: f32,
+    pub angle: f32,
+    pub kind: RocketKind,
+    pub depth: f32,
+    pub wall: bool,
+    pub leader: bool,
+    pub formation_leader: bool,
+    pub trail_from: Option<(f32, f32)>, // where the tracer starts, the spawn point if None
+    pub delay: f32,                     // seconds after the wave arrives before this one does
+}
+
+impl RocketSpec {
+    fn new(x: f32, y: f32, angle: f32) -> RocketSpec {
+        RocketSpec {
+            x,
+            y,
+            angle,
+            kind: RocketKind::Standard,
+            depth: DEPTH_MID,
+            wall: false,
+            leader: false,
+            formation_leader: false,
+            trail_from: None,
+            delay: 0.0,
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub enum WaveAction {
+    SpawnWave(Vec<RocketSpec>),
+    Nothing,
+}
+
+// Decides when rockets arrive and what they look like: the regular waves,
+// the wall of death and the bullet hell frenzy
+#[derive(Debug)]
+pub struct WaveManager {
+    pub rocket_delay: f32,
+    pub level: u32,
+    pub rockets_per_wave: Range<u32>,
+    pub rocket_adjustment: i32, // added to every regular wave by adaptive difficulty
+    pub frenzy: bool,
+    pub frenzy_timer: f32,
+    pub wall_warning: Option<f32>,
+    base_delay: f32,
+    head_start: u32, // levels ahead of the real one that rocket types unlock at
+    rng: Rand32,
+}
+
+// generate a random angle between 0.75 PI and 1.25 PI
+// an angle of PI sends the rocket straight downward
+fn random_heading(rng: &mut Rand32) -> f32 {
+    rng.rand_float() * 0.5 * std::f32::consts::PI + 0.75 * std::f32::consts::PI
+}
+
+// A rocket coming in from the left or right edge, somewhere in the upper half
+// of the sky and angled down and inward
+fn side_rocket(rng: &mut Rand32) -> RocketSpec {
+    use std::f32::consts::PI;
+
+    let y = 0.1 + rng.rand_float() * 0.4;
+    // between sideways and halfway to straight down
+    let tilt = rng.rand_float() * 0.25 * PI;
+    if rng.rand_float() < 0.5 {
+        RocketSpec::new(-0.5, y, 0.5 * PI + tilt)
+    } else {
+        RocketSpec::new(0.5, y, 1.5 * PI - tilt)
+    }
+}
+
+impl WaveManager {
+    pub fn new(seed: u64) -> WaveManager {
+        WaveManager {
+            rocket_delay: ROCKET_DELAY,
+            // set by start_level before the first tick
+            level: 0,
+            rockets_per_wave: 0..1,
+            rocket_adjustment: 0,
+            frenzy: false,
+            frenzy_timer: FRENZY_PERIOD,
+            wall_warning: None,
+            base_delay: ROCKET_DELAY,
+            head_start: 0,
+            rng: Rand32::new(seed),
+        }
+    }
+
+    pub fn set_difficulty(&mut self, settings: &DifficultySettings) {
+        self.base_delay = settings.rocket_delay;
+        self.rocket_delay = settings.rocket_delay;
+        self.head_start = settings.head_start;
+    }
+
+    // Called when a level starts, walls are announced ahead of time
+    pub fn start_level(&mut self, level: u32, rockets_per_wave: Range<u32>) {
+        self.level = level;
+        self.rockets_per_wave = rockets_per_wave;
+        if is_wall_level(level) {
+            self.wall_warning = Some(WALL_WARNING_TIME);
+        }
+    }
+
+    // Make the regular waves a rocket bigger or smaller, within limits so a
+    // long run of good or bad waves can be undone quickly
+    pub fn adjust_rockets(&mut self, change: i32) {
+        self.rocket_adjustment =
+            (self.rocket_adjustment + change).clamp(-MAX_ROCKET_ADJUSTMENT, MAX_ROCKET_ADJUSTMENT);
+    }
+
+    // Seconds until anything else is due to arrive
+    pub fn next_arrival(&self) -> f32 {
+        let mut next = self.rocket_delay;
+        if let Some(warning) = self.wall_warning {
+            next = next.min(warning);
+        }
+        if !self.frenzy && self.level >= FRENZY_LEVEL {
+            next = next.min(self.frenzy_timer);
+        }
+        next
+    }
+
+    pub fn tick(&mut self, dt: f32) -> WaveAction {
+        let mut specs = Vec::new();
+
+        // the wall skips the usual rocket delay
+        if let Some(warning) = self.wall_warning {
+            if warning - dt <= 0.0 {
+                self.wall_warning = None;
+                specs.extend(self.create_wall());
+            } else {
+                self.wall_warning = Some(warning - dt);
+            }
+        }
+
+        // every so often on later levels, flood the sky with tiny slow rockets
+        if !self.frenzy && self.level >= FRENZY_LEVEL {
+            self.frenzy_timer -= dt;
+            if self.frenzy_timer <= 0.0 {
+                self.frenzy_timer = FRENZY_PERIOD;
+                self.frenzy = true;
+                specs.extend(self.create_swarm());
+            }
+        }
+
+        self.rocket_delay -= dt;
+        if self.rocket_delay <= 0.0 {
+            self.rocket_delay = self.base_delay;
+            specs.extend(self.create_rockets());
+        }
+
+        if specs.is_empty() {
+            WaveAction::Nothing
+        } else {
+            WaveAction::SpawnWave(specs)
+        }
+    }
+
+    // a wave of rockets, adapted from the ggez example create_rock method
+    fn create_rockets(&mut self) -> Vec<RocketSpec> {
+        let unlocked = self.level + self.head_start;
+        if unlocked >= FORMATION_LEVEL && self.rng.rand_float() < FORMATION_CHANCE {
+            return self.create_formation();
+        }
+
+        // the adjustment can't take a wave past the cap, but the highest
+        // levels go past it on their own
+        let num = self.rng.rand_range(self.rockets_per_wave.clone()) as i32;
+        let most = MAX_ROCKETS_PER_WAVE.max(num);
+        let num = (num + self.rocket_adjustment).clamp(1, most) as u32;
+        let homing = homing_rockets(unlocked);
+
+        let new_rocket = |i| {
+            let depth = DEPTH_LAYERS[self.rng.rand_range(0..DEPTH_LAYERS.len() as u32) as usize];
+            // on later levels the top, left and right edges are equally likely
+            let from_side = unlocked >= SIDE_SPAWN_LEVEL && self.rng.rand_range(0..3) > 0;
+            let mut rocket = if from_side {
+                side_rocket(&mut self.rng)
+            } else {
+                // random starting pos at top of screen, near rockets only
+                // come into view halfway down
+                let y = if depth == DEPTH_NEAR { 0.0 } else { 0.5 };
+                let x = self.rng.rand_float() - 0.5;
+                RocketSpec::new(x, y, random_heading(&mut self.rng))
+            };
+            rocket.depth = depth;
+            // the first rocket of each wave leads it, the rest follow it in
+            rocket.leader = i == 0;
+            if i > 0 {
+                rocket.delay = self.rng.rand_float() * STAGGER_MAX;
+            }
+
+            if unlocked >= CHAOS_ROCKET_LEVEL && self.rng.rand_float() < CHAOS_ROCKET_CHANCE {
+                rocket.kind = RocketKind::Chaos;
+            } else if unlocked >= SPLITTER_LEVEL && i == num - 1 {
+                // one splitter per wave, the last rocket in it
+                rocket.kind = RocketKind::Splitter;
+            } else if i > 0 && i <= homing {
+                // the ones right behind the leader home in
+                rocket.kind = RocketKind::Homing;
+            } else if unlocked >= WEAVER_LEVEL && self.rng.rand_float() < WEAVER_CHANCE {
+                rocket.kind = RocketKind::Weaver;
+            }
+            rocket
+        };
+        (0..num).map(new_rocket).collect()
+    }
+
+    // A V of rockets with the leader in the middle and the wings fanned out
+    // to either side. A wing falls at more of a slant, so it starts that much
+    // lower and the whole formation reaches the ground together.
+    fn create_formation(&mut self) -> Vec<RocketSpec> {
+        let x = self.rng.rand_float() * 0.6 - 0.3;
+        let heading = random_heading(&mut self.rng);
+        let middle = (FORMATION_SIZE / 2) as f32;
+
+        let new_rocket = |i| {
+            let place = i as f32 - middle;
+            let angle = heading + place / middle * FORMATION_SPREAD;
+            // the middle one falls the full screen height from the top
+            let y = -0.5 + angle.cos() / heading.cos();
+            let mut rocket = RocketSpec::new(x + place * FORMATION_SPACING, y, angle);
+            rocket.formation_leader = i == FORMATION_SIZE / 2;
+            rocket
+        };
+        let mut formation: Vec<RocketSpec> = (0..FORMATION_SIZE).map(new_rocket).collect();
+
+        // a steep wing can start above the top of the sky, lower them all
+        // together so none are lost off the top
+        let drop = formation.iter().map(|r| r.y).fold(0.5, f32::max) - 0.5;
+        for rocket in &mut formation {
+            rocket.y -= drop;
+        }
+
+        // every tracer is drawn from the middle of the formation
+        let center_y = formation.iter().map(|r| r.y).sum::<f32>() / FORMATION_SIZE as f32;
+        for rocket in &mut formation {
+            rocket.trail_from = Some((x, center_y));
+        }
+        formation
+    }
+
+    // the bullet hell swarm scattered across the upper half of the sky
+    fn create_swarm(&mut self) -> Vec<RocketSpec> {
+        let new_rocket = |_| {
+            let x = self.rng.rand_float() - 0.5;
+            let y = self.rng.rand_float() * 0.5;
+            let mut rocket = RocketSpec::new(x, y, random_heading(&mut self.rng));
+            rocket.kind = RocketKind::Tiny;
+            rocket
+        };
+        (0..FRENZY_ROCKETS).map(new_rocket).collect()
+    }
+
+    // a perfectly level row of rockets across the whole screen, all heading
+    // straight down
+    fn create_wall(&self) -> Vec<RocketSpec> {
+        let new_rocket = |i| {
+            let x = -0.5 + (i as f32 + 0.5) / WALL_ROCKETS as f32;
+            let mut rocket = RocketSpec::new(x, 0.5, std::f32::consts::PI);
+            rocket.wall = true;
+            rocket
+        };
+        (0..WALL_ROCKETS).map(new_rocket).collect()
+    }
+}